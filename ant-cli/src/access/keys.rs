@@ -6,17 +6,23 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::wallet::input::get_password_input;
 use crate::wallet::load_wallet_private_key;
 use autonomi::client::registers::RegisterSecretKey;
 use autonomi::client::vault::VaultSecretKey;
-use autonomi::{get_evm_network_from_env, Wallet};
+use autonomi::{get_evm_network_from_env, RemoteSigner, Wallet};
 use color_eyre::eyre::{eyre, Context, Result};
 use color_eyre::Section;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 const SECRET_KEY_ENV: &str = "SECRET_KEY";
+const KEYSTORE_ENV: &str = "EVM_KEYSTORE";
+const KEYSTORE_PASSWORD_ENV: &str = "EVM_KEYSTORE_PASSWORD";
+const REMOTE_SIGNER_URL_ENV: &str = "EVM_REMOTE_SIGNER_URL";
+const REMOTE_SIGNER_ADDRESS_ENV: &str = "EVM_REMOTE_SIGNER_ADDRESS";
 const REGISTER_SIGNING_KEY_ENV: &str = "REGISTER_SIGNING_KEY";
 
 const REGISTER_SIGNING_KEY_FILE: &str = "register_signing_key";
@@ -31,6 +37,38 @@ pub fn load_evm_wallet_from_env() -> Result<Wallet> {
     Ok(wallet)
 }
 
+/// EVM wallet signing with a keystore file or a remote signer, if either is set in the ENV
+pub fn load_evm_signer_wallet_from_env() -> Result<Option<Wallet>> {
+    if let Ok(path) = env::var(KEYSTORE_ENV) {
+        let network = get_evm_network_from_env()?;
+        let password = env::var(KEYSTORE_PASSWORD_ENV)
+            .unwrap_or_else(|_| get_password_input("Enter password to decrypt keystore:"));
+        let wallet = Wallet::new_from_keystore(network, Path::new(&path), password)
+            .wrap_err(format!("Failed to load EVM wallet from keystore at {path}"))?;
+        return Ok(Some(wallet));
+    }
+
+    if let Ok(url) = env::var(REMOTE_SIGNER_URL_ENV) {
+        let network = get_evm_network_from_env()?;
+        let address = env::var(REMOTE_SIGNER_ADDRESS_ENV).wrap_err(eyre!(
+            "make sure you've provided the {REMOTE_SIGNER_ADDRESS_ENV} env var along with {REMOTE_SIGNER_URL_ENV}"
+        ))?;
+        let url = url
+            .parse()
+            .wrap_err(format!("Invalid remote signer URL: {url}"))?;
+        let address = address
+            .parse()
+            .wrap_err(format!("Invalid remote signer address: {address}"))?;
+        let signer = RemoteSigner::new(url, address);
+        return Ok(Some(Wallet::new_with_payment_signer(
+            network,
+            Arc::new(signer),
+        )));
+    }
+
+    Ok(None)
+}
+
 /// EVM wallet private key
 pub fn get_secret_key_from_env() -> Result<String> {
     env::var(SECRET_KEY_ENV).wrap_err(eyre!(
@@ -6,11 +6,20 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::get_progress_bar;
+use super::{get_progress_bar, ClientProgress};
+use ant_protocol::storage::PointerAddress;
 use autonomi::{
     client::{
         address::str_to_addr,
-        files::{archive::PrivateArchiveAccess, archive_public::ArchiveAddr},
+        files::{
+            archive::{Metadata, PrivateArchiveAccess},
+            archive_public::ArchiveAddr,
+            fs::DownloadError,
+            fs_public::{
+                check_dirs_and_symlinks, create_dirs_and_symlinks, download_dest,
+                restore_dir_metadata, restore_metadata,
+            },
+        },
     },
     Client,
 };
@@ -18,8 +27,22 @@ use color_eyre::{
     eyre::{eyre, Context, Result},
     Section,
 };
+use indicatif::ProgressBar;
+use std::future::Future;
 use std::path::PathBuf;
 
+/// Download the latest version of the private archive published with `file upload --name`.
+pub async fn download_named(name: &str, dest_path: &str, client: &mut Client) -> Result<()> {
+    let owner = crate::keys::get_vault_secret_key()
+        .wrap_err("Failed to load the secret key owning named archives")?;
+    let key = Client::mutable_archive_key(&owner, name);
+    let version = client.archive_version(&key).await.wrap_err(format!(
+        "Failed to fetch the latest version of archive {name}"
+    ))?;
+    info!("Downloading version {} of archive {name}", version.version);
+    download_private(name, version.archive, dest_path, client).await
+}
+
 pub async fn download(addr: &str, dest_path: &str, client: &mut Client) -> Result<()> {
     let public_address = str_to_addr(addr).ok();
     let private_address = crate::user_data::get_local_private_archive_access(addr)
@@ -47,27 +70,15 @@ async fn download_private(
         .await
         .wrap_err("Failed to fetch data from address")?;
 
-    let progress_bar = get_progress_bar(archive.iter().count() as u64)?;
-    let mut all_errs = vec![];
-    for (path, access, _meta) in archive.iter() {
-        progress_bar.println(format!("Fetching file: {path:?}..."));
-        let bytes = match client.data_get(access.clone()).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                let err = format!("Failed to fetch file {path:?}: {e}");
-                all_errs.push(err);
-                continue;
-            }
-        };
-
-        let path = PathBuf::from(dest_path).join(path);
-        let here = PathBuf::from(".");
-        let parent = path.parent().unwrap_or_else(|| &here);
-        std::fs::create_dir_all(parent)?;
-        std::fs::write(path, bytes)?;
-        progress_bar.clone().inc(1);
-    }
-    progress_bar.finish_and_clear();
+    let all_errs = download_archive(
+        client,
+        archive.iter().collect(),
+        archive.dirs().collect(),
+        archive.symlinks().collect(),
+        dest_path,
+        |client, access, dest| async move { client.file_download(access, dest).await },
+    )
+    .await?;
 
     if all_errs.is_empty() {
         info!("Successfully downloaded private data with local address: {addr}");
@@ -88,32 +99,35 @@ async fn download_public(
     dest_path: &str,
     client: &mut Client,
 ) -> Result<()> {
-    let archive = client
-        .archive_get_public(address)
-        .await
-        .wrap_err("Failed to fetch data from address")?;
-
-    let progress_bar = get_progress_bar(archive.iter().count() as u64)?;
-    let mut all_errs = vec![];
-    for (path, addr, _meta) in archive.iter() {
-        progress_bar.println(format!("Fetching file: {path:?}..."));
-        let bytes = match client.data_get_public(*addr).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                let err = format!("Failed to fetch file {path:?}: {e}");
-                all_errs.push(err);
-                continue;
+    let archive = match client.archive_get_public(address).await {
+        Ok(archive) => archive,
+        Err(err) if err.is_not_found() => {
+            info!("No archive at {addr}, trying as a named archive: {err}");
+            match client
+                .archive_get_mutable_public(PointerAddress::new(address))
+                .await
+            {
+                Ok(archive) => archive,
+                Err(named_err) => {
+                    error!("Failed to fetch archive or named archive at {addr}: {err:?}, {named_err:?}");
+                    return Err(eyre!(
+                        "Failed to fetch data from address, as an archive: {err}, as a named archive: {named_err}"
+                    ));
+                }
             }
-        };
+        }
+        Err(err) => return Err(err).wrap_err("Failed to fetch data from address"),
+    };
 
-        let path = PathBuf::from(dest_path).join(path);
-        let here = PathBuf::from(".");
-        let parent = path.parent().unwrap_or_else(|| &here);
-        std::fs::create_dir_all(parent)?;
-        std::fs::write(path, bytes)?;
-        progress_bar.clone().inc(1);
-    }
-    progress_bar.finish_and_clear();
+    let all_errs = download_archive(
+        client,
+        archive.iter().collect(),
+        archive.dirs().collect(),
+        archive.symlinks().collect(),
+        dest_path,
+        |client, addr, dest| async move { client.file_download_public(addr, dest).await },
+    )
+    .await?;
 
     if all_errs.is_empty() {
         info!("Successfully downloaded data at: {addr}");
@@ -127,3 +141,65 @@ async fn download_public(
         Err(eyre!("Errors while downloading data"))
     }
 }
+
+/// Stream the files of an archive into `dest_path` with `download_file`, then create its
+/// directories and symlinks and restore their metadata. Archive paths that would lead outside
+/// `dest_path` are refused. Returns the errors of the files that failed to download.
+async fn download_archive<A, Fut>(
+    client: &mut Client,
+    files: Vec<(&PathBuf, &A, &Metadata)>,
+    dirs: Vec<(&PathBuf, &Metadata)>,
+    symlinks: Vec<(&PathBuf, &PathBuf, &Metadata)>,
+    dest_path: &str,
+    download_file: impl Fn(Client, A, PathBuf) -> Fut,
+) -> Result<Vec<String>>
+where
+    A: Clone,
+    Fut: Future<Output = Result<(), DownloadError>>,
+{
+    let dest_path = PathBuf::from(dest_path);
+    check_dirs_and_symlinks(dirs.iter().copied(), symlinks.iter().copied())
+        .wrap_err("Refusing to download the archive")?;
+
+    let progress_bar = get_progress_bar(files.len() as u64)?;
+    let progress_task = show_download_progress(client, &progress_bar);
+    let mut all_errs = vec![];
+    for (path, access, meta) in files {
+        progress_bar.println(format!("Fetching file: {path:?}..."));
+        let dest = match download_dest(&dest_path, path) {
+            Ok(dest) => dest,
+            Err(e) => {
+                all_errs.push(format!("Failed to fetch file {path:?}: {e}"));
+                continue;
+            }
+        };
+        if let Err(e) = download_file(client.clone(), access.clone(), dest.clone()).await {
+            all_errs.push(format!("Failed to fetch file {path:?}: {e}"));
+            continue;
+        }
+        restore_metadata(&dest, meta);
+        progress_bar.inc(1);
+    }
+    progress_task.abort();
+    progress_bar.finish_and_clear();
+
+    create_dirs_and_symlinks(&dest_path, dirs.iter().copied(), symlinks.iter().copied())
+        .wrap_err("Failed to create the directories and symlinks of the archive")?;
+    restore_dir_metadata(&dest_path, dirs.into_iter());
+    Ok(all_errs)
+}
+
+/// Show the chunks fetched so far on the progress bar, until the returned task is aborted.
+fn show_download_progress(
+    client: &mut Client,
+    progress_bar: &ProgressBar,
+) -> tokio::task::JoinHandle<()> {
+    let mut event_receiver = client.enable_client_events();
+    let progress_bar = progress_bar.clone();
+    tokio::spawn(async move {
+        let mut progress = ClientProgress::default();
+        while let Some(event) = event_receiver.recv().await {
+            progress.update(&event, &progress_bar);
+        }
+    })
+}
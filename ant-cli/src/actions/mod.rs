@@ -11,6 +11,6 @@ mod download;
 mod progress_bar;
 
 pub use connect::connect_to_network;
-pub use download::download;
+pub use download::{download, download_named};
 
-pub use progress_bar::get_progress_bar;
+pub use progress_bar::{get_progress_bar, get_spinner, ClientProgress};
@@ -6,17 +6,82 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use autonomi::client::ClientEvent;
 use color_eyre::eyre::Result;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
 use std::time::Duration;
 
 pub fn get_progress_bar(length: u64) -> Result<ProgressBar> {
     let progress_bar = ProgressBar::new(length);
     progress_bar.set_style(
         ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len}")?
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
+            )?
             .progress_chars("#>-"),
     );
     progress_bar.enable_steady_tick(Duration::from_millis(100));
     Ok(progress_bar)
 }
+
+/// A spinner for operations of unknown length, showing their progress as its message.
+pub fn get_spinner() -> Result<ProgressBar> {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner().template("{spinner:.green} [{elapsed_precise}] {msg}")?,
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    Ok(spinner)
+}
+
+/// Progress reported by the client events, shown as the message of a progress bar.
+#[derive(Debug, Default)]
+pub struct ClientProgress {
+    quoted: usize,
+    payments: usize,
+    stored: usize,
+    fetched: usize,
+    files: usize,
+}
+
+impl ClientProgress {
+    /// Count `event` and show the progress so far on `progress_bar`.
+    pub fn update(&mut self, event: &ClientEvent, progress_bar: &ProgressBar) {
+        match event {
+            ClientEvent::ChunkQuoted(_) => self.quoted += 1,
+            ClientEvent::PaymentSent { .. } => self.payments += 1,
+            ClientEvent::ChunkStored(_) => self.stored += 1,
+            ClientEvent::ChunkFetched(_) => self.fetched += 1,
+            ClientEvent::FileCompleted(_) => self.files += 1,
+            ClientEvent::RetryScheduled {
+                chunks,
+                attempt,
+                max_attempts,
+            } => progress_bar.println(format!(
+                "Retrying {chunks} chunks (attempt {attempt}/{max_attempts})"
+            )),
+            ClientEvent::Error(err) => progress_bar.println(format!("Error: {err}")),
+            _ => {}
+        }
+        progress_bar.set_message(self.to_string());
+    }
+}
+
+impl fmt::Display for ClientProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = [
+            (self.quoted, "chunks quoted"),
+            (self.payments, "payments sent"),
+            (self.stored, "chunks stored"),
+            (self.fetched, "chunks fetched"),
+            (self.files, "files done"),
+        ];
+        let parts: Vec<_> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, what)| format!("{count} {what}"))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
@@ -7,13 +7,17 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod file;
+#[cfg(feature = "mount")]
+mod mount;
 mod register;
 mod vault;
 mod wallet;
 
 use crate::opt::Opt;
+use autonomi::client::AttoTokens;
 use clap::{error::ErrorKind, CommandFactory as _, Subcommand};
 use color_eyre::Result;
+use std::path::PathBuf;
 
 #[derive(Subcommand, Debug)]
 pub enum SubCmd {
@@ -40,6 +44,24 @@ pub enum SubCmd {
         #[command(subcommand)]
         command: WalletCmd,
     },
+
+    /// Mount an archive as a read-only filesystem, until it is unmounted or Ctrl-C is pressed.
+    /// File content is fetched as it is read, and kept in the chunk cache.
+    #[cfg(feature = "mount")]
+    Mount {
+        /// The address of the archive to mount, as given to `file download`.
+        addr: String,
+        /// The empty directory to mount the archive on.
+        mountpoint: PathBuf,
+        /// Mount the latest version of your private archive published with `file upload --name`,
+        /// taking `addr` as its name.
+        #[arg(long)]
+        named: bool,
+        /// Size in MiB of the cache of chunks read from the mounted files, kept in the client data
+        /// directory. 0 disables the cache, so every read fetches the chunks it covers.
+        #[arg(long, value_name = "MIB", default_value_t = 1024)]
+        chunk_cache: u64,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -57,6 +79,40 @@ pub enum FileCmd {
         /// Upload the file as public. Everyone can see public data on the Network.
         #[arg(short, long)]
         public: bool,
+        /// Resume a previously failed upload, skipping chunks already stored and
+        /// reusing payments that have not expired yet.
+        #[arg(long)]
+        resume: bool,
+        /// Publish the upload as the latest version of the archive with this name.
+        /// The archive keeps a stable address across uploads, derived from the name and your secret key.
+        #[arg(long, conflicts_with = "receipt")]
+        name: Option<String>,
+        /// Pay with the transactions sent for a payment request instead of the wallet:
+        /// the payment result file listing the transaction hashes.
+        #[arg(long, requires = "payment_request")]
+        receipt: Option<PathBuf>,
+        /// The payment request file from `file prepare-payment` the receipt pays for.
+        #[arg(long, requires = "receipt")]
+        payment_request: Option<PathBuf>,
+        /// Abort before paying anything if the upload would cost more than this many tokens in total.
+        ///
+        /// The estimate encrypts and quotes the whole upload once more before uploading it,
+        /// which takes about as long as the encryption and quoting of the upload itself.
+        #[arg(long, conflicts_with = "receipt")]
+        max_cost: Option<AttoTokens>,
+    },
+
+    /// Prepare the payment for uploading a file, to be signed and sent with a wallet elsewhere.
+    /// Writes a payment request file with the chosen quotes and the transactions to send.
+    PreparePayment {
+        /// The file to pay for.
+        file: String,
+        /// Pay for uploading the file as public.
+        #[arg(short, long)]
+        public: bool,
+        /// Where to write the payment request.
+        #[arg(short, long, default_value = "payment-request.json")]
+        output: PathBuf,
     },
 
     /// Download a file from the given address.
@@ -65,6 +121,28 @@ pub enum FileCmd {
         addr: String,
         /// The destination file path.
         dest_file: String,
+        /// Download the latest version of your private archive published with `file upload --name`,
+        /// taking `addr` as its name.
+        #[arg(long)]
+        named: bool,
+        /// Keep the downloaded chunks in a cache of up to this many MiB in the client data directory,
+        /// so downloading them again does not go to the network.
+        #[arg(long, value_name = "MIB")]
+        chunk_cache: Option<u64>,
+    },
+
+    /// Check that every chunk of the files at the given address is still stored on the Network.
+    /// Exits with an error if any chunk is missing.
+    Verify {
+        /// The address of the files to check.
+        addr: String,
+        /// Also challenge the nodes holding each chunk to prove they store it.
+        #[arg(long)]
+        prove: bool,
+        /// Upload missing chunks again, paying for them, from a local copy of the files in this directory,
+        /// as written by `file download`.
+        #[arg(long)]
+        repair: Option<PathBuf>,
     },
 
     /// List previous uploads
@@ -189,10 +267,49 @@ pub async fn handle_subcommand(opt: Opt) -> Result<()> {
     match cmd {
         Some(SubCmd::File { command }) => match command {
             FileCmd::Cost { file } => file::cost(&file, peers.await?).await,
-            FileCmd::Upload { file, public } => file::upload(&file, public, peers.await?).await,
-            FileCmd::Download { addr, dest_file } => {
-                file::download(&addr, &dest_file, peers.await?).await
+            FileCmd::Upload {
+                file,
+                public,
+                resume,
+                name,
+                receipt,
+                payment_request,
+                max_cost,
+            } => {
+                let paid_with =
+                    receipt
+                        .zip(payment_request)
+                        .map(|(payment_result, payment_request)| file::PaidWith {
+                            payment_request,
+                            payment_result,
+                        });
+                file::upload(
+                    &file,
+                    public,
+                    resume,
+                    name,
+                    paid_with,
+                    max_cost,
+                    peers.await?,
+                )
+                .await
             }
+            FileCmd::PreparePayment {
+                file,
+                public,
+                output,
+            } => file::prepare_payment(&file, public, &output, peers.await?).await,
+            FileCmd::Download {
+                addr,
+                dest_file,
+                named,
+                chunk_cache,
+            } => file::download(&addr, &dest_file, named, chunk_cache, peers.await?).await,
+            FileCmd::Verify {
+                addr,
+                prove,
+                repair,
+            } => file::verify(&addr, prove, repair, peers.await?).await,
             FileCmd::List => file::list(),
         },
         Some(SubCmd::Register { command }) => match command {
@@ -230,6 +347,13 @@ pub async fn handle_subcommand(opt: Opt) -> Result<()> {
             WalletCmd::Export => wallet::export(),
             WalletCmd::Balance => wallet::balance().await,
         },
+        #[cfg(feature = "mount")]
+        Some(SubCmd::Mount {
+            addr,
+            mountpoint,
+            named,
+            chunk_cache,
+        }) => mount::mount(&addr, &mountpoint, named, chunk_cache, peers.await?).await,
         None => {
             // If no subcommand is given, default to clap's error behaviour.
             Opt::command()
@@ -6,14 +6,23 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::access::data_dir::get_client_data_dir_path;
 use crate::utils::collect_upload_summary;
 use crate::wallet::load_wallet;
-use autonomi::client::address::addr_to_str;
-use autonomi::Multiaddr;
-use color_eyre::eyre::Context;
+use ant_protocol::storage::{DataTypes, PointerAddress};
+use autonomi::client::address::{addr_to_str, str_to_addr};
+use autonomi::client::budget::CostLimits;
+use autonomi::client::data::verify::VerifyMode;
+use autonomi::client::data::PayError;
+use autonomi::client::external_signer::{PaymentRequest, PaymentResult};
+use autonomi::client::files::sync::SyncReport;
+use autonomi::client::files::verify::ArchiveHealth;
+use autonomi::client::{AttoTokens, UploadSummary};
+use autonomi::{Client, Multiaddr};
 use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Context};
 use color_eyre::Section;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub async fn cost(file: &str, peers: Vec<Multiaddr>) -> Result<()> {
     let client = crate::actions::connect_to_network(peers).await?;
@@ -31,9 +40,79 @@ pub async fn cost(file: &str, peers: Vec<Multiaddr>) -> Result<()> {
     Ok(())
 }
 
-pub async fn upload(file: &str, public: bool, peers: Vec<Multiaddr>) -> Result<()> {
+/// Paths of the payment request and payment result files of an offline payment.
+pub struct PaidWith {
+    pub payment_request: PathBuf,
+    pub payment_result: PathBuf,
+}
+
+pub async fn prepare_payment(
+    file: &str,
+    public: bool,
+    output: &Path,
+    peers: Vec<Multiaddr>,
+) -> Result<()> {
+    let client = crate::actions::connect_to_network(peers).await?;
+
+    println!("Encrypting {file} to find the chunks to pay for...");
+    let dir_path = PathBuf::from(file);
+    let content_addrs = if public {
+        client.dir_and_archive_content_addrs_public(dir_path).await
+    } else {
+        client.dir_and_archive_content_addrs(dir_path).await
+    }
+    .wrap_err("Failed to encrypt file")?;
+
+    println!("Getting quotes for {} chunks...", content_addrs.len());
+    let request = client
+        .prepare_payment(DataTypes::Chunk, content_addrs.into_iter())
+        .await
+        .wrap_err("Failed to prepare payment")?;
+    std::fs::write(output, request.to_json()?)
+        .wrap_err(format!("Failed to write payment request to {output:?}"))?;
+    info!(
+        "Prepared payment of {} for {file} in {output:?}",
+        request.total_amount
+    );
+
+    println!("Payment request written to: {}", output.display());
+    println!(
+        "Total cost: {} AttoTokens, in {} payment transaction(s)",
+        request.total_amount,
+        request.payments.len()
+    );
+    println!(
+        "Once the transactions are sent, upload with: ant file upload {file}{} --payment-request {} --receipt <payment result file>",
+        if public { " --public" } else { "" },
+        output.display()
+    );
+    Ok(())
+}
+
+pub async fn upload(
+    file: &str,
+    public: bool,
+    resume: bool,
+    archive_name: Option<String>,
+    paid_with: Option<PaidWith>,
+    max_cost: Option<AttoTokens>,
+    peers: Vec<Multiaddr>,
+) -> Result<()> {
+    if let Some(paid_with) = paid_with {
+        return upload_with_receipt(file, public, resume, paid_with, peers).await;
+    }
+
     let wallet = load_wallet()?;
     let mut client = crate::actions::connect_to_network(peers).await?;
+    let journal_dir = get_client_data_dir_path()?.join("upload_journal");
+    client.enable_upload_journal(journal_dir, resume);
+    if let Some(max_cost) = max_cost {
+        client.set_cost_limits(CostLimits {
+            max_total: Some(max_cost),
+            ..Default::default()
+        });
+        reserve_upload_cost(&client, file, public).await?;
+    }
     let event_receiver = client.enable_client_events();
     let (upload_summary_thread, upload_completed_tx) = collect_upload_summary(event_receiver);
 
@@ -44,30 +123,237 @@ pub async fn upload(file: &str, public: bool, peers: Vec<Multiaddr>) -> Result<(
     );
 
     let dir_path = PathBuf::from(file);
-    let name = dir_path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or(file.to_string());
+    let name = archive_name.clone().unwrap_or_else(|| {
+        dir_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or(file.to_string())
+    });
 
     // upload dir
     let local_addr;
-    let archive = if public {
+    let archive = if let Some(archive_name) = archive_name {
+        let owner = crate::keys::get_vault_secret_key()
+            .wrap_err("Failed to load the secret key owning named archives")?;
+        let key = Client::mutable_archive_key(&owner, &archive_name);
+
+        if public {
+            let address = PointerAddress::from_owner(key.public_key());
+            let archive = match client.archive_get_mutable_public(address).await {
+                Ok(previous) => {
+                    let (archive, report) = client
+                        .dir_sync_public(dir_path, &previous, &wallet)
+                        .await
+                        .wrap_err("Failed to sync file")
+                        .with_suggestion(resume_suggestion)?;
+                    print_sync_report(&report);
+                    archive
+                }
+                Err(err) => {
+                    info!(
+                        "No previous version of {archive_name} found, uploading everything: {err}"
+                    );
+                    client
+                        .dir_upload_public(dir_path, &wallet)
+                        .await
+                        .wrap_err("Failed to upload file")
+                        .with_suggestion(resume_suggestion)?
+                }
+            };
+            let version = client
+                .archive_update_public(&archive, &key, &wallet)
+                .await
+                .wrap_err(format!("Failed to publish archive {archive_name}"))?;
+            println!("Published version {} of {archive_name}", version.version);
+            info!("Published version {version:?} of public archive {archive_name}");
+
+            local_addr = addr_to_str(*address.xorname());
+            local_addr.clone()
+        } else {
+            let archive = match client.archive_get_mutable(&key).await {
+                Ok(previous) => {
+                    let (archive, report) = client
+                        .dir_sync(dir_path, &previous, &wallet)
+                        .await
+                        .wrap_err("Failed to sync dir")
+                        .with_suggestion(resume_suggestion)?;
+                    print_sync_report(&report);
+                    archive
+                }
+                Err(err) => {
+                    info!(
+                        "No previous version of {archive_name} found, uploading everything: {err}"
+                    );
+                    client
+                        .dir_upload(dir_path, &wallet)
+                        .await
+                        .wrap_err("Failed to upload dir")
+                        .with_suggestion(resume_suggestion)?
+                }
+            };
+            let version = client
+                .archive_update(&archive, &key, wallet.into())
+                .await
+                .wrap_err(format!("Failed to publish archive {archive_name}"))?;
+            println!("Published version {} of {archive_name}", version.version);
+            info!(
+                "Published version {} of private archive {archive_name}",
+                version.version
+            );
+
+            local_addr = version.archive.address();
+            version.archive.to_hex()
+        }
+    } else if public {
         let xor_name = client
             .dir_and_archive_upload_public(dir_path, &wallet)
             .await
-            .wrap_err("Failed to upload file")?;
+            .wrap_err("Failed to upload file")
+            .with_suggestion(resume_suggestion)?;
         local_addr = addr_to_str(xor_name);
         local_addr.clone()
     } else {
         let private_data_access = client
             .dir_and_archive_upload(dir_path, &wallet)
             .await
-            .wrap_err("Failed to upload dir and archive")?;
+            .wrap_err("Failed to upload dir and archive")
+            .with_suggestion(resume_suggestion)?;
 
         local_addr = private_data_access.address();
         private_data_access.to_hex()
     };
 
+    finish_upload(
+        file,
+        public,
+        &name,
+        archive,
+        local_addr,
+        upload_summary_thread,
+        upload_completed_tx,
+    )
+    .await
+}
+
+/// Quote the whole upload and reserve its cost under `--max-cost`, so an upload that would cost
+/// more is refused before anything is paid for instead of halfway through.
+///
+/// The upload encrypts and quotes the data again itself, so this doubles the time spent on both.
+async fn reserve_upload_cost(client: &Client, file: &str, public: bool) -> Result<()> {
+    println!("Encrypting {file} to estimate the upload cost...");
+    let dir_path = PathBuf::from(file);
+    let content_addrs = if public {
+        client.dir_and_archive_content_addrs_public(dir_path).await
+    } else {
+        client.dir_and_archive_content_addrs(dir_path).await
+    }
+    .wrap_err("Failed to encrypt file")?;
+
+    println!("Getting quotes for {} chunks...", content_addrs.len());
+    match client
+        .reserve_cost(DataTypes::Chunk, content_addrs.into_iter())
+        .await
+    {
+        Ok(estimate) => {
+            println!("Estimated cost: {estimate}");
+            Ok(())
+        }
+        Err(PayError::CostLimitExceeded(estimate)) => {
+            println!("Estimated cost: {estimate}");
+            Err(eyre!(
+                "The upload would cost more than the maximum cost, nothing was paid for"
+            ))
+            .with_suggestion(|| "Raise --max-cost or upload less data")
+        }
+        Err(err) => Err(err).wrap_err("Failed to estimate the upload cost"),
+    }
+}
+
+/// Upload with the payments made offline for a payment request from [`prepare_payment`].
+async fn upload_with_receipt(
+    file: &str,
+    public: bool,
+    resume: bool,
+    paid_with: PaidWith,
+    peers: Vec<Multiaddr>,
+) -> Result<()> {
+    let request = std::fs::read_to_string(&paid_with.payment_request)
+        .wrap_err("Failed to read payment request")?;
+    let request = PaymentRequest::from_json(&request).wrap_err("Invalid payment request")?;
+    let result = std::fs::read_to_string(&paid_with.payment_result)
+        .wrap_err("Failed to read payment result")?;
+    let result = PaymentResult::from_json(&result).wrap_err("Invalid payment result")?;
+    let receipt = request
+        .receipt(&result)
+        .wrap_err("Failed to form receipt from payment result")?;
+    info!(
+        "Uploading {file} with a receipt for {} of {} content addresses",
+        receipt.len(),
+        request.content_addrs.len()
+    );
+
+    let mut client = crate::actions::connect_to_network(peers).await?;
+    let journal_dir = get_client_data_dir_path()?.join("upload_journal");
+    client.enable_upload_journal(journal_dir, resume);
+    let event_receiver = client.enable_client_events();
+    let (upload_summary_thread, upload_completed_tx) = collect_upload_summary(event_receiver);
+
+    println!("Uploading data to network...");
+    let dir_path = PathBuf::from(file);
+    let name = dir_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(file.to_string());
+
+    let (archive, local_addr) = if public {
+        let archive = client
+            .dir_upload_public_with_payment(dir_path, receipt.clone().into())
+            .await
+            .wrap_err("Failed to upload file")
+            .with_suggestion(prepared_upload_suggestion)?;
+        let archive_addr = client
+            .data_put_public(archive.to_bytes()?, receipt.into())
+            .await
+            .wrap_err("Failed to upload archive")
+            .with_suggestion(prepared_upload_suggestion)?;
+        let local_addr = addr_to_str(archive_addr);
+        (local_addr.clone(), local_addr)
+    } else {
+        let archive = client
+            .dir_upload_with_payment(dir_path, receipt.clone().into())
+            .await
+            .wrap_err("Failed to upload dir")
+            .with_suggestion(prepared_upload_suggestion)?;
+        let private_data_access = client
+            .archive_put(&archive, receipt.into())
+            .await
+            .wrap_err("Failed to upload archive")
+            .with_suggestion(prepared_upload_suggestion)?;
+        (private_data_access.to_hex(), private_data_access.address())
+    };
+
+    finish_upload(
+        file,
+        public,
+        &name,
+        archive,
+        local_addr,
+        upload_summary_thread,
+        upload_completed_tx,
+    )
+    .await
+}
+
+/// Report the summary of an upload and save its address to the local user data.
+async fn finish_upload(
+    file: &str,
+    public: bool,
+    name: &str,
+    archive: String,
+    local_addr: String,
+    upload_summary_thread: tokio::task::JoinHandle<UploadSummary>,
+    upload_completed_tx: tokio::sync::oneshot::Sender<()>,
+) -> Result<()> {
     // wait for upload to complete
     if let Err(e) = upload_completed_tx.send(()) {
         error!("Failed to send upload completed event: {e:?}");
@@ -93,9 +379,9 @@ pub async fn upload(file: &str, public: bool, peers: Vec<Multiaddr>) -> Result<(
 
     // save to local user data
     let writer = if public {
-        crate::user_data::write_local_public_file_archive(archive, &name)
+        crate::user_data::write_local_public_file_archive(archive, name)
     } else {
-        crate::user_data::write_local_private_file_archive(archive, local_addr, &name)
+        crate::user_data::write_local_private_file_archive(archive, local_addr, name)
     };
     writer
         .wrap_err("Failed to save file to local user data")
@@ -105,9 +391,155 @@ pub async fn upload(file: &str, public: bool, peers: Vec<Multiaddr>) -> Result<(
     Ok(())
 }
 
-pub async fn download(addr: &str, dest_path: &str, peers: Vec<Multiaddr>) -> Result<()> {
+fn print_sync_report(report: &SyncReport) {
+    println!(
+        "Compared with the previous version: {} added, {} modified, {} removed, {} unchanged",
+        report.added.len(),
+        report.modified.len(),
+        report.removed.len(),
+        report.unchanged.len()
+    );
+    info!("Sync report: {report:?}");
+}
+
+fn prepared_upload_suggestion() -> &'static str {
+    "Only what the payment request was prepared for is paid, prepare a new one if the file changed since"
+}
+
+fn resume_suggestion() -> &'static str {
+    "Run the same command with `--resume` to retry without paying again for what was already paid"
+}
+
+pub async fn download(
+    addr: &str,
+    dest_path: &str,
+    named: bool,
+    chunk_cache: Option<u64>,
+    peers: Vec<Multiaddr>,
+) -> Result<()> {
     let mut client = crate::actions::connect_to_network(peers).await?;
-    crate::actions::download(addr, dest_path, &mut client).await
+    if let Some(size_mib) = chunk_cache {
+        enable_chunk_cache(&mut client, size_mib)?;
+    }
+    if named {
+        crate::actions::download_named(addr, dest_path, &mut client).await
+    } else {
+        crate::actions::download(addr, dest_path, &mut client).await
+    }
+}
+
+/// Keep fetched chunks in the chunk cache of the client data directory, kept under `size_mib` MiB.
+pub(super) fn enable_chunk_cache(client: &mut Client, size_mib: u64) -> Result<()> {
+    let cache_dir = get_client_data_dir_path()?.join("chunk_cache");
+    let max_size = size_mib.saturating_mul(1024 * 1024);
+    if let Err(err) = client.enable_chunk_cache(cache_dir, max_size) {
+        warn!("Failed to enable the chunk cache, continuing without it: {err:?}");
+    }
+    Ok(())
+}
+
+pub async fn verify(
+    addr: &str,
+    prove: bool,
+    repair_from: Option<PathBuf>,
+    peers: Vec<Multiaddr>,
+) -> Result<()> {
+    let public_address = str_to_addr(addr).ok();
+    let private_address = crate::user_data::get_local_private_archive_access(addr)
+        .inspect_err(|e| error!("Failed to get private archive access: {e}"))
+        .ok();
+    if public_address.is_none() && private_address.is_none() {
+        return Err(eyre!("Failed to parse data address {addr}"))
+            .with_suggestion(|| "Try the `file list` command to get addresses you have access to");
+    }
+
+    let client = crate::actions::connect_to_network(peers).await?;
+    let mode = if prove {
+        VerifyMode::Prove
+    } else {
+        VerifyMode::Fetch
+    };
+
+    println!("Verifying the chunks of {addr}...");
+    let health = match (public_address, &private_address) {
+        (Some(archive_addr), _) => client.dir_verify_public(archive_addr, mode).await,
+        (_, Some(archive_access)) => client.dir_verify(archive_access.clone(), mode).await,
+        (None, None) => unreachable!("checked above"),
+    }
+    .wrap_err("Failed to verify the files")?;
+    info!("Integrity report for {addr}: {health:?}");
+    print_health(&health);
+
+    if health.is_healthy() {
+        println!("✅ All {} files are fully stored", health.files.len());
+        return Ok(());
+    }
+    let Some(from) = repair_from else {
+        return Err(eyre!("Some chunks of {addr} are missing or unproven")).with_suggestion(|| {
+            "Run the same command with `--repair <DIR>` to upload the missing chunks again from a local copy"
+        });
+    };
+
+    let wallet = load_wallet()?;
+    println!("Repairing from the local copy in {from:?}...");
+    let summary = match (public_address, private_address) {
+        (Some(archive_addr), _) => {
+            client
+                .dir_repair_public(archive_addr, &health, from, wallet.into())
+                .await
+        }
+        (_, Some(archive_access)) => {
+            client
+                .dir_repair(archive_access, &health, from, wallet.into())
+                .await
+        }
+        (None, None) => unreachable!("checked above"),
+    }
+    .wrap_err("Failed to repair the files")?;
+    info!("Repair of {addr}: {summary:?}");
+
+    println!(
+        "Repaired {} files by uploading {} chunks for {} tokens",
+        summary.files_repaired,
+        summary.chunks_uploaded,
+        AttoTokens::from_atto(summary.tokens_spent)
+    );
+    if !summary.unrepairable.is_empty() {
+        return Err(eyre!(
+            "{} files could not be repaired: {:?}",
+            summary.unrepairable.len(),
+            summary.unrepairable
+        ))
+        .with_suggestion(|| {
+            "The local copy of these files is missing or differs from the uploaded one"
+        });
+    }
+    Ok(())
+}
+
+fn print_health(health: &ArchiveHealth) {
+    if !health.archive.is_healthy() {
+        println!(
+            "The archive itself has {} missing and {} unproven chunks",
+            health.archive.missing.len(),
+            health.archive.unproven.len()
+        );
+    }
+    for (path, file_health) in health.unhealthy_files() {
+        println!(
+            "{path:?}: {} missing and {} unproven of {} chunks checked{}",
+            file_health.missing.len(),
+            file_health.unproven.len(),
+            file_health.chunks_checked,
+            if file_health.data_map_missing {
+                ", the rest could not be listed"
+            } else {
+                ""
+            }
+        );
+    }
+    let healthy = health.files.len() - health.unhealthy_files().count();
+    println!("{healthy} of {} files are fully stored", health.files.len());
 }
 
 pub fn list() -> Result<()> {
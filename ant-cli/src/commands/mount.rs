@@ -0,0 +1,295 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use ant_protocol::storage::PointerAddress;
+use autonomi::client::address::str_to_addr;
+use autonomi::client::files::mount::{InodeEntry, InodeKind, InodeTable, OpenFile};
+use autonomi::{Client, Multiaddr};
+use color_eyre::eyre::{Context, Result};
+use color_eyre::Section;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, Request, Session,
+};
+use libc::{EBADF, EIO, EISDIR, ENOENT, ENOTDIR};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::runtime::Handle;
+
+/// How long the kernel may cache attributes and lookups. Archives never change once mounted.
+const TTL: Duration = Duration::from_secs(3600);
+
+/// Block size reported to the kernel, the size of the chunks data is split in.
+const BLOCK_SIZE: u32 = 1024 * 1024;
+
+pub async fn mount(
+    addr: &str,
+    mountpoint: &Path,
+    named: bool,
+    chunk_cache: u64,
+    peers: Vec<Multiaddr>,
+) -> Result<()> {
+    let mut client = crate::actions::connect_to_network(peers).await?;
+    if chunk_cache > 0 {
+        super::file::enable_chunk_cache(&mut client, chunk_cache)?;
+    }
+
+    println!("Fetching archive...");
+    let table = if named {
+        inode_table_of_named_archive(addr, &client).await?
+    } else {
+        inode_table_of_archive(addr, &client).await?
+    };
+    info!(
+        "Mounting archive {addr} with {} entries at {mountpoint:?}",
+        table.len()
+    );
+
+    let filesystem = ArchiveFs {
+        client,
+        table,
+        runtime: Handle::current(),
+        open_files: HashMap::new(),
+        next_file_handle: 1,
+    };
+    let options = [
+        MountOption::RO,
+        MountOption::FSName(format!("autonomi:{addr}")),
+        MountOption::Subtype("autonomi".to_string()),
+    ];
+    let mut session = Session::new(filesystem, mountpoint, &options)
+        .wrap_err(format!("Failed to mount the archive at {mountpoint:?}"))
+        .with_suggestion(|| "Mounting needs FUSE, with `fusermount3` or `fusermount` installed")?;
+    let mut unmounter = session.unmount_callable();
+    println!("Mounted {addr} at {mountpoint:?}, unmount it or press Ctrl-C to stop");
+
+    let mut running = tokio::task::spawn_blocking(move || session.run());
+    let result = tokio::select! {
+        result = &mut running => result,
+        _ = tokio::signal::ctrl_c() => {
+            info!("Unmounting {mountpoint:?} on Ctrl-C");
+            unmounter.unmount().wrap_err("Failed to unmount the archive")?;
+            running.await
+        }
+    };
+    result
+        .wrap_err("The filesystem thread panicked")?
+        .wrap_err("Failed to serve the mounted archive")?;
+    println!("Unmounted {mountpoint:?}");
+    Ok(())
+}
+
+/// Build the inode table of a public archive, of a named public archive, or of a private archive
+/// from the local user data, like `file download` resolves addresses.
+async fn inode_table_of_archive(addr: &str, client: &Client) -> Result<InodeTable> {
+    if let Ok(address) = str_to_addr(addr) {
+        let archive = match client.archive_get_public(address).await {
+            Ok(archive) => archive,
+            Err(err) if err.is_not_found() => client
+                .archive_get_mutable_public(PointerAddress::new(address))
+                .await
+                .wrap_err(format!("No archive or named archive at {addr}"))?,
+            Err(err) => return Err(err).wrap_err("Failed to fetch archive from address"),
+        };
+        return Ok(InodeTable::from_public_archive(&archive));
+    }
+
+    let access = crate::user_data::get_local_private_archive_access(addr)
+        .wrap_err(format!("Failed to parse archive address {addr}"))
+        .with_suggestion(|| "Try the `file list` command to get addresses you have access to")?;
+    let archive = client
+        .archive_get(access)
+        .await
+        .wrap_err("Failed to fetch archive from address")?;
+    Ok(InodeTable::from_private_archive(&archive))
+}
+
+/// Build the inode table of the latest version of the private archive published with
+/// `file upload --name`.
+async fn inode_table_of_named_archive(name: &str, client: &Client) -> Result<InodeTable> {
+    let owner = crate::keys::get_vault_secret_key()
+        .wrap_err("Failed to load the secret key owning named archives")?;
+    let key = Client::mutable_archive_key(&owner, name);
+    let version = client.archive_version(&key).await.wrap_err(format!(
+        "Failed to fetch the latest version of archive {name}"
+    ))?;
+    let archive = client
+        .archive_get(version.archive)
+        .await
+        .wrap_err(format!("Failed to fetch archive {name}"))?;
+    Ok(InodeTable::from_private_archive(&archive))
+}
+
+/// A read-only FUSE filesystem serving an archive: entries come from the inode table, file
+/// content is fetched from the network as it is read.
+struct ArchiveFs {
+    client: Client,
+    table: InodeTable,
+    /// The runtime the reads run on, the FUSE callbacks block on them.
+    runtime: Handle,
+    open_files: HashMap<u64, OpenFile>,
+    next_file_handle: u64,
+}
+
+impl ArchiveFs {
+    fn attr(&self, entry: &InodeEntry, req: &Request<'_>) -> FileAttr {
+        let (kind, nlink) = match entry.kind {
+            InodeKind::File(_) => (FileType::RegularFile, 1),
+            InodeKind::Dir => (FileType::Directory, 2),
+            InodeKind::Symlink(_) => (FileType::Symlink, 1),
+        };
+        let size = entry.size();
+        let modified = UNIX_EPOCH + Duration::from_secs(entry.meta.modified);
+        FileAttr {
+            ino: entry.inode,
+            size,
+            blocks: size.div_ceil(512),
+            atime: modified,
+            mtime: modified,
+            ctime: modified,
+            crtime: UNIX_EPOCH + Duration::from_secs(entry.meta.created),
+            kind,
+            perm: entry.permissions(),
+            nlink,
+            uid: req.uid(),
+            gid: req.gid(),
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+}
+
+impl Filesystem for ArchiveFs {
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.table.lookup(parent, name) {
+            Some(entry) => reply.entry(&TTL, &self.attr(entry, req), 0),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.table.get(ino) {
+            Some(entry) => reply.attr(&TTL, &self.attr(entry, req)),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        match self.table.get(ino).map(|entry| &entry.kind) {
+            Some(InodeKind::Symlink(target)) => reply.data(target.as_os_str().as_bytes()),
+            Some(_) => reply.error(libc::EINVAL),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        let data = match self.table.get(ino).map(|entry| &entry.kind) {
+            Some(InodeKind::File(data)) => data,
+            Some(_) => return reply.error(EISDIR),
+            None => return reply.error(ENOENT),
+        };
+        match self.runtime.block_on(self.client.mount_open(data)) {
+            Ok(file) => {
+                let fh = self.next_file_handle;
+                self.next_file_handle += 1;
+                self.open_files.insert(fh, file);
+                reply.opened(fh, 0);
+            }
+            Err(err) => {
+                error!("Failed to open archived file {ino}: {err:?}");
+                reply.error(EIO);
+            }
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(file) = self.open_files.get(&fh) else {
+            return reply.error(EBADF);
+        };
+        let offset = offset.max(0) as u64;
+        match self
+            .runtime
+            .block_on(self.client.mount_read(file, offset, size))
+        {
+            Ok(bytes) => reply.data(&bytes),
+            Err(err) => {
+                error!("Failed to read {size} bytes at {offset} of archived file {ino}: {err:?}");
+                reply.error(EIO);
+            }
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.open_files.remove(&fh);
+        reply.ok();
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(dir) = self.table.get(ino) else {
+            return reply.error(ENOENT);
+        };
+        if dir.kind != InodeKind::Dir {
+            return reply.error(ENOTDIR);
+        }
+
+        let dots = [
+            (dir.inode, FileType::Directory, OsStr::new(".")),
+            (dir.parent, FileType::Directory, OsStr::new("..")),
+        ];
+        let children = self.table.children(ino).map(|entry| {
+            let kind = match entry.kind {
+                InodeKind::File(_) => FileType::RegularFile,
+                InodeKind::Dir => FileType::Directory,
+                InodeKind::Symlink(_) => FileType::Symlink,
+            };
+            (entry.inode, kind, entry.name.as_os_str())
+        });
+        // the offset of an entry is the one to resume listing from after it
+        for (index, (inode, kind, name)) in dots
+            .into_iter()
+            .chain(children)
+            .enumerate()
+            .skip(offset.max(0) as usize)
+        {
+            if reply.add(inode, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
@@ -6,9 +6,11 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::actions::{get_spinner, ClientProgress};
 use autonomi::client::{Amount, ClientEvent, UploadSummary};
+use indicatif::ProgressBar;
 
-/// Collects upload summary from the event receiver.
+/// Collects upload summary from the event receiver, showing the progress of the upload on a spinner.
 /// Send a signal to the returned sender to stop collecting and to return the result via the join handle.
 pub fn collect_upload_summary(
     mut event_receiver: tokio::sync::mpsc::Receiver<ClientEvent>,
@@ -18,19 +20,19 @@ pub fn collect_upload_summary(
 ) {
     let (upload_completed_tx, mut upload_completed_rx) = tokio::sync::oneshot::channel::<()>();
     let stats_thread = tokio::spawn(async move {
-        let mut tokens_spent: Amount = Amount::from(0);
-        let mut record_count = 0;
-        let mut records_already_paid = 0;
+        let mut summary = UploadSummary {
+            tokens_spent: Amount::from(0),
+            records_paid: 0,
+            records_already_paid: 0,
+        };
+        let spinner = get_spinner().unwrap_or_else(|_| ProgressBar::hidden());
+        let mut progress = ClientProgress::default();
 
         loop {
             tokio::select! {
                 event = event_receiver.recv() => {
                     match event {
-                        Some(ClientEvent::UploadComplete(upload_summary)) => {
-                            tokens_spent += upload_summary.tokens_spent;
-                            record_count += upload_summary.records_paid;
-                            records_already_paid += upload_summary.records_already_paid;
-                        }
+                        Some(event) => add_event(&mut summary, &mut progress, &spinner, event),
                         None => break,
                     }
                 }
@@ -40,21 +42,26 @@ pub fn collect_upload_summary(
 
         // try to drain the event receiver in case there are any more events
         while let Ok(event) = event_receiver.try_recv() {
-            match event {
-                ClientEvent::UploadComplete(upload_summary) => {
-                    tokens_spent += upload_summary.tokens_spent;
-                    record_count += upload_summary.records_paid;
-                    records_already_paid += upload_summary.records_already_paid;
-                }
-            }
+            add_event(&mut summary, &mut progress, &spinner, event);
         }
+        spinner.finish_and_clear();
 
-        UploadSummary {
-            tokens_spent,
-            records_paid: record_count,
-            records_already_paid,
-        }
+        summary
     });
 
     (stats_thread, upload_completed_tx)
 }
+
+fn add_event(
+    summary: &mut UploadSummary,
+    progress: &mut ClientProgress,
+    spinner: &ProgressBar,
+    event: ClientEvent,
+) {
+    progress.update(&event, spinner);
+    if let ClientEvent::UploadComplete(upload_summary) = event {
+        summary.tokens_spent += upload_summary.tokens_spent;
+        summary.records_paid += upload_summary.records_paid;
+        summary.records_already_paid += upload_summary.records_already_paid;
+    }
+}
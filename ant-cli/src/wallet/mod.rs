@@ -6,7 +6,9 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::keys::{get_secret_key_from_env, load_evm_wallet_from_env};
+use crate::keys::{
+    get_secret_key_from_env, load_evm_signer_wallet_from_env, load_evm_wallet_from_env,
+};
 use crate::wallet::fs::{select_wallet, select_wallet_private_key};
 use autonomi::{Network, Wallet};
 
@@ -24,6 +26,11 @@ pub(crate) fn load_wallet() -> color_eyre::Result<Wallet> {
         return Ok(wallet);
     }
 
+    // Then a keystore or remote signer from ENV, keeping the key off this machine's wallet dir
+    if let Some(wallet) = load_evm_signer_wallet_from_env()? {
+        return Ok(wallet);
+    }
+
     let wallet = select_wallet()?;
 
     Ok(wallet)
@@ -372,6 +372,29 @@ mod tests {
         assert!(!quote.check_is_signed_by_claimed_peer(false_peer));
     }
 
+    #[test]
+    fn test_signature_covers_quoted_data() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+
+        let mut quote = PaymentQuote::zero();
+        quote.quoting_metrics.data_type = 2;
+        quote.quoting_metrics.data_size = 100;
+        quote.pub_key = keypair.public().encode_protobuf();
+        quote.signature = keypair
+            .sign(&quote.bytes_for_sig())
+            .expect("Cannot sign the quote!");
+        assert!(quote.check_is_signed_by_claimed_peer(peer_id));
+
+        let mut bigger = quote.clone();
+        bigger.quoting_metrics.data_size = 4 * 1024 * 1024;
+        assert!(!bigger.check_is_signed_by_claimed_peer(peer_id));
+
+        let mut other_type = quote;
+        other_type.quoting_metrics.data_type = 0;
+        assert!(!other_type.check_is_signed_by_claimed_peer(peer_id));
+    }
+
     #[test]
     fn test_historical_verify() {
         let mut old_quote = PaymentQuote::zero();
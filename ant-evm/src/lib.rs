@@ -11,6 +11,7 @@ extern crate tracing;
 
 pub use evmlib::common::Address as RewardsAddress;
 pub use evmlib::common::Address as EvmAddress;
+pub use evmlib::common::Calldata;
 pub use evmlib::common::QuotePayment;
 pub use evmlib::common::U256;
 pub use evmlib::common::{QuoteHash, TxHash};
@@ -18,10 +19,13 @@ pub use evmlib::contract::payment_vault;
 pub use evmlib::cryptography;
 #[cfg(feature = "external-signer")]
 pub use evmlib::external_signer;
+pub use evmlib::signer::{PaymentSigner, RemoteSigner};
 pub use evmlib::utils;
 pub use evmlib::utils::get_evm_network_from_env;
 pub use evmlib::utils::{DATA_PAYMENTS_ADDRESS, PAYMENT_TOKEN_ADDRESS, RPC_URL};
+pub use evmlib::wallet::ApprovalMode;
 pub use evmlib::wallet::Error as EvmWalletError;
+pub use evmlib::wallet::PayForQuotesError;
 pub use evmlib::wallet::Wallet as EvmWallet;
 pub use evmlib::CustomNetwork;
 pub use evmlib::Network as EvmNetwork;
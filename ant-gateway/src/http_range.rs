@@ -0,0 +1,125 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Parsing of the HTTP `Range` request header, see RFC 9110 section 14.
+
+use std::ops::Range;
+
+/// Longest range served for any request, as the whole range is fetched into memory. Longer
+/// ranges are cut short, and clients ask for the rest once they have consumed what they got.
+pub(crate) const MAX_RANGE_LEN: u64 = 16 * 1024 * 1024;
+
+/// A range request for data of a known size.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum RangeRequest {
+    /// The bytes to serve, never empty and within the data.
+    Satisfiable(Range<u64>),
+    /// None of the requested bytes are in the data.
+    Unsatisfiable,
+}
+
+/// Parse the value of a `Range` header for data of `size` bytes.
+///
+/// Returns `None` for headers that should be ignored, in which case the full data is served:
+/// malformed ones, units other than bytes and requests for multiple ranges.
+pub(crate) fn parse_range_header(value: &str, size: u64) -> Option<RangeRequest> {
+    let spec = value.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let mut range = match (start.is_empty(), end.is_empty()) {
+        // bytes=-500, the last 500 bytes
+        (true, false) => {
+            let suffix: u64 = end.parse().ok()?;
+            if suffix == 0 {
+                return Some(RangeRequest::Unsatisfiable);
+            }
+            size.saturating_sub(suffix)..size
+        }
+        // bytes=500-
+        (false, true) => {
+            let start: u64 = start.parse().ok()?;
+            start..size
+        }
+        // bytes=500-999, the end is inclusive
+        (false, false) => {
+            let start: u64 = start.parse().ok()?;
+            let end: u64 = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            start..size.min(end.saturating_add(1))
+        }
+        (true, true) => return None,
+    };
+
+    if range.start >= size {
+        return Some(RangeRequest::Unsatisfiable);
+    }
+    range.end = range.end.min(range.start.saturating_add(MAX_RANGE_LEN));
+    Some(RangeRequest::Satisfiable(range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        let parse = |value| parse_range_header(value, 1000);
+        assert_eq!(parse("bytes=0-99"), Some(RangeRequest::Satisfiable(0..100)));
+        assert_eq!(
+            parse("bytes=900-2000"),
+            Some(RangeRequest::Satisfiable(900..1000))
+        );
+        assert_eq!(
+            parse("bytes=990-"),
+            Some(RangeRequest::Satisfiable(990..1000))
+        );
+        assert_eq!(
+            parse("bytes=-10"),
+            Some(RangeRequest::Satisfiable(990..1000))
+        );
+        assert_eq!(
+            parse("bytes=-5000"),
+            Some(RangeRequest::Satisfiable(0..1000))
+        );
+        assert_eq!(parse("bytes=1000-"), Some(RangeRequest::Unsatisfiable));
+        assert_eq!(parse("bytes=-0"), Some(RangeRequest::Unsatisfiable));
+
+        assert_eq!(parse("bytes=0-1,5-6"), None);
+        assert_eq!(parse("bytes=5-1"), None);
+        assert_eq!(parse("items=0-1"), None);
+        assert_eq!(parse("bytes=a-b"), None);
+
+        assert_eq!(
+            parse_range_header("bytes=0-", u64::MAX),
+            Some(RangeRequest::Satisfiable(0..MAX_RANGE_LEN))
+        );
+    }
+
+    #[test]
+    fn caps_long_closed_and_suffix_ranges() {
+        let size = 10 * MAX_RANGE_LEN;
+        assert_eq!(
+            parse_range_header("bytes=0-99999999999", size),
+            Some(RangeRequest::Satisfiable(0..MAX_RANGE_LEN))
+        );
+        assert_eq!(
+            parse_range_header("bytes=100-99999999999", u64::MAX),
+            Some(RangeRequest::Satisfiable(100..100 + MAX_RANGE_LEN))
+        );
+        assert_eq!(
+            parse_range_header("bytes=-99999999999", size),
+            Some(RangeRequest::Satisfiable(0..MAX_RANGE_LEN))
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+#[macro_use]
+extern crate tracing;
+
+mod http_range;
+mod opt;
+mod server;
+
+use ant_bootstrap::ANT_PEERS_ENV;
+use ant_logging::{LogBuilder, LogFormat, ReloadHandle, WorkerGuard};
+use ant_protocol::version;
+use autonomi::Client;
+use clap::Parser;
+use color_eyre::eyre::Context;
+use color_eyre::{Result, Section};
+use opt::Opt;
+use tracing::Level;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install().expect("Failed to initialise error handler");
+    let opt = Opt::parse();
+    if let Some(network_id) = opt.network_id {
+        version::set_network_id(network_id);
+    }
+
+    if opt.version {
+        let identify_protocol_str = version::IDENTIFY_PROTOCOL_STR
+            .read()
+            .expect("Failed to obtain read lock for IDENTIFY_PROTOCOL_STR")
+            .clone();
+        println!(
+            "{}",
+            ant_build_info::version_string(
+                "Autonomi Gateway",
+                env!("CARGO_PKG_VERSION"),
+                Some(&identify_protocol_str)
+            )
+        );
+        return Ok(());
+    }
+
+    let _log_guards = init_logging(&opt)?;
+    info!("\"{}\"", std::env::args().collect::<Vec<_>>().join(" "));
+
+    let peers = opt.peers.get_addrs(None, Some(100)).await
+        .wrap_err("Please provide valid Network peers to connect to")
+        .with_suggestion(|| format!("make sure you've provided network peers using the --peer option or the {ANT_PEERS_ENV} env var"))?;
+    let client = Client::init_with_peers(peers)
+        .await
+        .wrap_err("Failed to connect to the network")?;
+    info!("Connected to the Network");
+
+    server::serve(client, opt.listen).await
+}
+
+fn init_logging(opt: &Opt) -> Result<(ReloadHandle, Option<WorkerGuard>)> {
+    let logging_targets = vec![
+        ("ant_bootstrap".to_string(), Level::DEBUG),
+        ("ant_gateway".to_string(), Level::TRACE),
+        ("ant_networking".to_string(), Level::INFO),
+        ("ant_protocol".to_string(), Level::TRACE),
+        ("autonomi".to_string(), Level::DEBUG),
+    ];
+    let mut log_builder = LogBuilder::new(logging_targets);
+    log_builder.output_dest(opt.log_output_dest.clone());
+    log_builder.format(opt.log_format.unwrap_or(LogFormat::Default));
+    let guards = log_builder.initialize()?;
+    Ok(guards)
+}
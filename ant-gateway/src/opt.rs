@@ -0,0 +1,52 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use ant_bootstrap::PeersArgs;
+use ant_logging::{LogFormat, LogOutputDest};
+use clap::Parser;
+use std::net::SocketAddr;
+
+// Please do not remove the blank lines in these doc comments.
+// They are used for inserting line breaks when the help menu is rendered in the UI.
+#[derive(Parser)]
+#[command(disable_version_flag = true)]
+#[command(author, version, about, long_about = None)]
+pub(crate) struct Opt {
+    /// The address to serve HTTP requests on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    pub listen: SocketAddr,
+
+    /// Specify the logging format.
+    ///
+    /// Valid values are "default" or "json".
+    ///
+    /// If the argument is not used, the default format will be applied.
+    #[clap(long, value_parser = LogFormat::parse_from_str, verbatim_doc_comment)]
+    pub log_format: Option<LogFormat>,
+
+    /// Specify the logging output destination.
+    ///
+    /// Valid values are "stdout", "data-dir", or a custom path.
+    ///
+    /// `stdout` is the default value.
+    #[clap(long, value_parser = LogOutputDest::parse_from_str, verbatim_doc_comment, default_value = "stdout")]
+    pub log_output_dest: LogOutputDest,
+
+    /// Specify the network ID to use. This will allow you to run the gateway on a different network.
+    ///
+    /// By default, the network ID is set to 1, which represents the mainnet.
+    #[clap(long, verbatim_doc_comment)]
+    pub network_id: Option<u8>,
+
+    #[command(flatten)]
+    pub(crate) peers: PeersArgs,
+
+    /// Print version information.
+    #[clap(long)]
+    pub version: bool,
+}
@@ -0,0 +1,446 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! HTTP server mapping requests onto public data and archives on the network.
+//!
+//! - `/data/<address>` serves a blob of public data.
+//! - `/archive/<address>/<path>` serves a file of a public archive, or a directory listing.
+
+use crate::http_range::{parse_range_header, RangeRequest};
+use ant_protocol::storage::PointerAddress;
+use autonomi::client::{
+    address::str_to_addr,
+    data::{DataAddr, GetError},
+    files::{
+        archive_mutable::MutableArchiveError,
+        archive_public::{ArchiveAddr, PublicArchive},
+    },
+};
+use autonomi::Client;
+use color_eyre::Result;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio_util::io::ReaderStream;
+
+/// Number of leading bytes used to sniff the content type of data.
+const SNIFF_LEN: u64 = 1024;
+
+/// Characters to escape in a path segment used as a link in a directory listing.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'\'')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Errors that end a request, each mapping to an HTTP status.
+#[derive(Debug, thiserror::Error)]
+enum GatewayError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Failed to fetch data from the network: {0}")]
+    Get(#[from] GetError),
+    #[error("Failed to fetch archive from the network: {0}")]
+    MutableArchive(#[from] MutableArchiveError),
+    #[error("Failed to build response: {0}")]
+    Http(#[from] hyper::http::Error),
+}
+
+impl GatewayError {
+    fn status(&self) -> StatusCode {
+        match self {
+            GatewayError::NotFound => StatusCode::NOT_FOUND,
+            GatewayError::Get(err) if err.is_not_found() => StatusCode::NOT_FOUND,
+            GatewayError::MutableArchive(err) if err.is_not_found() => StatusCode::NOT_FOUND,
+            GatewayError::Get(_) | GatewayError::MutableArchive(_) => StatusCode::BAD_GATEWAY,
+            GatewayError::Http(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// What a request path refers to.
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Data(DataAddr),
+    /// An archive and a path within it, without leading or trailing slashes.
+    Archive(ArchiveAddr, String),
+}
+
+/// Serve HTTP requests on `addr` until the server fails.
+pub(crate) async fn serve(client: Client, addr: SocketAddr) -> Result<()> {
+    let make_service = make_service_fn(move |_conn| {
+        let client = client.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(client.clone(), request))) }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    info!("Gateway listening on http://{}", server.local_addr());
+    println!("Gateway listening on http://{}", server.local_addr());
+    server.await?;
+    Ok(())
+}
+
+async fn handle(client: Client, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let head = request.method() == Method::HEAD;
+    if !head && request.method() != Method::GET {
+        let mut response = error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+        let _ = response
+            .headers_mut()
+            .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+        return Ok(response);
+    }
+
+    let uri_path = request.uri().path();
+    debug!("{} {uri_path}", request.method());
+    let result = match parse_route(uri_path) {
+        Some(Route::Data(addr)) => {
+            serve_data(&client, addr, None, true, request.headers(), head).await
+        }
+        Some(Route::Archive(addr, path)) => {
+            serve_archive(&client, addr, &path, uri_path, request.headers(), head).await
+        }
+        None => Err(GatewayError::NotFound),
+    };
+
+    Ok(result.unwrap_or_else(|err| {
+        let status = err.status();
+        if status.is_server_error() {
+            error!("Failed to serve {uri_path}: {err}");
+        } else {
+            debug!("Failed to serve {uri_path}: {err}");
+        }
+        error_response(status, &err.to_string())
+    }))
+}
+
+/// Map a request path onto a [`Route`], if it refers to one.
+fn parse_route(uri_path: &str) -> Option<Route> {
+    let path = percent_decode_str(uri_path).decode_utf8().ok()?;
+    let mut parts = path.trim_start_matches('/').splitn(3, '/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("data"), Some(addr), None) => Some(Route::Data(str_to_addr(addr).ok()?)),
+        (Some("archive"), Some(addr), rest) => Some(Route::Archive(
+            str_to_addr(addr).ok()?,
+            rest.unwrap_or_default().trim_matches('/').to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Fetch the archive at `addr`, which is either an [`ArchiveAddr`] or the stable address of a
+/// mutable archive. Returns whether it was the latter.
+async fn fetch_archive(
+    client: &Client,
+    addr: ArchiveAddr,
+) -> Result<(PublicArchive, bool), GatewayError> {
+    match client.archive_get_public(addr).await {
+        Ok(archive) => Ok((archive, false)),
+        Err(err) if err.is_not_found() => {
+            debug!("No archive at {addr:?}, trying a mutable archive");
+            let archive = client
+                .archive_get_mutable_public(PointerAddress::new(addr))
+                .await?;
+            Ok((archive, true))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn serve_archive(
+    client: &Client,
+    addr: ArchiveAddr,
+    path: &str,
+    uri_path: &str,
+    headers: &HeaderMap,
+    head: bool,
+) -> Result<Response<Body>, GatewayError> {
+    let (archive, mutable) = fetch_archive(client, addr).await?;
+    let path = Path::new(path);
+
+    if let Some((data_addr, _meta)) = archive.map().get(path) {
+        let content_type = mime_guess::from_path(path).first().map(with_charset);
+        return serve_data(client, *data_addr, content_type, !mutable, headers, head).await;
+    }
+
+    let entries = dir_entries(&archive, path);
+    let is_dir = path.as_os_str().is_empty()
+        || !entries.is_empty()
+        || archive.dirs().any(|(dir, _)| dir == path);
+    if !is_dir {
+        return Err(GatewayError::NotFound);
+    }
+
+    // relative links in the directory only resolve properly with a trailing slash
+    if !uri_path.ends_with('/') {
+        return Ok(Response::builder()
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header(header::LOCATION, format!("{uri_path}/"))
+            .body(Body::empty())?);
+    }
+
+    if let Some((data_addr, _meta)) = archive.map().get(&path.join("index.html")) {
+        let content_type = Some("text/html; charset=utf-8".to_string());
+        return serve_data(client, *data_addr, content_type, !mutable, headers, head).await;
+    }
+
+    let html = listing_html(uri_path, &entries);
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::CONTENT_LENGTH, html.len())
+        .body(if head {
+            Body::empty()
+        } else {
+            Body::from(html)
+        })?)
+}
+
+/// Serve a blob of public data, honouring `If-None-Match` and `Range` request headers.
+/// The content type is sniffed from the data when not given.
+async fn serve_data(
+    client: &Client,
+    addr: DataAddr,
+    content_type: Option<String>,
+    immutable: bool,
+    headers: &HeaderMap,
+    head: bool,
+) -> Result<Response<Body>, GatewayError> {
+    // data is addressed by its content, so its address makes for a strong validator
+    let etag = format!("\"{}\"", hex::encode(addr));
+    let cache_control = if immutable {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    if not_modified {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .header(header::CACHE_CONTROL, cache_control)
+            .body(Body::empty())?);
+    }
+
+    let size = client.data_size_public(addr).await?;
+    let content_type = match content_type {
+        Some(content_type) => content_type,
+        None => {
+            let leading = client
+                .data_get_range_public(addr, 0..SNIFF_LEN.min(size))
+                .await?;
+            sniff_content_type(&leading)
+        }
+    };
+
+    let response = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ETAG, etag);
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range_header(value, size));
+    let response = match range {
+        Some(RangeRequest::Unsatisfiable) => response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+            .body(Body::empty())?,
+        Some(RangeRequest::Satisfiable(range)) => {
+            let content_range = format!("bytes {}-{}/{size}", range.start, range.end - 1);
+            let response = response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, content_range)
+                .header(header::CONTENT_LENGTH, range.end - range.start);
+            if head {
+                response.body(Body::empty())?
+            } else {
+                let bytes = client.data_get_range_public(addr, range).await?;
+                response.body(Body::from(bytes))?
+            }
+        }
+        None => {
+            let response = response.header(header::CONTENT_LENGTH, size);
+            if head {
+                response.body(Body::empty())?
+            } else {
+                let reader = client.data_get_stream_public(addr).await?;
+                response.body(Body::wrap_stream(ReaderStream::new(reader)))?
+            }
+        }
+    };
+    Ok(response)
+}
+
+/// Guess the content type of data from its leading bytes.
+fn sniff_content_type(leading: &[u8]) -> String {
+    if let Some(kind) = infer::Infer::new().get(leading) {
+        return kind.mime;
+    }
+    match std::str::from_utf8(leading) {
+        // the leading bytes may cut a multi-byte character in half
+        Ok(_) => "text/plain; charset=utf-8".to_string(),
+        Err(err) if err.error_len().is_none() => "text/plain; charset=utf-8".to_string(),
+        Err(_) => "application/octet-stream".to_string(),
+    }
+}
+
+fn with_charset(mime: mime_guess::Mime) -> String {
+    if mime.type_() == mime_guess::mime::TEXT && mime.get_param("charset").is_none() {
+        format!("{mime}; charset=utf-8")
+    } else {
+        mime.to_string()
+    }
+}
+
+/// The names of the files and directories directly in `dir`, and whether they are directories.
+fn dir_entries(archive: &PublicArchive, dir: &Path) -> BTreeMap<String, bool> {
+    let files = archive.map().keys().map(|path| (path, false));
+    let dirs = archive.dirs().map(|(path, _)| (path, true));
+
+    let mut entries = BTreeMap::new();
+    for (path, path_is_dir) in files.chain(dirs) {
+        let Ok(rest) = path.strip_prefix(dir) else {
+            continue;
+        };
+        let mut components = rest.components();
+        let Some(name) = components.next() else {
+            continue;
+        };
+        let is_dir = path_is_dir || components.next().is_some();
+        let entry = entries
+            .entry(name.as_os_str().to_string_lossy().into_owned())
+            .or_insert(false);
+        *entry |= is_dir;
+    }
+    entries
+}
+
+fn listing_html(uri_path: &str, entries: &BTreeMap<String, bool>) -> String {
+    let title = escape_html(&percent_decode_str(uri_path).decode_utf8_lossy());
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n<li><a href=\"../\">../</a></li>\n"
+    );
+    for (name, is_dir) in entries {
+        let slash = if *is_dir { "/" } else { "" };
+        let href = utf8_percent_encode(name, PATH_SEGMENT);
+        let name = escape_html(name);
+        html.push_str(&format!(
+            "<li><a href=\"{href}{slash}\">{name}{slash}</a></li>\n"
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{message}\n")));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::client::files::archive::Metadata;
+    use std::path::PathBuf;
+
+    #[test]
+    fn routes_and_lists_archive_directories() {
+        let addr = ArchiveAddr::random(&mut rand::thread_rng());
+        let hex_addr = hex::encode(addr);
+        assert_eq!(
+            parse_route(&format!("/data/{hex_addr}")),
+            Some(Route::Data(addr))
+        );
+        assert_eq!(
+            parse_route(&format!("/archive/{hex_addr}/site/my%20page.html")),
+            Some(Route::Archive(addr, "site/my page.html".to_string()))
+        );
+        assert_eq!(
+            parse_route(&format!("/archive/{hex_addr}/site/")),
+            Some(Route::Archive(addr, "site".to_string()))
+        );
+        assert_eq!(parse_route("/data/not-hex"), None);
+        assert_eq!(parse_route("/other"), None);
+
+        let mut archive = PublicArchive::new();
+        for path in ["site/index.html", "site/css/style.css", "site/a&b.txt"] {
+            archive.add_file(PathBuf::from(path), addr, Metadata::new_with_size(0));
+        }
+        archive.add_dir(PathBuf::from("site/empty"), Metadata::new_with_size(0));
+
+        let entries = dir_entries(&archive, Path::new("site"));
+        let expected = [
+            ("a&b.txt", false),
+            ("css", true),
+            ("empty", true),
+            ("index.html", false),
+        ];
+        assert_eq!(
+            entries,
+            expected
+                .iter()
+                .map(|(name, is_dir)| (name.to_string(), *is_dir))
+                .collect()
+        );
+        assert_eq!(dir_entries(&archive, Path::new("")).len(), 1);
+
+        let html = listing_html("/archive/x/site/", &entries);
+        assert!(html.contains("<a href=\"a%26b.txt\">a&amp;b.txt</a>"));
+        assert!(html.contains("<a href=\"css/\">css/</a>"));
+    }
+
+    #[test]
+    fn sniffs_content_types() {
+        assert_eq!(
+            sniff_content_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            "image/png"
+        );
+        // the leading bytes may end in the middle of a character
+        let text = "caf\u{e9}".as_bytes();
+        assert_eq!(
+            sniff_content_type(&text[..text.len() - 1]),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            sniff_content_type(&[0, 159, 146, 150]),
+            "application/octet-stream"
+        );
+    }
+}
@@ -378,6 +378,8 @@ impl Network {
     pub async fn get_store_quote_from_network(
         &self,
         record_address: NetworkAddress,
+        data_type: u32,
+        data_size: usize,
         ignore_peers: Vec<PeerId>,
     ) -> Result<Vec<(PeerId, PaymentQuote)>> {
         // The requirement of having at least CLOSE_GROUP_SIZE
@@ -400,6 +402,8 @@ impl Network {
         // Client shall decide whether to carry out storage verification or not.
         let request = Request::Query(Query::GetStoreQuote {
             key: record_address.clone(),
+            data_type,
+            data_size,
             nonce: None,
             difficulty: 0,
         });
@@ -430,6 +434,13 @@ impl Network {
                         warn!("Received invalid quote from {peer_address:?}, {quote:?}");
                         continue;
                     }
+                    // A quote for other data would be refused when storing ours.
+                    if quote.quoting_metrics.data_type != data_type
+                        || quote.quoting_metrics.data_size != data_size
+                    {
+                        warn!("Received quote for other data from {peer_address:?}, {quote:?}");
+                        continue;
+                    }
 
                     all_quotes.push((peer_address.clone(), quote.clone()));
                     quotes_to_pay.push((peer, quote));
@@ -806,13 +817,21 @@ impl Network {
         Ok(None)
     }
 
-    /// Get the quoting metrics for storing the next record from the network
+    /// Get the quoting metrics for storing the next record, of `data_type` and `data_size` bytes,
+    /// from the network
     pub async fn get_local_quoting_metrics(
         &self,
         key: RecordKey,
+        data_type: u32,
+        data_size: usize,
     ) -> Result<(QuotingMetrics, bool)> {
         let (sender, receiver) = oneshot::channel();
-        self.send_local_swarm_cmd(LocalSwarmCmd::GetLocalQuotingMetrics { key, sender });
+        self.send_local_swarm_cmd(LocalSwarmCmd::GetLocalQuotingMetrics {
+            key,
+            data_type,
+            data_size,
+            sender,
+        });
 
         receiver
             .await
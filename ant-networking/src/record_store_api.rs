@@ -118,6 +118,8 @@ impl UnifiedRecordStore {
     pub(crate) fn quoting_metrics(
         &self,
         key: &RecordKey,
+        data_type: u32,
+        data_size: usize,
         network_size: Option<u64>,
     ) -> (QuotingMetrics, bool) {
         match self {
@@ -125,7 +127,7 @@ impl UnifiedRecordStore {
                 warn!("Calling quoting metrics calculation at Client. This should not happen");
                 Default::default()
             }
-            Self::Node(store) => store.quoting_metrics(key, network_size),
+            Self::Node(store) => store.quoting_metrics(key, data_type, data_size, network_size),
         }
     }
 
@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use ant_evm::AttoTokens;
-use ant_protocol::{NetworkAddress, PrettyPrintRecordKey};
+use ant_protocol::{storage::GraphEntryAddress, NetworkAddress, PrettyPrintRecordKey};
 use thiserror::Error;
 
 pub(super) type Result<T, E = Error> = std::result::Result<T, E>;
@@ -47,10 +47,21 @@ pub enum Error {
     // Scratchpad is invalid
     #[error("Scratchpad signature is invalid over the counter + content hash")]
     InvalidScratchpadSignature,
+    // Pointer is old version
+    #[error("A Pointer with counter {local} already exists, ignoring counter {incoming}")]
+    IgnoringOutdatedPointerPut { local: u32, incoming: u32 },
 
     #[error("Invalid signature")]
     InvalidSignature,
 
+    // GraphEntry address already holds the maximum number of forks
+    #[error("Rejected {rejected} GraphEntry fork(s) at {address:?}, which already holds the limit of {limit}")]
+    GraphEntryForkLimitReached {
+        address: GraphEntryAddress,
+        limit: usize,
+        rejected: usize,
+    },
+
     // ---------- Payment Errors
     #[error("The content of the payment quote is invalid")]
     InvalidQuoteContent,
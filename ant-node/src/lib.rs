@@ -27,6 +27,7 @@ mod log_markers;
 #[cfg(feature = "open-metrics")]
 mod metrics;
 mod node;
+mod payment_verifier;
 mod put_validation;
 #[cfg(feature = "extension-module")]
 mod python;
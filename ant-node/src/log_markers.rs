@@ -45,6 +45,8 @@ pub enum Marker<'a> {
     ValidTransactionRecordPutFromNetwork(&'a PrettyPrintRecordKey<'a>),
     /// Valid Scratchpad record PUT from the network received and stored
     ValidScratchpadRecordPutFromNetwork(&'a PrettyPrintRecordKey<'a>),
+    /// Valid Pointer record PUT from the network received and stored
+    ValidPointerRecordPutFromNetwork(&'a PrettyPrintRecordKey<'a>),
 
     /// Valid paid to us and royalty paid chunk stored
     ValidPaidChunkPutFromClient(&'a PrettyPrintRecordKey<'a>),
@@ -55,9 +57,20 @@ pub enum Marker<'a> {
     /// Valid scratchpad stored
     ValidScratchpadRecordPutFromClient(&'a PrettyPrintRecordKey<'a>),
 
-    /// Valid paid to us and royalty paid pointer stored
+    /// Valid pointer stored, either paid to us or updated by its owner
     ValidPointerPutFromClient(&'a PrettyPrintRecordKey<'a>),
 
+    /// Graph entries now stored at an address, more than one means the address is forked
+    GraphEntriesStored {
+        /// entries: number of entries stored at the address
+        entries: usize,
+    },
+    /// Graph entries dropped to keep an address within the fork limit
+    GraphEntryForksRejected {
+        /// rejected: number of entries dropped
+        rejected: usize,
+    },
+
     /// Record rejected
     RecordRejected(&'a PrettyPrintRecordKey<'a>, &'a Error),
 
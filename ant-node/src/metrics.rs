@@ -33,6 +33,10 @@ pub(crate) struct NodeMetricsRecorder {
     replication_triggered: Counter,
     replication_keys_to_fetch: Histogram,
 
+    // graph entry forks
+    graph_entry_forks: Histogram,
+    graph_entry_forks_rejected: Counter,
+
     // routing table
     peer_added_to_routing_table: Counter,
     peer_removed_from_routing_table: Counter,
@@ -95,6 +99,21 @@ impl NodeMetricsRecorder {
             replication_keys_to_fetch.clone(),
         );
 
+        // MAX_GRAPH_ENTRY_FORKS = 5
+        let graph_entry_forks = Histogram::new(exponential_buckets(1.0, 2.0, 3));
+        sub_registry.register(
+            "graph_entry_forks",
+            "Number of entries stored at a graph entry address, more than one being forks",
+            graph_entry_forks.clone(),
+        );
+
+        let graph_entry_forks_rejected = Counter::default();
+        sub_registry.register(
+            "graph_entry_forks_rejected",
+            "Number of graph entries dropped for exceeding the fork limit of their address",
+            graph_entry_forks_rejected.clone(),
+        );
+
         let peer_added_to_routing_table = Counter::default();
         sub_registry.register(
             "peer_added_to_routing_table",
@@ -135,6 +154,8 @@ impl NodeMetricsRecorder {
             put_record_err,
             replication_triggered,
             replication_keys_to_fetch,
+            graph_entry_forks,
+            graph_entry_forks_rejected,
             peer_added_to_routing_table,
             peer_removed_from_routing_table,
             current_reward_wallet_balance,
@@ -186,6 +207,14 @@ impl NodeMetricsRecorder {
                 .replication_keys_to_fetch
                 .observe(fetching_keys_len as f64),
 
+            Marker::GraphEntriesStored { entries } => {
+                self.graph_entry_forks.observe(entries as f64)
+            }
+
+            Marker::GraphEntryForksRejected { rejected } => {
+                let _ = self.graph_entry_forks_rejected.inc_by(rejected as u64);
+            }
+
             Marker::PeerAddedToRoutingTable(_) => {
                 let _ = self.peer_added_to_routing_table.inc();
             }
@@ -11,6 +11,7 @@ use super::{
 };
 #[cfg(feature = "open-metrics")]
 use crate::metrics::NodeMetricsRecorder;
+use crate::payment_verifier::PaymentVerifier;
 use crate::RunningNode;
 use ant_bootstrap::BootstrapCacheStore;
 use ant_evm::RewardsAddress;
@@ -22,7 +23,9 @@ use ant_networking::{
 use ant_protocol::{
     convert_distance_to_u256,
     error::Error as ProtocolError,
-    messages::{ChunkProof, CmdResponse, Nonce, Query, QueryResponse, Request, Response},
+    messages::{
+        ChunkProof, CmdResponse, Nonce, Query, QueryResponse, RecordVersion, Request, Response,
+    },
     storage::ValidationType,
     NetworkAddress, PrettyPrintRecordKey, CLOSE_GROUP_SIZE,
 };
@@ -35,7 +38,7 @@ use rand::{
     thread_rng, Rng, SeedableRng,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     net::SocketAddr,
     path::PathBuf,
     sync::{
@@ -75,6 +78,13 @@ const MIN_ACCEPTABLE_HEALTHY_SCORE: usize = 5000;
 /// in ms, expecting average StorageChallenge complete time to be around 250ms.
 const TIME_STEP: usize = 20;
 
+/// How many updates an answer to a StorageChallenge can be behind our version of a record,
+/// and still be taken as the update not having reached the peer yet.
+const MAX_RECORD_VERSION_LAG: u64 = 3;
+
+/// The proofs expected from a StorageChallenge, along with our version of each record.
+type ExpectedProofs = HashMap<NetworkAddress, (RecordVersion, ChunkProof)>;
+
 /// Interval to carryout network density sampling
 /// This is the max time it should take. Minimum interval at any node will be half this
 const NETWORK_DENSITY_SAMPLING_INTERVAL_MAX_S: u64 = 200;
@@ -198,7 +208,7 @@ impl NodeBuilder {
             reward_address: self.evm_address,
             #[cfg(feature = "open-metrics")]
             metrics_recorder,
-            evm_network: self.evm_network,
+            payment_verifier: PaymentVerifier::new(self.evm_network),
         };
         let node = Node {
             inner: Arc::new(node),
@@ -235,7 +245,7 @@ struct NodeInner {
     #[cfg(feature = "open-metrics")]
     metrics_recorder: Option<NodeMetricsRecorder>,
     reward_address: RewardsAddress,
-    evm_network: EvmNetwork,
+    payment_verifier: PaymentVerifier,
 }
 
 impl Node {
@@ -266,8 +276,9 @@ impl Node {
         &self.inner.reward_address
     }
 
-    pub(crate) fn evm_network(&self) -> &EvmNetwork {
-        &self.inner.evm_network
+    /// Returns the verifier of on-chain payments
+    pub(crate) fn payment_verifier(&self) -> &PaymentVerifier {
+        &self.inner.payment_verifier
     }
 
     /// Runs the provided `SwarmDriver` and spawns a task to process for `NetworkEvents`
@@ -572,15 +583,18 @@ impl Node {
         let resp: QueryResponse = match query {
             Query::GetStoreQuote {
                 key,
+                data_type,
+                data_size,
                 nonce,
                 difficulty,
             } => {
-                debug!("Got GetStoreQuote request for {key:?} with difficulty {difficulty}");
+                debug!("Got GetStoreQuote request for {key:?} of data type {data_type} and {data_size} bytes, with difficulty {difficulty}");
                 let record_key = key.to_record_key();
                 let self_id = network.peer_id();
 
-                let maybe_quoting_metrics =
-                    network.get_local_quoting_metrics(record_key.clone()).await;
+                let maybe_quoting_metrics = network
+                    .get_local_quoting_metrics(record_key.clone(), data_type, data_size)
+                    .await;
 
                 let storage_proofs = if let Some(nonce) = nonce {
                     Self::respond_x_closest_record_proof(
@@ -591,6 +605,9 @@ impl Node {
                         false,
                     )
                     .await
+                    .into_iter()
+                    .map(|(addr, proof)| (addr, proof.map(|(_, proof)| proof)))
+                    .collect()
                 } else {
                     vec![]
                 };
@@ -675,6 +692,23 @@ impl Node {
 
                 QueryResponse::GetChunkExistenceProof(
                     Self::respond_x_closest_record_proof(network, key, nonce, difficulty, true)
+                        .await
+                        .into_iter()
+                        .map(|(addr, proof)| (addr, proof.map(|(_, proof)| proof)))
+                        .collect(),
+                )
+            }
+            Query::GetRecordExistenceProof {
+                key,
+                nonce,
+                difficulty,
+            } => {
+                debug!(
+                    "Got GetRecordExistenceProof targeting record {key:?} with {difficulty} answers."
+                );
+
+                QueryResponse::GetRecordExistenceProof(
+                    Self::respond_x_closest_record_proof(network, key, nonce, difficulty, false)
                         .await,
                 )
             }
@@ -780,15 +814,18 @@ impl Node {
         }
     }
 
-    // Nodes only check ChunkProof each other, to avoid `multi-version` issue
-    // Client check proof against all records, as have to fetch from network anyway.
+    // Each proof comes with the version of the record it is for, so that a challenger holding
+    // a newer version of a record can tell a lagging answer from a false one.
     async fn respond_x_closest_record_proof(
         network: &Network,
         key: NetworkAddress,
         nonce: Nonce,
         difficulty: usize,
         chunk_only: bool,
-    ) -> Vec<(NetworkAddress, Result<ChunkProof, ProtocolError>)> {
+    ) -> Vec<(
+        NetworkAddress,
+        Result<(RecordVersion, ChunkProof), ProtocolError>,
+    )> {
         let start = Instant::now();
         let mut results = vec![];
         if difficulty == 1 {
@@ -797,7 +834,7 @@ impl Node {
             if let Ok(Some(record)) = network.get_local_record(&key.to_record_key()).await {
                 let proof = ChunkProof::new(&record.value, nonce);
                 debug!("Chunk proof for {key:?} is {proof:?}");
-                result = Ok(proof)
+                result = Ok((RecordVersion::from_record(&record), proof))
             } else {
                 debug!("Could not get ChunkProof for {key:?} as we don't have the record locally.");
             }
@@ -833,7 +870,10 @@ impl Node {
                     {
                         let proof = ChunkProof::new(&record.value, nonce);
                         debug!("Chunk proof for {key:?} is {proof:?}");
-                        results.push((addr.clone(), Ok(proof)));
+                        results.push((
+                            addr.clone(),
+                            Ok((RecordVersion::from_record(&record), proof)),
+                        ));
                     }
                 }
             }
@@ -847,7 +887,7 @@ impl Node {
         results
     }
 
-    /// Check among all chunk type records that we have,
+    /// Check among all records that we have, of any type,
     /// and randomly pick one as the verification candidate.
     /// This will challenge all closest peers at once.
     async fn storage_challenge(network: Network) {
@@ -871,22 +911,13 @@ impl Node {
             return;
         }
 
-        let mut verify_candidates: Vec<NetworkAddress> =
-            if let Ok(all_keys) = network.get_all_local_record_addresses().await {
-                all_keys
-                    .iter()
-                    .filter_map(|(addr, record_type)| {
-                        if ValidationType::Chunk == *record_type {
-                            Some(addr.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
-            } else {
-                error!("Failed to get local record addresses.");
-                return;
-            };
+        let all_keys = if let Ok(all_keys) = network.get_all_local_record_addresses().await {
+            all_keys
+        } else {
+            error!("Failed to get local record addresses.");
+            return;
+        };
+        let mut verify_candidates: Vec<NetworkAddress> = all_keys.keys().cloned().collect();
         let num_of_targets = verify_candidates.len();
         if num_of_targets < 50 {
             debug!("Not enough candidates({num_of_targets}/50) to be checked against neighbours.");
@@ -902,23 +933,30 @@ impl Node {
         // TODO: workload shall be dynamically deduced from resource usage
         let difficulty = CLOSE_GROUP_SIZE;
         verify_candidates.sort_by_key(|addr| target.distance(addr));
-        let expected_targets = verify_candidates.into_iter().take(difficulty);
         let nonce: Nonce = thread_rng().gen::<u64>();
-        let mut expected_proofs = HashMap::new();
-        for addr in expected_targets {
-            if let Ok(Some(record)) = network.get_local_record(&addr.to_record_key()).await {
-                let expected_proof = ChunkProof::new(&record.value, nonce);
-                let _ = expected_proofs.insert(addr, expected_proof);
-            } else {
-                error!("Local record {addr:?} cann't be loaded from disk.");
-            }
-        }
-        let request = Request::Query(Query::GetChunkExistenceProof {
+        let expected_proofs =
+            Self::expected_proofs(&network, verify_candidates.iter().take(difficulty), nonce).await;
+        let request = Request::Query(Query::GetRecordExistenceProof {
             key: target.clone(),
             nonce,
             difficulty,
         });
 
+        // Peers not knowing of GetRecordExistenceProof yet are challenged on chunks only.
+        let chunk_targets = verify_candidates
+            .iter()
+            .filter(|addr| all_keys.get(*addr) == Some(&ValidationType::Chunk))
+            .take(difficulty);
+        let expected_chunk_proofs = Self::expected_proofs(&network, chunk_targets, nonce).await;
+        let fallback = (!expected_chunk_proofs.is_empty()).then(|| {
+            let request = Request::Query(Query::GetChunkExistenceProof {
+                key: target.clone(),
+                nonce,
+                difficulty,
+            });
+            (request, expected_chunk_proofs)
+        });
+
         let mut tasks = JoinSet::new();
         for peer_id in closest_peers {
             if peer_id == network.peer_id() {
@@ -927,10 +965,16 @@ impl Node {
             let network_clone = network.clone();
             let request_clone = request.clone();
             let expected_proofs_clone = expected_proofs.clone();
+            let fallback_clone = fallback.clone();
             let _ = tasks.spawn(async move {
-                let res =
-                    scoring_peer(network_clone, peer_id, request_clone, expected_proofs_clone)
-                        .await;
+                let res = scoring_peer(
+                    network_clone,
+                    peer_id,
+                    request_clone,
+                    expected_proofs_clone,
+                    fallback_clone,
+                )
+                .await;
                 (peer_id, res)
             });
         }
@@ -941,7 +985,7 @@ impl Node {
                     if score < MIN_ACCEPTABLE_HEALTHY_SCORE {
                         info!("Peer {peer_id:?} failed storage challenge with low score {score}/{MIN_ACCEPTABLE_HEALTHY_SCORE}.");
                         // TODO: shall the challenge failure immediately triggers the node to be removed?
-                        network.record_node_issues(peer_id, NodeIssue::FailedChunkProofCheck);
+                        network.record_node_issues(peer_id, NodeIssue::FailedRecordProofCheck);
                     }
                 }
                 Err(e) => {
@@ -956,6 +1000,27 @@ impl Node {
         );
     }
 
+    /// The proofs we expect for the given records of ours, along with our version of each.
+    async fn expected_proofs(
+        network: &Network,
+        addrs: impl Iterator<Item = &NetworkAddress>,
+        nonce: Nonce,
+    ) -> ExpectedProofs {
+        let mut expected_proofs = HashMap::new();
+        for addr in addrs {
+            if let Ok(Some(record)) = network.get_local_record(&addr.to_record_key()).await {
+                let expected_proof = ChunkProof::new(&record.value, nonce);
+                let _ = expected_proofs.insert(
+                    addr.clone(),
+                    (RecordVersion::from_record(&record), expected_proof),
+                );
+            } else {
+                error!("Local record {addr:?} cann't be loaded from disk.");
+            }
+        }
+        expected_proofs
+    }
+
     #[allow(dead_code)]
     async fn network_density_sampling(network: Network) {
         for _ in 0..10 {
@@ -980,39 +1045,82 @@ async fn scoring_peer(
     network: Network,
     peer_id: PeerId,
     request: Request,
-    expected_proofs: HashMap<NetworkAddress, ChunkProof>,
+    expected_proofs: ExpectedProofs,
+    fallback: Option<(Request, ExpectedProofs)>,
 ) -> usize {
     let start = Instant::now();
     let responses = network
         .send_and_get_responses(&[peer_id], &request, true)
         .await;
 
-    if let Some(Ok(Response::Query(QueryResponse::GetChunkExistenceProof(answers)))) =
-        responses.get(&peer_id)
-    {
-        if answers.is_empty() {
-            info!("Peer {peer_id:?} didn't answer the ChunkProofChallenge.");
-            return 0;
+    match responses.get(&peer_id) {
+        Some(Ok(Response::Query(QueryResponse::GetRecordExistenceProof(answers)))) => {
+            let received_proofs = answers
+                .iter()
+                .filter_map(|(addr, proof)| {
+                    let (version, proof) = proof.as_ref().ok()?;
+                    Some((addr.clone(), version.clone(), proof.clone()))
+                })
+                .collect();
+            score_answers(peer_id, start.elapsed(), received_proofs, &expected_proofs)
         }
-        let elapsed = start.elapsed();
-
-        let mut received_proofs = vec![];
-        for (addr, proof) in answers {
-            if let Ok(proof) = proof {
-                received_proofs.push((addr.clone(), proof.clone()));
+        Some(Err(err)) => {
+            let Some((request, expected_chunk_proofs)) = fallback else {
+                info!("Peer {peer_id:?} failed to reply the RecordProofChallenge: {err:?}");
+                return 0;
+            };
+            // The peer may not know of the RecordProofChallenge yet.
+            debug!("Peer {peer_id:?} failed to reply the RecordProofChallenge: {err:?}, challenging it on chunks only.");
+            let start = Instant::now();
+            let responses = network
+                .send_and_get_responses(&[peer_id], &request, true)
+                .await;
+            if let Some(Ok(Response::Query(QueryResponse::GetChunkExistenceProof(answers)))) =
+                responses.get(&peer_id)
+            {
+                let received_proofs = answers
+                    .iter()
+                    .filter_map(|(addr, proof)| {
+                        let proof = proof.as_ref().ok()?;
+                        Some((addr.clone(), RecordVersion::Chunk, proof.clone()))
+                    })
+                    .collect();
+                score_answers(
+                    peer_id,
+                    start.elapsed(),
+                    received_proofs,
+                    &expected_chunk_proofs,
+                )
+            } else {
+                info!("Peer {peer_id:?} doesn't reply the ChunkProofChallenge, or replied with error.");
+                0
             }
         }
+        _ => {
+            info!(
+                "Peer {peer_id:?} doesn't reply the RecordProofChallenge, or replied with error."
+            );
+            0
+        }
+    }
+}
 
-        let score = mark_peer(elapsed, received_proofs, &expected_proofs);
-        info!(
-            "Received {} answers from peer {peer_id:?} after {elapsed:?}, score it as {score}.",
-            answers.len()
-        );
-        score
-    } else {
-        info!("Peer {peer_id:?} doesn't reply the ChunkProofChallenge, or replied with error.");
-        0
+fn score_answers(
+    peer_id: PeerId,
+    elapsed: Duration,
+    received_proofs: Vec<(NetworkAddress, RecordVersion, ChunkProof)>,
+    expected_proofs: &ExpectedProofs,
+) -> usize {
+    if received_proofs.is_empty() {
+        info!("Peer {peer_id:?} didn't answer the storage challenge.");
+        return 0;
     }
+    let num_of_answers = received_proofs.len();
+    let score = mark_peer(elapsed, received_proofs, expected_proofs);
+    info!(
+        "Received {num_of_answers} answers from peer {peer_id:?} after {elapsed:?}, score it as {score}."
+    );
+    score
 }
 
 // Based on following metrics:
@@ -1022,8 +1130,8 @@ async fn scoring_peer(
 // The higher the score, the better confidence on the peer
 fn mark_peer(
     duration: Duration,
-    answers: Vec<(NetworkAddress, ChunkProof)>,
-    expected_proofs: &HashMap<NetworkAddress, ChunkProof>,
+    answers: Vec<(NetworkAddress, RecordVersion, ChunkProof)>,
+    expected_proofs: &ExpectedProofs,
 ) -> usize {
     let duration_score = duration_score_scheme(duration);
     let challenge_score = challenge_score_scheme(answers, expected_proofs);
@@ -1045,21 +1153,44 @@ fn duration_score_scheme(duration: Duration) -> usize {
     HIGHEST_SCORE - step
 }
 
-// Any false answer shall result in 0 score immediately
+// Any false answer shall result in 0 score immediately.
+// An answer for an older version of a record can't be verified by us, and is most likely due to
+// the update not having reached the peer yet. It only counts as half a correct answer, and only
+// when that version is validly signed by the same owner and not too far behind ours.
+// Any other answer for another version counts as no answer.
 fn challenge_score_scheme(
-    answers: Vec<(NetworkAddress, ChunkProof)>,
-    expected_proofs: &HashMap<NetworkAddress, ChunkProof>,
+    answers: Vec<(NetworkAddress, RecordVersion, ChunkProof)>,
+    expected_proofs: &ExpectedProofs,
 ) -> usize {
+    if expected_proofs.is_empty() {
+        return 0;
+    }
+
     let mut correct_answers = 0;
-    for (addr, chunk_proof) in answers {
-        if let Some(expected_proof) = expected_proofs.get(&addr) {
-            if expected_proof.verify(&chunk_proof) {
+    let mut lagging_answers = 0;
+    let mut answered = HashSet::new();
+    for (addr, version, proof) in answers {
+        let Some((expected_version, expected_proof)) = expected_proofs.get(&addr) else {
+            continue;
+        };
+        if !answered.insert(addr.clone()) {
+            continue;
+        }
+        if *expected_version == version {
+            if expected_proof.verify(&proof) {
                 correct_answers += 1;
             } else {
                 info!("Spot a false answer to the challenge regarding {addr:?}");
-                // Any false answer shall result in 0 score immediately
                 return 0;
             }
+        } else if *expected_version == RecordVersion::Chunk || version == RecordVersion::Chunk {
+            info!("Spot an answer of the wrong record type regarding {addr:?}");
+            return 0;
+        } else if is_lagging_version(&version, expected_version) {
+            debug!("Spot an answer of an older version of {addr:?}, {version:?} instead of {expected_version:?}");
+            lagging_answers += 1;
+        } else {
+            info!("Spot an answer of an unacceptable version of {addr:?}, {version:?} instead of {expected_version:?}");
         }
     }
     // TODO: For those answers not among the expected_proofs,
@@ -1070,14 +1201,31 @@ fn challenge_score_scheme(
     //         * fetch from network to testify
     std::cmp::min(
         HIGHEST_SCORE,
-        HIGHEST_SCORE * correct_answers / expected_proofs.len(),
+        HIGHEST_SCORE * (2 * correct_answers + lagging_answers) / (2 * expected_proofs.len()),
     )
 }
 
+// Whether the answered version is validly signed by the owner of our version, for the same type
+// of record, with a counter lower than ours by at most `MAX_RECORD_VERSION_LAG`.
+fn is_lagging_version(version: &RecordVersion, ours: &RecordVersion) -> bool {
+    match (version.verified_counter(), ours.verified_counter()) {
+        (Some((data_type, owner, counter)), Some((our_data_type, our_owner, our_counter))) => {
+            data_type == our_data_type
+                && owner == our_owner
+                && counter < our_counter
+                && our_counter - counter <= MAX_RECORD_VERSION_LAG
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ant_protocol::storage::{ChunkAddress, Pointer, PointerTarget};
+    use bls::SecretKey;
     use std::str::FromStr;
+    use xor_name::XorName;
 
     #[test]
     fn test_no_local_peers() {
@@ -1173,4 +1321,81 @@ mod tests {
 
         assert_eq!(expected_result, result);
     }
+
+    #[test]
+    fn test_challenge_score_with_lagging_versions() {
+        let nonce: Nonce = 42;
+        let sk = SecretKey::random();
+        let pointer_version = |counter: u32, signing_key: &SecretKey| {
+            let target = PointerTarget::ChunkAddress(ChunkAddress::new(XorName::from_content(
+                &counter.to_be_bytes(),
+            )));
+            RecordVersion::Pointer(Pointer::new(sk.public_key(), counter, target, signing_key))
+        };
+        let chunk_addr = NetworkAddress::from_peer(PeerId::random());
+        let chunk_proof = ChunkProof::new(b"chunk", nonce);
+        let pointer_addr = NetworkAddress::from_peer(PeerId::random());
+        let new_version = pointer_version(10, &sk);
+        let new_proof = ChunkProof::new(b"new", nonce);
+        let expected_proofs = HashMap::from([
+            (
+                chunk_addr.clone(),
+                (RecordVersion::Chunk, chunk_proof.clone()),
+            ),
+            (
+                pointer_addr.clone(),
+                (new_version.clone(), new_proof.clone()),
+            ),
+        ]);
+        let chunk_answer = (
+            chunk_addr.clone(),
+            RecordVersion::Chunk,
+            chunk_proof.clone(),
+        );
+        let score_with_pointer_answer = |version: RecordVersion, proof: ChunkProof| {
+            let answers = vec![
+                chunk_answer.clone(),
+                (pointer_addr.clone(), version.clone(), proof.clone()),
+                (pointer_addr.clone(), version, proof),
+            ];
+            challenge_score_scheme(answers, &expected_proofs)
+        };
+
+        // All answers correct
+        assert_eq!(
+            score_with_pointer_answer(new_version.clone(), new_proof),
+            HIGHEST_SCORE
+        );
+
+        // A slightly older, validly signed version counts as half an answer, even when repeated
+        let old_proof = ChunkProof::new(b"old", nonce);
+        assert_eq!(
+            score_with_pointer_answer(pointer_version(7, &sk), old_proof.clone()),
+            HIGHEST_SCORE * 3 / 4
+        );
+
+        // Versions too far behind, ahead of ours, not signed by the owner, or without a counter
+        // count as no answer
+        let unacceptable_versions = [
+            pointer_version(6, &sk),
+            pointer_version(11, &sk),
+            pointer_version(9, &SecretKey::random()),
+            RecordVersion::Content(XorName::from_content(b"old")),
+        ];
+        for version in unacceptable_versions {
+            assert_eq!(
+                score_with_pointer_answer(version, old_proof.clone()),
+                HIGHEST_SCORE / 2
+            );
+        }
+
+        // A false proof for the same version is a failure
+        assert_eq!(score_with_pointer_answer(new_version.clone(), old_proof), 0);
+
+        // So is claiming a chunk is another type of record
+        let answers = vec![(chunk_addr, new_version, chunk_proof)];
+        assert_eq!(challenge_score_scheme(answers, &expected_proofs), 0);
+
+        assert_eq!(challenge_score_scheme(vec![], &HashMap::new()), 0);
+    }
 }
@@ -0,0 +1,392 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! On-chain payment verification, batched and cached.
+//!
+//! A client paying for many records in one transaction sends their PUTs to us at about the same
+//! time. Instead of one RPC round trip to the payment vault per record, concurrent verifications
+//! are queued and sent as a single batch request, and the results are kept until the quotes
+//! they are for expire, so a record PUT again with the same payment is not verified twice.
+
+use crate::error::{Error, Result};
+use ant_evm::{
+    payment_vault::PaymentBatchVerifier, Amount, EvmNetwork, QuoteHash, QuotingMetrics,
+    RewardsAddress, QUOTE_EXPIRATION_SECS,
+};
+use ant_networking::Instant;
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot, Mutex, Semaphore},
+    task::spawn,
+};
+
+/// Number of verifications waiting for a batch. Once full, callers wait for room, which holds
+/// back PUTs while the RPC endpoint is slow.
+const QUEUE_SIZE: usize = 256;
+/// Maximum number of payments verified in one batch request.
+const MAX_BATCH_SIZE: usize = 64;
+/// Maximum number of batch requests in flight at once.
+const MAX_CONCURRENT_BATCHES: usize = 4;
+
+/// The digest of a proof of payment, as verified by the payment vault.
+type PaymentDigest = Vec<(QuoteHash, QuotingMetrics, RewardsAddress)>;
+/// The amount paid to each quote of a verified payment, or why it could not be verified.
+type PaidQuotes = std::result::Result<Vec<(QuoteHash, Amount)>, String>;
+
+struct VerificationRequest {
+    payment: PaymentDigest,
+    reply: oneshot::Sender<PaidQuotes>,
+}
+
+/// Verifies a batch of payments in a single request to the payment vault, returning the result
+/// of each payment in order.
+trait VerifyBatch: Send + Sync + 'static {
+    fn verify_batch(
+        &self,
+        payments: Vec<PaymentDigest>,
+    ) -> impl Future<Output = std::result::Result<Vec<PaidQuotes>, String>> + Send;
+}
+
+impl VerifyBatch for PaymentBatchVerifier {
+    async fn verify_batch(
+        &self,
+        payments: Vec<PaymentDigest>,
+    ) -> std::result::Result<Vec<PaidQuotes>, String> {
+        let results = self
+            .verify_data_payments(payments)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(results
+            .into_iter()
+            .map(|result| result.map_err(|err| err.to_string()))
+            .collect())
+    }
+}
+
+/// Verifies payments on chain, see the [module docs](self).
+pub(crate) struct PaymentVerifier {
+    requests: mpsc::Sender<VerificationRequest>,
+    cache: Arc<Mutex<VerifiedQuotes>>,
+}
+
+impl PaymentVerifier {
+    /// Start the task sending the batches to `evm_network`.
+    pub(crate) fn new(evm_network: EvmNetwork) -> Self {
+        Self::with_verifier(PaymentBatchVerifier::new(&evm_network))
+    }
+
+    fn with_verifier(verifier: impl VerifyBatch) -> Self {
+        let (requests, receiver) = mpsc::channel(QUEUE_SIZE);
+        let cache = Arc::new(Mutex::new(VerifiedQuotes::default()));
+        let _handle = spawn(run_batches(
+            Arc::new(verifier),
+            receiver,
+            Arc::clone(&cache),
+        ));
+        Self { requests, cache }
+    }
+
+    /// Verify `payment` on chain and return the amount paid to the quotes in
+    /// `owned_quote_hashes`.
+    pub(crate) async fn verify(
+        &self,
+        owned_quote_hashes: Vec<QuoteHash>,
+        payment: PaymentDigest,
+    ) -> Result<Amount> {
+        if let Some(amount) =
+            self.cache
+                .lock()
+                .await
+                .amount_paid(&owned_quote_hashes, &payment, Instant::now())
+        {
+            debug!("Payment of {amount:?} was already verified");
+            return Ok(amount);
+        }
+
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(VerificationRequest { payment, reply })
+            .await
+            .map_err(|_| Error::EvmNetwork("Payment verification has stopped".to_string()))?;
+        let paid_quotes = response
+            .await
+            .map_err(|_| Error::EvmNetwork("Payment verification was dropped".to_string()))?
+            .map_err(|err| Error::EvmNetwork(format!("Failed to verify chunk payment: {err}")))?;
+
+        Ok(paid_quotes
+            .into_iter()
+            .filter(|(quote_hash, _)| owned_quote_hashes.contains(quote_hash))
+            .map(|(_, amount)| amount)
+            .sum())
+    }
+}
+
+/// Take the queued verifications in batches, sending up to [`MAX_CONCURRENT_BATCHES`] at once.
+/// Requests queued while all batches are in flight go in the next one.
+async fn run_batches(
+    verifier: Arc<impl VerifyBatch>,
+    mut receiver: mpsc::Receiver<VerificationRequest>,
+    cache: Arc<Mutex<VerifiedQuotes>>,
+) {
+    let in_flight = Arc::new(Semaphore::new(MAX_CONCURRENT_BATCHES));
+    loop {
+        let Ok(permit) = Arc::clone(&in_flight).acquire_owned().await else {
+            break;
+        };
+        let mut requests = Vec::with_capacity(MAX_BATCH_SIZE);
+        if receiver.recv_many(&mut requests, MAX_BATCH_SIZE).await == 0 {
+            break;
+        }
+
+        let verifier = Arc::clone(&verifier);
+        let cache = Arc::clone(&cache);
+        let _handle = spawn(async move {
+            verify_batch(verifier.as_ref(), requests, &cache).await;
+            drop(permit);
+        });
+    }
+    debug!("Payment verification stopped");
+}
+
+/// Verify the payments of `requests` in a single batch request and reply to each of them.
+async fn verify_batch(
+    verifier: &impl VerifyBatch,
+    requests: Vec<VerificationRequest>,
+    cache: &Mutex<VerifiedQuotes>,
+) {
+    // Records PUT more than once are verified once.
+    let mut payments: Vec<PaymentDigest> = Vec::new();
+    let mut replies: Vec<Vec<oneshot::Sender<PaidQuotes>>> = Vec::new();
+    let mut index: HashMap<PaymentDigest, usize> = HashMap::new();
+    for request in requests {
+        match index.get(&request.payment) {
+            Some(i) => replies[*i].push(request.reply),
+            None => {
+                let _ = index.insert(request.payment.clone(), payments.len());
+                payments.push(request.payment);
+                replies.push(vec![request.reply]);
+            }
+        }
+    }
+
+    debug!("Verifying a batch of {} payments", payments.len());
+    let results = match verifier.verify_batch(payments.clone()).await {
+        Ok(results) => results,
+        Err(err) => {
+            warn!(
+                "Failed to verify a batch of {} payments: {err}",
+                payments.len()
+            );
+            vec![Err(err); payments.len()]
+        }
+    };
+
+    let now = Instant::now();
+    for ((payment, result), replies) in payments.iter().zip(results).zip(replies) {
+        if let Ok(paid_quotes) = &result {
+            cache.lock().await.insert(payment, paid_quotes, now);
+        }
+        for reply in replies {
+            let _ = reply.send(result.clone());
+        }
+    }
+}
+
+/// The quotes of verified payments, with the amount paid to each, until the quotes expire.
+#[derive(Default)]
+struct VerifiedQuotes {
+    amounts: HashMap<QuoteHash, Amount>,
+    /// When each quote is dropped from `amounts`, oldest first.
+    expiries: VecDeque<(Instant, QuoteHash)>,
+}
+
+impl VerifiedQuotes {
+    const LIFETIME: Duration = Duration::from_secs(QUOTE_EXPIRATION_SECS);
+
+    /// Keep the quotes of a payment verified at `now`. Quotes of the payment the vault did not
+    /// check were paid nothing.
+    fn insert(
+        &mut self,
+        payment: &PaymentDigest,
+        paid_quotes: &[(QuoteHash, Amount)],
+        now: Instant,
+    ) {
+        self.prune(now);
+        for (quote_hash, _, _) in payment {
+            let amount = paid_quotes
+                .iter()
+                .find(|(paid_hash, _)| paid_hash == quote_hash)
+                .map(|(_, amount)| *amount)
+                .unwrap_or(Amount::ZERO);
+            if self.amounts.insert(*quote_hash, amount).is_none() {
+                self.expiries.push_back((now + Self::LIFETIME, *quote_hash));
+            }
+        }
+    }
+
+    /// The amount paid to `owned_quote_hashes`, if all the quotes of `payment` were verified.
+    fn amount_paid(
+        &mut self,
+        owned_quote_hashes: &[QuoteHash],
+        payment: &PaymentDigest,
+        now: Instant,
+    ) -> Option<Amount> {
+        self.prune(now);
+        if !payment
+            .iter()
+            .all(|(quote_hash, _, _)| self.amounts.contains_key(quote_hash))
+        {
+            return None;
+        }
+        Some(
+            owned_quote_hashes
+                .iter()
+                .filter_map(|quote_hash| self.amounts.get(quote_hash))
+                .sum(),
+        )
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((expiry, quote_hash)) = self.expiries.front() {
+            if *expiry > now {
+                break;
+            }
+            let _ = self.amounts.remove(quote_hash);
+            let _ = self.expiries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Pays every quote 1, or fails the whole batch while `fail` is set.
+    #[derive(Default)]
+    struct MockVault {
+        batch_sizes: Arc<std::sync::Mutex<Vec<usize>>>,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl VerifyBatch for MockVault {
+        async fn verify_batch(
+            &self,
+            payments: Vec<PaymentDigest>,
+        ) -> std::result::Result<Vec<PaidQuotes>, String> {
+            self.batch_sizes
+                .lock()
+                .expect("lock poisoned")
+                .push(payments.len());
+            if self.fail.load(Ordering::Relaxed) {
+                return Err("payment vault unreachable".to_string());
+            }
+            Ok(payments
+                .iter()
+                .map(|payment| {
+                    Ok(payment
+                        .iter()
+                        .map(|(quote_hash, _, _)| (*quote_hash, Amount::from(1)))
+                        .collect())
+                })
+                .collect())
+        }
+    }
+
+    fn payment(quote_hashes: &[QuoteHash]) -> PaymentDigest {
+        quote_hashes
+            .iter()
+            .map(|quote_hash| {
+                (
+                    *quote_hash,
+                    QuotingMetrics::default(),
+                    RewardsAddress::default(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn verified_quotes_are_kept_until_they_expire() {
+        let [a, b, c] = [[1; 32], [2; 32], [3; 32]].map(QuoteHash::from);
+        let payment = payment(&[a, b, c]);
+        let now = Instant::now();
+
+        let mut verified = VerifiedQuotes::default();
+        assert_eq!(verified.amount_paid(&[a], &payment, now), None);
+
+        // the vault only checked `a` and `b`
+        verified.insert(
+            &payment,
+            &[(a, Amount::from(10)), (b, Amount::from(20))],
+            now,
+        );
+        assert_eq!(
+            verified.amount_paid(&[a], &payment, now),
+            Some(Amount::from(10))
+        );
+        assert_eq!(
+            verified.amount_paid(&[a, b], &payment, now),
+            Some(Amount::from(30))
+        );
+        assert_eq!(
+            verified.amount_paid(&[c], &payment, now),
+            Some(Amount::ZERO)
+        );
+
+        // a payment with quotes that were not verified
+        let other = [4; 32].into();
+        assert_eq!(
+            verified.amount_paid(&[a], &super::tests::payment(&[a, other]), now),
+            None
+        );
+
+        let later = now + VerifiedQuotes::LIFETIME;
+        assert_eq!(verified.amount_paid(&[a], &payment, later), None);
+        assert!(verified.amounts.is_empty());
+        assert!(verified.expiries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn concurrent_verifications_share_a_batch_and_failures_are_not_cached() {
+        let vault = MockVault::default();
+        let batch_sizes = Arc::clone(&vault.batch_sizes);
+        let fail = Arc::clone(&vault.fail);
+        let verifier = PaymentVerifier::with_verifier(vault);
+        let payments: Vec<_> = (0..3u8)
+            .map(|i| payment(&[QuoteHash::from([i; 32])]))
+            .collect();
+        let verify = |payment: &PaymentDigest| {
+            let owned = vec![payment[0].0];
+            verifier.verify(owned, payment.clone())
+        };
+
+        // failed verifications are reported to every caller, and not kept
+        fail.store(true, Ordering::Relaxed);
+        let results = futures::future::join_all(payments.iter().map(verify)).await;
+        assert!(results.iter().all(|result| result.is_err()));
+        assert_eq!(*batch_sizes.lock().expect("lock poisoned"), vec![3]);
+
+        // so they are verified again, in a single batch
+        fail.store(false, Ordering::Relaxed);
+        let results = futures::future::join_all(payments.iter().map(verify)).await;
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Ok(amount) if *amount == Amount::from(1))));
+        assert_eq!(*batch_sizes.lock().expect("lock poisoned"), vec![3, 3]);
+
+        // successful verifications are kept
+        assert_eq!(verify(&payments[0]).await.ok(), Some(Amount::from(1)));
+        assert_eq!(batch_sizes.lock().expect("lock poisoned").len(), 2);
+    }
+}
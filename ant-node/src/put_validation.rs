@@ -9,14 +9,14 @@
 use std::collections::BTreeSet;
 
 use crate::{node::Node, Error, Marker, Result};
-use ant_evm::payment_vault::verify_data_payment;
 use ant_evm::{AttoTokens, ProofOfPayment};
 use ant_networking::NetworkError;
 use ant_protocol::storage::GraphEntry;
 use ant_protocol::{
     storage::{
-        try_deserialize_record, try_serialize_record, Chunk, DataTypes, GraphEntryAddress, Pointer,
-        RecordHeader, RecordKind, Scratchpad, ValidationType,
+        limit_graph_entry_forks, try_deserialize_record, try_serialize_record, Chunk, DataTypes,
+        GraphEntryAddress, Pointer, RecordHeader, RecordKind, Scratchpad, ValidationType,
+        MAX_GRAPH_ENTRY_FORKS,
     },
     NetworkAddress, PrettyPrintRecordKey,
 };
@@ -40,7 +40,12 @@ impl Node {
                 // Validate the payment and that we received what we asked.
                 // This stores any payments to disk
                 let payment_res = self
-                    .payment_for_us_exists_and_is_still_valid(&chunk.network_address(), payment)
+                    .payment_for_us_exists_and_is_still_valid(
+                        &chunk.network_address(),
+                        DataTypes::Chunk,
+                        chunk.payload_size(),
+                        payment,
+                    )
                     .await;
 
                 // Now that we've taken any money passed to us, regardless of the payment's validity,
@@ -106,6 +111,8 @@ impl Node {
                 let payment_res = self
                     .payment_for_us_exists_and_is_still_valid(
                         &scratchpad.network_address(),
+                        DataTypes::Scratchpad,
+                        scratchpad.payload_size(),
                         payment,
                     )
                     .await;
@@ -195,7 +202,12 @@ impl Node {
                 // However, if the transaction is already present, the incoming one shall be
                 // appended with the existing one, if content is different.
                 if let Err(err) = self
-                    .payment_for_us_exists_and_is_still_valid(&net_addr, payment)
+                    .payment_for_us_exists_and_is_still_valid(
+                        &net_addr,
+                        DataTypes::GraphEntry,
+                        transaction.size(),
+                        payment,
+                    )
                     .await
                 {
                     if already_exists {
@@ -289,7 +301,12 @@ impl Node {
                 // However, if the register already presents, the incoming one maybe for edit only.
                 // Hence the corresponding payment error shall not be thrown out.
                 if let Err(err) = self
-                    .payment_for_us_exists_and_is_still_valid(&net_addr, payment)
+                    .payment_for_us_exists_and_is_still_valid(
+                        &net_addr,
+                        DataTypes::Register,
+                        register.size(),
+                        payment,
+                    )
                     .await
                 {
                     if already_exists {
@@ -315,11 +332,27 @@ impl Node {
                 res
             }
             RecordKind::DataOnly(DataTypes::Pointer) => {
-                // Pointers should always be paid for
-                error!("Pointer should not be validated at this point");
-                Err(Error::InvalidPutWithoutPayment(
-                    PrettyPrintRecordKey::from(&record.key).into_owned(),
-                ))
+                // make sure we already have this pointer locally, else reject it as first time upload needs payment
+                let key = record.key.clone();
+                let pointer = try_deserialize_record::<Pointer>(&record)?;
+                let net_addr = NetworkAddress::from_pointer_address(pointer.network_address());
+                let pretty_key = PrettyPrintRecordKey::from(&key);
+                debug!("Got record to store without payment for pointer at {pretty_key:?}");
+                if !self.validate_key_and_existence(&net_addr, &key).await? {
+                    warn!("Ignore store without payment for pointer at {pretty_key:?}");
+                    return Err(Error::InvalidPutWithoutPayment(
+                        PrettyPrintRecordKey::from(&record.key).into_owned(),
+                    ));
+                }
+
+                // store the update, the signature proves it comes from the owner
+                let res = self
+                    .validate_and_store_pointer_record(pointer, key.clone(), true)
+                    .await;
+                if res.is_ok() {
+                    Marker::ValidPointerPutFromClient(&pretty_key).log();
+                }
+                res
             }
             RecordKind::DataWithPayment(DataTypes::Pointer) => {
                 let (payment, pointer) =
@@ -341,7 +374,12 @@ impl Node {
                 // The pointer may already exist during the replication.
                 // The payment shall get deposit to self even if the pointer already exists.
                 if let Err(err) = self
-                    .payment_for_us_exists_and_is_still_valid(&net_addr, payment)
+                    .payment_for_us_exists_and_is_still_valid(
+                        &net_addr,
+                        DataTypes::Pointer,
+                        pointer.size(),
+                        payment,
+                    )
                     .await
                 {
                     if already_exists {
@@ -352,21 +390,11 @@ impl Node {
                     }
                 }
 
-                let res = self.validate_and_store_pointer_record(pointer, key);
+                let res = self
+                    .validate_and_store_pointer_record(pointer, key.clone(), true)
+                    .await;
                 if res.is_ok() {
-                    let content_hash = XorName::from_content(&record.value);
-                    Marker::ValidPointerPutFromClient(&PrettyPrintRecordKey::from(&record.key))
-                        .log();
-                    self.replicate_valid_fresh_record(
-                        record.key.clone(),
-                        ValidationType::NonChunk(content_hash),
-                    );
-
-                    // Notify replication_fetcher to mark the attempt as completed.
-                    self.network().notify_fetch_completed(
-                        record.key.clone(),
-                        ValidationType::NonChunk(content_hash),
-                    );
+                    Marker::ValidPointerPutFromClient(&pretty_key).log();
                 }
                 res
             }
@@ -431,7 +459,8 @@ impl Node {
             RecordKind::DataOnly(DataTypes::Pointer) => {
                 let pointer = try_deserialize_record::<Pointer>(&record)?;
                 let key = record.key.clone();
-                self.validate_and_store_pointer_record(pointer, key)
+                self.validate_and_store_pointer_record(pointer, key, false)
+                    .await
             }
         }
     }
@@ -661,7 +690,7 @@ impl Node {
         }
 
         // verify the transactions
-        let mut validated_transactions: BTreeSet<GraphEntry> = transactions_for_key
+        let validated_transactions: BTreeSet<GraphEntry> = transactions_for_key
             .into_iter()
             .filter(|t| t.verify())
             .collect();
@@ -675,34 +704,78 @@ impl Node {
             Some(t) => t.address(),
         };
 
-        // add local transactions to the validated transactions, turn to Vec
+        // add local transactions to the validated transactions, keeping at most
+        // MAX_GRAPH_ENTRY_FORKS of them, chosen the same way on every node
         let local_txs = self.get_local_transactions(addr).await?;
-        validated_transactions.extend(local_txs.into_iter());
-        let validated_transactions: Vec<GraphEntry> = validated_transactions.into_iter().collect();
+        let (kept_transactions, dropped_transactions) = limit_graph_entry_forks(
+            local_txs
+                .iter()
+                .cloned()
+                .chain(validated_transactions.iter().cloned()),
+            MAX_GRAPH_ENTRY_FORKS,
+        );
+        let rejected = validated_transactions
+            .iter()
+            .filter(|t| dropped_transactions.contains(t))
+            .count();
+        // entries sorting lower than the stored ones take their place
+        let evicted = local_txs
+            .iter()
+            .filter(|t| dropped_transactions.contains(t))
+            .count();
+
+        if kept_transactions != local_txs {
+            // store the record into the local storage
+            let record = Record {
+                key: record_key.clone(),
+                value: try_serialize_record(
+                    &kept_transactions,
+                    RecordKind::DataOnly(DataTypes::GraphEntry),
+                )?
+                .to_vec(),
+                publisher: None,
+                expires: None,
+            };
+            self.network().put_local_record(record);
+            debug!("Successfully stored validated transactions at {pretty_key:?}");
+
+            // Just log the multiple transactions
+            if kept_transactions.len() > 1 {
+                debug!(
+                    "Got multiple transaction(s) of len {} at {pretty_key:?}",
+                    kept_transactions.len()
+                );
+            }
 
-        // store the record into the local storage
-        let record = Record {
-            key: record_key.clone(),
-            value: try_serialize_record(
-                &validated_transactions,
-                RecordKind::DataOnly(DataTypes::GraphEntry),
-            )?
-            .to_vec(),
-            publisher: None,
-            expires: None,
-        };
-        self.network().put_local_record(record);
-        debug!("Successfully stored validated transactions at {pretty_key:?}");
+            self.record_metrics(Marker::ValidTransactionRecordPutFromNetwork(&pretty_key));
+            self.record_metrics(Marker::GraphEntriesStored {
+                entries: kept_transactions.len(),
+            });
+        } else {
+            debug!("No new transactions to store at {pretty_key:?}");
+        }
 
-        // Just log the multiple transactions
-        if validated_transactions.len() > 1 {
-            debug!(
-                "Got multiple transaction(s) of len {} at {pretty_key:?}",
-                validated_transactions.len()
+        if evicted > 0 {
+            warn!(
+                "Evicted {evicted} stored transaction(s) at {pretty_key:?} for lower sorting ones over the fork limit of {MAX_GRAPH_ENTRY_FORKS}"
             );
         }
-
-        self.record_metrics(Marker::ValidTransactionRecordPutFromNetwork(&pretty_key));
+        if !dropped_transactions.is_empty() {
+            warn!(
+                "Dropped {} transaction(s) over the fork limit of {MAX_GRAPH_ENTRY_FORKS} at {pretty_key:?}",
+                dropped_transactions.len()
+            );
+            self.record_metrics(Marker::GraphEntryForksRejected {
+                rejected: dropped_transactions.len(),
+            });
+        }
+        if rejected > 0 {
+            return Err(Error::GraphEntryForkLimitReached {
+                address: addr,
+                limit: MAX_GRAPH_ENTRY_FORKS,
+                rejected,
+            });
+        }
         Ok(())
     }
 
@@ -710,6 +783,8 @@ impl Node {
     async fn payment_for_us_exists_and_is_still_valid(
         &self,
         address: &NetworkAddress,
+        data_type: DataTypes,
+        data_size: usize,
         payment: ProofOfPayment,
     ) -> Result<()> {
         let key = address.to_record_key();
@@ -726,6 +801,18 @@ impl Node {
         }
         debug!("Payment is valid for record {pretty_key}");
 
+        // check we quoted for this data, not for a cheaper type or a smaller size
+        let quoted_for_other_data = payment.quotes_by_peer(&self_peer_id).iter().any(|quote| {
+            quote.quoting_metrics.data_type != data_type.get_index()
+                || quote.quoting_metrics.data_size < data_size
+        });
+        if quoted_for_other_data {
+            warn!("Payment quote is not for a {data_type:?} of {data_size} bytes for record {pretty_key}");
+            return Err(Error::InvalidRequest(format!(
+                "Payment quote is not for a {data_type:?} of {data_size} bytes for record {pretty_key}"
+            )));
+        }
+
         // verify quote expiration
         if payment.has_expired() {
             warn!("Payment quote has expired for record {pretty_key}");
@@ -752,10 +839,10 @@ impl Node {
         // check if payment is valid on chain
         let payments_to_verify = payment.digest();
         debug!("Verifying payment for record {pretty_key}");
-        let reward_amount =
-            verify_data_payment(self.evm_network(), owned_payment_quotes, payments_to_verify)
-                .await
-                .map_err(|e| Error::EvmNetwork(format!("Failed to verify chunk payment: {e}")))?;
+        let reward_amount = self
+            .payment_verifier()
+            .verify(owned_payment_quotes, payments_to_verify)
+            .await?;
         debug!("Payment of {reward_amount:?} is valid for record {pretty_key}");
 
         // Notify `record_store` that the node received a payment.
@@ -864,11 +951,15 @@ impl Node {
         Ok(local_transactions)
     }
 
-    /// Validate and store a pointer record
-    pub(crate) fn validate_and_store_pointer_record(
+    /// Validate and store a `Pointer` to the RecordStore
+    ///
+    /// Only the version with the highest counter is kept: an incoming pointer with a counter lower
+    /// than or equal to the stored one is rejected, unless it is the very same pointer.
+    pub(crate) async fn validate_and_store_pointer_record(
         &self,
         pointer: Pointer,
         key: RecordKey,
+        is_client_put: bool,
     ) -> Result<()> {
         // Verify the pointer's signature
         if !pointer.verify() {
@@ -883,7 +974,7 @@ impl Node {
             return Err(Error::RecordKeyMismatch);
         }
 
-        // Store the pointer
+        let pretty_key = PrettyPrintRecordKey::from(&key).into_owned();
         let record = Record {
             key: key.clone(),
             value: try_serialize_record(&pointer, RecordKind::DataOnly(DataTypes::Pointer))?
@@ -891,11 +982,116 @@ impl Node {
             publisher: None,
             expires: None,
         };
-        self.network().put_local_record(record);
+        let content_hash = XorName::from_content(&record.value);
 
-        let content_hash = XorName::from_content(&pointer.network_address().to_bytes());
-        self.replicate_valid_fresh_record(key, ValidationType::NonChunk(content_hash));
+        // check that we don't have a newer version of the pointer locally
+        let local_pointer = match self.network().get_local_record(&key).await? {
+            Some(local_record) => Some(try_deserialize_record::<Pointer>(&local_record)?),
+            None => None,
+        };
+        match pointer_update(local_pointer.as_ref(), &pointer) {
+            PointerUpdate::AlreadyStored => {
+                debug!(
+                    "Pointer {pretty_key:?} with counter {} is already stored",
+                    pointer.count()
+                );
+            }
+            PointerUpdate::Store => {
+                info!(
+                    "Storing pointer {pretty_key:?} with counter {} as Record locally",
+                    pointer.count()
+                );
+                self.network().put_local_record(record);
+                if !is_client_put {
+                    self.record_metrics(Marker::ValidPointerRecordPutFromNetwork(&pretty_key));
+                }
+            }
+            PointerUpdate::Outdated { local } => {
+                warn!(
+                    "Rejecting Pointer PUT at {pretty_key:?} with counter {} not above the current counter {local}",
+                    pointer.count()
+                );
+                return Err(Error::IgnoringOutdatedPointerPut {
+                    local,
+                    incoming: pointer.count(),
+                });
+            }
+        }
+
+        // Replicate by the content hash of the stored record, so that peers holding an older
+        // version see it differs from theirs and fetch the new one.
+        // A `replicated in` pointer shall not trigger any further replication out.
+        if is_client_put {
+            self.replicate_valid_fresh_record(key.clone(), ValidationType::NonChunk(content_hash));
+
+            // Notify replication_fetcher to mark the attempt as completed.
+            self.network()
+                .notify_fetch_completed(key, ValidationType::NonChunk(content_hash));
+        }
 
         Ok(())
     }
 }
+
+/// What to do with an incoming pointer, given the one stored locally.
+#[derive(Debug, PartialEq, Eq)]
+enum PointerUpdate {
+    /// The very same pointer is stored already.
+    AlreadyStored,
+    /// The incoming pointer is new, or has a higher counter than the stored one.
+    Store,
+    /// The stored pointer has a counter as high or higher, the incoming one is rejected.
+    Outdated { local: u32 },
+}
+
+/// Only accept a pointer with a higher counter than the stored one, so that an older version can
+/// never overwrite a newer one, and one with the same counter is not replaced by another.
+fn pointer_update(local: Option<&Pointer>, incoming: &Pointer) -> PointerUpdate {
+    match local {
+        Some(local) if local == incoming => PointerUpdate::AlreadyStored,
+        Some(local) if local.count() >= incoming.count() => PointerUpdate::Outdated {
+            local: local.count(),
+        },
+        _ => PointerUpdate::Store,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_protocol::storage::{ChunkAddress, PointerTarget};
+    use bls::SecretKey;
+
+    fn chunk_target() -> PointerTarget {
+        PointerTarget::ChunkAddress(ChunkAddress::new(XorName::random(&mut rand::thread_rng())))
+    }
+
+    #[test]
+    fn pointer_update_only_accepts_higher_counters() {
+        let owner = SecretKey::random();
+        let target = chunk_target();
+        let pointer = |counter| Pointer::new(owner.public_key(), counter, target.clone(), &owner);
+        let local = pointer(5);
+
+        assert_eq!(pointer_update(None, &local), PointerUpdate::Store);
+        assert_eq!(
+            pointer_update(Some(&local), &local.clone()),
+            PointerUpdate::AlreadyStored
+        );
+        assert_eq!(
+            pointer_update(Some(&local), &pointer(6)),
+            PointerUpdate::Store
+        );
+        assert_eq!(
+            pointer_update(Some(&local), &pointer(4)),
+            PointerUpdate::Outdated { local: 5 }
+        );
+
+        // same counter but a different target
+        let other = Pointer::new(owner.public_key(), 5, chunk_target(), &owner);
+        assert_eq!(
+            pointer_update(Some(&local), &other),
+            PointerUpdate::Outdated { local: 5 }
+        );
+    }
+}
@@ -11,6 +11,7 @@ mod chunk_proof;
 mod cmd;
 mod node_id;
 mod query;
+mod record_version;
 mod register;
 mod response;
 
@@ -19,6 +20,7 @@ pub use self::{
     cmd::Cmd,
     node_id::NodeId,
     query::Query,
+    record_version::RecordVersion,
     register::RegisterCmd,
     response::{CmdResponse, QueryResponse},
 };
@@ -12,7 +12,8 @@ use std::fmt;
 /// The nonce provided by the verifier
 pub type Nonce = u64;
 
-/// The hash(record_value + nonce) that is used to prove the existence of a chunk
+/// The hash(record_value + nonce) that is used to prove the existence of a chunk, or of the exact
+/// version of any other record
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct ChunkProof([u8; 32]);
 
@@ -23,6 +23,12 @@ pub enum Query {
     GetStoreQuote {
         /// The Address of the record to be stored.
         key: NetworkAddress,
+        /// The type of the record to be stored, as its [`DataTypes`] index.
+        ///
+        /// [`DataTypes`]: crate::storage::DataTypes
+        data_type: u32,
+        /// The size in bytes of the data to be stored.
+        data_size: usize,
         /// The random nonce that nodes use to produce the Proof (i.e., hash(record+nonce))
         /// Set to None if no need to carry out storage check.
         nonce: Option<Nonce>,
@@ -64,6 +70,21 @@ pub enum Query {
         /// Node shall try their best to fulfill the number, based on their capacity.
         difficulty: usize,
     },
+    /// Get the proofs that the records closest to the given NetworkAddress, of any type, exist
+    /// with the requested node, and which version of each it holds.
+    ///
+    /// This should eventually lead to a [`GetRecordExistenceProof`] response.
+    ///
+    /// [`GetRecordExistenceProof`]: super::QueryResponse::GetRecordExistenceProof
+    GetRecordExistenceProof {
+        /// The Address the challenged records are closest to.
+        key: NetworkAddress,
+        /// The random nonce that the node uses to produce the Proof (i.e., hash(record+nonce))
+        nonce: Nonce,
+        /// Defines the expected number of answers to the challenge.
+        /// Node shall try their best to fulfill the number, based on their capacity.
+        difficulty: usize,
+    },
     /// Queries close_group peers whether the target peer is a bad_node
     CheckNodeInProblem(NetworkAddress),
     /// Query the peers in range to the target address, from the receiver's perspective.
@@ -91,6 +112,7 @@ impl Query {
             | Query::GetReplicatedRecord { key, .. }
             | Query::GetRegisterRecord { key, .. }
             | Query::GetChunkExistenceProof { key, .. }
+            | Query::GetRecordExistenceProof { key, .. }
             | Query::GetClosestPeers { key, .. } => key.clone(),
         }
     }
@@ -101,10 +123,15 @@ impl std::fmt::Display for Query {
         match self {
             Query::GetStoreQuote {
                 key,
+                data_type,
+                data_size,
                 nonce,
                 difficulty,
             } => {
-                write!(f, "Query::GetStoreQuote({key:?} {nonce:?} {difficulty})")
+                write!(
+                    f,
+                    "Query::GetStoreQuote({key:?} {data_type} {data_size} {nonce:?} {difficulty})"
+                )
             }
             Query::GetReplicatedRecord { key, requester } => {
                 write!(f, "Query::GetReplicatedRecord({requester:?} {key:?})")
@@ -122,6 +149,16 @@ impl std::fmt::Display for Query {
                     "Query::GetChunkExistenceProof({key:?} {nonce:?} {difficulty})"
                 )
             }
+            Query::GetRecordExistenceProof {
+                key,
+                nonce,
+                difficulty,
+            } => {
+                write!(
+                    f,
+                    "Query::GetRecordExistenceProof({key:?} {nonce:?} {difficulty})"
+                )
+            }
             Query::CheckNodeInProblem(address) => {
                 write!(f, "Query::CheckNodeInProblem({address:?})")
             }
@@ -0,0 +1,151 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::storage::{
+    try_deserialize_record, DataTypes, Pointer, RecordHeader, RecordKind, Scratchpad,
+};
+use bls::{PublicKey, Signature};
+use libp2p::kad::Record;
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// The version of a record that a proof of its existence is for.
+///
+/// Records with a counter come with what their owner signed for that version, so that a node
+/// holding another version can check the answer is for a version the owner really published.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum RecordVersion {
+    /// Any copy of a chunk is the same.
+    Chunk,
+    /// A record without a counter, identified by the hash of its content.
+    Content(XorName),
+    /// A pointer, small enough to be sent as a whole.
+    Pointer(Pointer),
+    /// The signed header of a scratchpad, leaving out its data.
+    Scratchpad {
+        owner: PublicKey,
+        counter: u64,
+        encrypted_data_hash: XorName,
+        signature: Signature,
+    },
+}
+
+impl RecordVersion {
+    /// The version of the given locally stored record.
+    pub fn from_record(record: &Record) -> Self {
+        let kind = RecordHeader::from_record(record).map(|header| header.kind);
+        match kind {
+            Ok(RecordKind::DataOnly(DataTypes::Chunk)) => return Self::Chunk,
+            Ok(RecordKind::DataOnly(DataTypes::Pointer)) => {
+                if let Ok(pointer) = try_deserialize_record::<Pointer>(record) {
+                    return Self::Pointer(pointer);
+                }
+            }
+            Ok(RecordKind::DataOnly(DataTypes::Scratchpad)) => {
+                if let Ok(scratchpad) = try_deserialize_record::<Scratchpad>(record) {
+                    if let Some(signature) = scratchpad.signature() {
+                        return Self::Scratchpad {
+                            owner: *scratchpad.owner(),
+                            counter: scratchpad.count(),
+                            encrypted_data_hash: scratchpad.encrypted_data_hash(),
+                            signature: signature.clone(),
+                        };
+                    }
+                }
+            }
+            _ => {}
+        }
+        Self::Content(XorName::from_content(&record.value))
+    }
+
+    /// The type, owner and counter of this version, if it has one and is validly signed.
+    pub fn verified_counter(&self) -> Option<(DataTypes, &PublicKey, u64)> {
+        match self {
+            Self::Chunk | Self::Content(_) => None,
+            Self::Pointer(pointer) => pointer.verify().then(|| {
+                (
+                    DataTypes::Pointer,
+                    pointer.owner(),
+                    u64::from(pointer.count()),
+                )
+            }),
+            Self::Scratchpad {
+                owner,
+                counter,
+                encrypted_data_hash,
+                signature,
+            } => {
+                let bytes = Scratchpad::bytes_to_sign(*counter, *encrypted_data_hash);
+                owner
+                    .verify(signature, bytes)
+                    .then_some((DataTypes::Scratchpad, owner, *counter))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{try_serialize_record, ChunkAddress, PointerTarget};
+    use bls::SecretKey;
+    use bytes::Bytes;
+    use libp2p::kad::RecordKey;
+
+    fn record_of<T: Serialize>(data: &T, data_type: DataTypes) -> Record {
+        let value = try_serialize_record(data, RecordKind::DataOnly(data_type))
+            .expect("serialize record")
+            .to_vec();
+        Record::new(RecordKey::new(&[1, 2, 3]), value)
+    }
+
+    #[test]
+    fn only_validly_signed_versions_have_a_counter() {
+        let sk = SecretKey::random();
+        let target = PointerTarget::ChunkAddress(ChunkAddress::new(XorName::random(
+            &mut rand::thread_rng(),
+        )));
+        let pointer = Pointer::new(sk.public_key(), 7, target.clone(), &sk);
+        let version = RecordVersion::from_record(&record_of(&pointer, DataTypes::Pointer));
+        assert_eq!(version, RecordVersion::Pointer(pointer));
+        assert_eq!(
+            version.verified_counter(),
+            Some((DataTypes::Pointer, &sk.public_key(), 7))
+        );
+
+        let forged = RecordVersion::Pointer(Pointer::new(
+            sk.public_key(),
+            6,
+            target,
+            &SecretKey::random(),
+        ));
+        assert_eq!(forged.verified_counter(), None);
+
+        let mut scratchpad = Scratchpad::new(sk.public_key(), 0);
+        let _ = scratchpad.update_and_sign(Bytes::from_static(b"data"), &sk);
+        let version = RecordVersion::from_record(&record_of(&scratchpad, DataTypes::Scratchpad));
+        assert_eq!(
+            version.verified_counter(),
+            Some((DataTypes::Scratchpad, &sk.public_key(), 1))
+        );
+
+        let RecordVersion::Scratchpad {
+            owner, signature, ..
+        } = version
+        else {
+            panic!("expected a scratchpad version, got {version:?}");
+        };
+        let tampered = RecordVersion::Scratchpad {
+            owner,
+            counter: 0,
+            encrypted_data_hash: scratchpad.encrypted_data_hash(),
+            signature,
+        };
+        assert_eq!(tampered.verified_counter(), None);
+    }
+}
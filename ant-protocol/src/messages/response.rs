@@ -8,7 +8,7 @@
 
 use crate::{error::Result, NetworkAddress};
 
-use super::ChunkProof;
+use super::{ChunkProof, RecordVersion};
 use ant_evm::PaymentQuote;
 use bytes::Bytes;
 use core::fmt;
@@ -58,6 +58,12 @@ pub enum QueryResponse {
     ///
     /// [`GetChunkExistenceProof`]: crate::messages::Query::GetChunkExistenceProof
     GetChunkExistenceProof(Vec<(NetworkAddress, Result<ChunkProof>)>),
+    // ===== RecordExistenceProof =====
+    //
+    /// Response to [`GetRecordExistenceProof`], with the version of each record the proof is for.
+    ///
+    /// [`GetRecordExistenceProof`]: crate::messages::Query::GetRecordExistenceProof
+    GetRecordExistenceProof(Vec<(NetworkAddress, Result<(RecordVersion, ChunkProof)>)>),
     // ===== GetClosestPeers =====
     //
     /// Response to [`GetClosestPeers`]
@@ -130,6 +136,10 @@ impl Debug for QueryResponse {
                 let addresses: Vec<_> = proofs.iter().map(|(addr, _)| addr.clone()).collect();
                 write!(f, "GetChunkExistenceProof(checked chunks: {addresses:?})")
             }
+            QueryResponse::GetRecordExistenceProof(proofs) => {
+                let addresses: Vec<_> = proofs.iter().map(|(addr, _)| addr.clone()).collect();
+                write!(f, "GetRecordExistenceProof(checked records: {addresses:?})")
+            }
             QueryResponse::GetClosestPeers { target, peers, .. } => {
                 let addresses: Vec<_> = peers.iter().map(|(addr, _)| addr.clone()).collect();
                 write!(
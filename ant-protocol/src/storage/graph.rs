@@ -9,6 +9,7 @@
 use super::address::GraphEntryAddress;
 use bls::SecretKey;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 // re-exports
 pub use bls::{PublicKey, Signature};
@@ -16,6 +17,35 @@ pub use bls::{PublicKey, Signature};
 /// Content of a graph, limited to 32 bytes
 pub type GraphContent = [u8; 32];
 
+/// Maximum number of entries stored at a single graph entry address. This is a protocol
+/// parameter: all nodes of a network must use the same limit, or they keep different entries for
+/// the same address.
+///
+/// More than one entry at an address is a fork. Owners can sign as many conflicting entries as
+/// they want, but each one is stored by every node holding the address, so nodes keep at most
+/// this many of them, see [`limit_graph_entry_forks`].
+pub const MAX_GRAPH_ENTRY_FORKS: usize = 5;
+
+/// Split the entries found at a single address into the ones nodes keep and the ones they drop
+/// to stay within `max_forks` entries.
+///
+/// The entries kept are the lowest in the `GraphEntry` ordering, so every node keeps the same
+/// ones regardless of the order it received them in, as long as they use the same limit.
+/// Duplicates are removed. As the order decides and not the arrival, an entry sorting lower than
+/// the ones stored so far evicts the highest of them once the limit is reached.
+pub fn limit_graph_entry_forks(
+    entries: impl IntoIterator<Item = GraphEntry>,
+    max_forks: usize,
+) -> (Vec<GraphEntry>, Vec<GraphEntry>) {
+    let mut kept: Vec<GraphEntry> = entries
+        .into_iter()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let dropped = kept.split_off(max_forks.min(kept.len()));
+    (kept, dropped)
+}
+
 /// A generic GraphEntry on the Network
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, Ord, PartialOrd)]
 pub struct GraphEntry {
@@ -98,6 +128,11 @@ impl GraphEntry {
         GraphEntryAddress::from_owner(self.owner)
     }
 
+    /// Size of this graph entry serialized into a record, as quoted for storing it.
+    pub fn size(&self) -> usize {
+        super::serialized_record_size(self)
+    }
+
     /// Get the bytes that the signature is calculated from.
     pub fn bytes_for_signature(&self) -> Vec<u8> {
         Self::bytes_to_sign(&self.owner, &self.parents, &self.content, &self.outputs)
@@ -108,3 +143,48 @@ impl GraphEntry {
             .verify(&self.signature, self.bytes_for_signature())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fork(owner_sk: &SecretKey, content: u8) -> GraphEntry {
+        GraphEntry::new(owner_sk.public_key(), vec![], [content; 32], None, owner_sk)
+    }
+
+    #[test]
+    fn fork_limit_keeps_the_same_entries_in_any_order() {
+        let owner_sk = SecretKey::random();
+        let entries: Vec<_> = (0..MAX_GRAPH_ENTRY_FORKS as u8 + 3)
+            .map(|content| fork(&owner_sk, content))
+            .collect();
+
+        let (kept, dropped) = limit_graph_entry_forks(entries.clone(), MAX_GRAPH_ENTRY_FORKS);
+        assert_eq!(kept.len(), MAX_GRAPH_ENTRY_FORKS);
+        assert_eq!(dropped.len(), 3);
+        assert!(kept
+            .iter()
+            .all(|entry| entry.content[0] < MAX_GRAPH_ENTRY_FORKS as u8));
+
+        let (kept_reversed, _) =
+            limit_graph_entry_forks(entries.into_iter().rev(), MAX_GRAPH_ENTRY_FORKS);
+        assert_eq!(kept, kept_reversed);
+
+        // a lower limit keeps the lowest of them
+        let (kept_one, dropped) = limit_graph_entry_forks(kept.clone(), 1);
+        assert_eq!(kept_one, kept[..1]);
+        assert_eq!(dropped, kept[1..]);
+    }
+
+    #[test]
+    fn fork_limit_ignores_duplicates() {
+        let owner_sk = SecretKey::random();
+        let entry = fork(&owner_sk, 0);
+        let (kept, dropped) = limit_graph_entry_forks(
+            vec![entry.clone(); MAX_GRAPH_ENTRY_FORKS + 1],
+            MAX_GRAPH_ENTRY_FORKS,
+        );
+        assert_eq!(kept, vec![entry]);
+        assert!(dropped.is_empty());
+    }
+}
@@ -190,6 +190,12 @@ pub fn try_serialize_record<T: serde::Serialize>(
     Ok(bytes.freeze())
 }
 
+/// Size of `data` once serialized into a record, leaving out the header: what storing it is
+/// quoted for. Clients and nodes both compute it from the data itself, so they agree on it.
+pub fn serialized_record_size<T: serde::Serialize>(data: &T) -> usize {
+    rmp_serde::to_vec(data).map_or(0, |bytes| bytes.len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,4 +286,15 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn serialized_record_size_leaves_out_the_header() {
+        let data = (7u32, vec![1u8; 100], "pointer".to_string());
+        let record = try_serialize_record(&data, RecordKind::DataOnly(DataTypes::Pointer))
+            .expect("serializable data");
+        assert_eq!(
+            serialized_record_size(&data),
+            record.len() - RecordHeader::SIZE
+        );
+    }
 }
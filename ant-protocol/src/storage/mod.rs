@@ -21,10 +21,10 @@ use std::{num::NonZeroUsize, time::Duration};
 pub use self::{
     address::{ChunkAddress, GraphEntryAddress, PointerAddress, ScratchpadAddress},
     chunks::Chunk,
-    graph::GraphEntry,
+    graph::{limit_graph_entry_forks, GraphEntry, MAX_GRAPH_ENTRY_FORKS},
     header::{
-        try_deserialize_record, try_serialize_record, DataTypes, RecordHeader, RecordKind,
-        ValidationType,
+        serialized_record_size, try_deserialize_record, try_serialize_record, DataTypes,
+        RecordHeader, RecordKind, ValidationType,
     },
     scratchpad::Scratchpad,
 };
@@ -20,7 +20,7 @@ pub enum PointerError {
     SerializationError(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Pointer {
     owner: PublicKey,
     counter: u32,
@@ -28,7 +28,7 @@ pub struct Pointer {
     signature: Signature,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum PointerTarget {
     ChunkAddress(ChunkAddress),
     GraphEntryAddress(GraphEntryAddress),
@@ -104,10 +104,25 @@ impl Pointer {
         self.target.xorname()
     }
 
+    /// Get the owner of this pointer
+    pub fn owner(&self) -> &PublicKey {
+        &self.owner
+    }
+
+    /// Get the target this pointer points to
+    pub fn target(&self) -> &PointerTarget {
+        &self.target
+    }
+
     pub fn count(&self) -> u32 {
         self.counter
     }
 
+    /// Size of this pointer serialized into a record, as quoted for storing it.
+    pub fn size(&self) -> usize {
+        crate::storage::serialized_record_size(self)
+    }
+
     /// Get the network address for this pointer
     pub fn network_address(&self) -> PointerAddress {
         PointerAddress::from_owner(self.owner)
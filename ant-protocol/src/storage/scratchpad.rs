@@ -74,10 +74,7 @@ impl Scratchpad {
 
         self.encrypted_data = Bytes::from(pk.encrypt(unencrypted_data).to_bytes());
 
-        let encrypted_data_xorname = self.encrypted_data_hash().to_vec();
-
-        let mut bytes_to_sign = self.counter.to_be_bytes().to_vec();
-        bytes_to_sign.extend(encrypted_data_xorname);
+        let bytes_to_sign = Self::bytes_to_sign(self.counter, self.encrypted_data_hash());
 
         self.signature = Some(sk.sign(&bytes_to_sign));
         next_count
@@ -87,8 +84,7 @@ impl Scratchpad {
     /// owner's public key.
     pub fn is_valid(&self) -> bool {
         if let Some(signature) = &self.signature {
-            let mut signing_bytes = self.counter.to_be_bytes().to_vec();
-            signing_bytes.extend(self.encrypted_data_hash().to_vec()); // add the count
+            let signing_bytes = Self::bytes_to_sign(self.counter, self.encrypted_data_hash());
 
             self.owner().verify(signature, &signing_bytes)
         } else {
@@ -96,6 +92,18 @@ impl Scratchpad {
         }
     }
 
+    /// Get the bytes the owner signs for the given count and encrypted_data hash
+    pub fn bytes_to_sign(counter: u64, encrypted_data_hash: XorName) -> Vec<u8> {
+        let mut bytes = counter.to_be_bytes().to_vec();
+        bytes.extend(encrypted_data_hash.to_vec());
+        bytes
+    }
+
+    /// Returns the signature, if the scratchpad has been signed.
+    pub fn signature(&self) -> Option<&Signature> {
+        self.signature.as_ref()
+    }
+
     /// Returns the encrypted_data.
     pub fn encrypted_data(&self) -> &Bytes {
         &self.encrypted_data
@@ -54,6 +54,12 @@ impl SignedRegister {
         }
     }
 
+    /// Size of this register serialized into a record, as quoted for storing it. Registers are
+    /// paid for once, at creation, so this is the size of the newly created register.
+    pub fn size(&self) -> usize {
+        rmp_serde::to_vec(self).map_or(0, |bytes| bytes.len())
+    }
+
     /// Return the base register. This is the register before any operations have been applied.
     pub fn base_register(&self) -> &Register {
         &self.register
@@ -0,0 +1,383 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Limits on what a client spends on payments.
+//!
+//! The limits are checked against the quotes before any payment transaction is sent, so a price
+//! spike aborts an upload instead of paying for it.
+
+use std::fmt;
+use std::sync::Mutex;
+
+use ant_evm::AttoTokens;
+use ant_protocol::storage::DataTypes;
+use xor_name::XorName;
+
+use crate::client::data::PayError;
+use crate::client::quote::StoreQuote;
+use crate::Client;
+
+/// Limits on what a client may spend on payments made with a wallet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CostLimits {
+    /// Maximum total spent by the client, over all of its payments.
+    pub max_total: Option<AttoTokens>,
+    /// Maximum price of a single content address (e.g. a chunk).
+    pub max_chunk_price: Option<AttoTokens>,
+}
+
+/// Breakdown of the estimated cost of a payment, reported when it exceeds the [`CostLimits`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Number of content addresses that need paying for.
+    pub content_addrs: usize,
+    /// Total price of the payment.
+    pub total: AttoTokens,
+    /// Highest price of a single content address.
+    pub max_chunk_price: AttoTokens,
+    /// What the client had already spent before this payment.
+    pub spent: AttoTokens,
+    /// The limits the payment was checked against.
+    pub limits: CostLimits,
+}
+
+impl CostEstimate {
+    fn of(quotes: &StoreQuote, spent: AttoTokens, limits: CostLimits) -> Self {
+        let max_chunk_price = quotes
+            .0
+            .values()
+            .map(|quote| quote.price())
+            .max()
+            .unwrap_or_default();
+
+        Self {
+            content_addrs: quotes.len(),
+            total: AttoTokens::from_atto(quotes.price()),
+            max_chunk_price: AttoTokens::from_atto(max_chunk_price),
+            spent,
+            limits,
+        }
+    }
+
+    /// Whether the payment stays within the limits.
+    pub fn is_within_limits(&self) -> bool {
+        let within_total = match self.limits.max_total {
+            Some(max_total) => self
+                .spent
+                .checked_add(self.total)
+                .is_some_and(|total| total <= max_total),
+            None => true,
+        };
+        let within_chunk_price = self
+            .limits
+            .max_chunk_price
+            .is_none_or(|max_chunk_price| self.max_chunk_price <= max_chunk_price);
+
+        within_total && within_chunk_price
+    }
+}
+
+impl fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} content addresses for {} tokens (highest price {}), with {} already spent",
+            self.content_addrs, self.total, self.max_chunk_price, self.spent
+        )?;
+        if let Some(max_total) = self.limits.max_total {
+            write!(f, ", max total {max_total}")?;
+        }
+        if let Some(max_chunk_price) = self.limits.max_chunk_price {
+            write!(f, ", max chunk price {max_chunk_price}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the client spent, checked against its limits. Shared by all clones of a client.
+#[derive(Debug)]
+pub(crate) struct CostBudget {
+    limits: CostLimits,
+    spending: Mutex<Spending>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Spending {
+    /// What was spent or reserved, including what was set aside.
+    spent: AttoTokens,
+    /// What was set aside for an upload by [`CostBudget::set_aside`] and not paid yet.
+    set_aside: AttoTokens,
+}
+
+/// A payment reserved from the budget with [`CostBudget::reserve`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Reservation {
+    /// Total reserved for the payment.
+    pub(crate) total: AttoTokens,
+    /// Part of the total that was drawn from what was set aside.
+    from_set_aside: AttoTokens,
+}
+
+impl Default for CostBudget {
+    fn default() -> Self {
+        Self::new(CostLimits::default())
+    }
+}
+
+impl CostBudget {
+    pub(crate) fn new(limits: CostLimits) -> Self {
+        Self {
+            limits,
+            spending: Mutex::new(Spending {
+                spent: AttoTokens::zero(),
+                set_aside: AttoTokens::zero(),
+            }),
+        }
+    }
+
+    /// Reserve the total of `quotes` from the budget, or return the estimate if the payment
+    /// would exceed the limits. The reservation is counted as spent until it is released.
+    ///
+    /// The payment is drawn from what was set aside first, only what goes beyond it is checked
+    /// against the total limit.
+    pub(crate) fn reserve(&self, quotes: &StoreQuote) -> Result<Reservation, Box<CostEstimate>> {
+        let mut spending = self.spending.lock().expect("cost budget lock poisoned");
+        let total = AttoTokens::from_atto(quotes.price());
+        let from_set_aside = total.min(spending.set_aside);
+        let spent_before = spending
+            .spent
+            .checked_sub(from_set_aside)
+            .unwrap_or_else(AttoTokens::zero);
+        let estimate = CostEstimate::of(quotes, spent_before, self.limits);
+        info!("Estimated payment cost: {estimate}");
+
+        if !estimate.is_within_limits() {
+            return Err(Box::new(estimate));
+        }
+
+        spending.set_aside = spending
+            .set_aside
+            .checked_sub(from_set_aside)
+            .unwrap_or_else(AttoTokens::zero);
+        spending.spent = spent_before.checked_add(total).unwrap_or(spending.spent);
+        Ok(Reservation {
+            total,
+            from_set_aside,
+        })
+    }
+
+    /// Set the total of `quotes` aside for payments to come, or return the estimate if it would
+    /// exceed the limits. What is set aside is counted as spent until it is paid or released.
+    pub(crate) fn set_aside(&self, quotes: &StoreQuote) -> Result<CostEstimate, Box<CostEstimate>> {
+        let mut spending = self.spending.lock().expect("cost budget lock poisoned");
+        let estimate = CostEstimate::of(quotes, spending.spent, self.limits);
+        info!("Estimated upload cost: {estimate}");
+
+        if !estimate.is_within_limits() {
+            return Err(Box::new(estimate));
+        }
+
+        spending.spent = spending
+            .spent
+            .checked_add(estimate.total)
+            .unwrap_or(spending.spent);
+        spending.set_aside = spending
+            .set_aside
+            .checked_add(estimate.total)
+            .unwrap_or(spending.set_aside);
+        Ok(estimate)
+    }
+
+    /// Give back the part of a reservation that was not paid after all.
+    ///
+    /// What was drawn from what was set aside goes back to it, so it stays reserved for the rest
+    /// of the upload and is given back with it by [`CostBudget::release_set_aside`].
+    pub(crate) fn release(&self, reservation: Reservation, paid: AttoTokens) {
+        let mut spending = self.spending.lock().expect("cost budget lock poisoned");
+        let unpaid = reservation
+            .total
+            .checked_sub(paid)
+            .unwrap_or_else(AttoTokens::zero);
+        let to_set_aside = unpaid.min(reservation.from_set_aside);
+        let to_budget = unpaid
+            .checked_sub(to_set_aside)
+            .unwrap_or_else(AttoTokens::zero);
+
+        spending.set_aside = spending
+            .set_aside
+            .checked_add(to_set_aside)
+            .unwrap_or(spending.set_aside);
+        spending.spent = spending
+            .spent
+            .checked_sub(to_budget)
+            .unwrap_or_else(AttoTokens::zero);
+    }
+
+    /// Give back what is left of what was set aside.
+    pub(crate) fn release_set_aside(&self) {
+        let mut spending = self.spending.lock().expect("cost budget lock poisoned");
+        spending.spent = spending
+            .spent
+            .checked_sub(spending.set_aside)
+            .unwrap_or_else(AttoTokens::zero);
+        spending.set_aside = AttoTokens::zero();
+    }
+
+    pub(crate) fn spent(&self) -> AttoTokens {
+        self.spending
+            .lock()
+            .expect("cost budget lock poisoned")
+            .spent
+    }
+}
+
+impl Client {
+    /// Limit what this client and its clones spend on payments made with a wallet, replacing the
+    /// limits from [`ClientConfig`](crate::ClientConfig). What was spent so far is reset.
+    pub fn set_cost_limits(&mut self, limits: CostLimits) {
+        debug!("Cost limits set to {limits:?}");
+        self.cost_budget = std::sync::Arc::new(CostBudget::new(limits));
+    }
+
+    /// The limits on what this client spends on payments.
+    pub fn cost_limits(&self) -> CostLimits {
+        self.cost_budget.limits
+    }
+
+    /// What this client spent on payments since its cost limits were set.
+    pub fn cost_spent(&self) -> AttoTokens {
+        self.cost_budget.spent()
+    }
+
+    /// Quote storing data at all of the content addresses of an upload and set the total aside
+    /// under the cost limits, before any of it is paid for.
+    ///
+    /// Uploads pay in several payments (per batch of chunks, per file), each checked against the
+    /// limits on its own. Reserving the whole upload first makes it fail before the first payment
+    /// instead of halfway through: the payments draw from what was set aside, only a price rise
+    /// since the quotes can still exceed the limits. Give back what is left when the upload is
+    /// done with [`Client::release_cost_reservation`].
+    ///
+    /// Returns the estimated cost, or [`PayError::CostLimitExceeded`] with it if it would exceed
+    /// the limits.
+    pub async fn reserve_cost(
+        &self,
+        data_type: DataTypes,
+        content_addrs: impl Iterator<Item = (XorName, usize)>,
+    ) -> Result<CostEstimate, PayError> {
+        let quotes = self.get_store_quotes(data_type, content_addrs).await?;
+        self.cost_budget
+            .set_aside(&quotes)
+            .map_err(PayError::CostLimitExceeded)
+    }
+
+    /// Give back what is left of the cost reserved with [`Client::reserve_cost`].
+    pub fn release_cost_reservation(&self) {
+        self.cost_budget.release_set_aside();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::quote::QuoteForAddress;
+    use ant_evm::{Amount, PaymentQuote};
+    use libp2p::PeerId;
+    use std::collections::HashMap;
+    use xor_name::XorName;
+
+    fn quotes(prices: &[u64]) -> StoreQuote {
+        let rng = &mut rand::thread_rng();
+        StoreQuote(
+            prices
+                .iter()
+                .map(|price| {
+                    let addr = XorName::random(rng);
+                    let quote = (
+                        PeerId::random(),
+                        PaymentQuote::test_dummy(addr),
+                        Amount::from(*price),
+                    );
+                    (addr, QuoteForAddress(vec![quote]))
+                })
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn budget_rejects_payments_over_the_limits() {
+        let budget = CostBudget::new(CostLimits {
+            max_total: Some(AttoTokens::from_u64(100)),
+            max_chunk_price: Some(AttoTokens::from_u64(40)),
+        });
+
+        let reserved = budget.reserve(&quotes(&[30, 30])).unwrap();
+        assert_eq!(reserved.total, AttoTokens::from_u64(60));
+
+        // over the price ceiling for a single chunk
+        let estimate = budget.reserve(&quotes(&[41])).unwrap_err();
+        assert_eq!(estimate.max_chunk_price, AttoTokens::from_u64(41));
+
+        // over what is left of the total
+        let estimate = budget.reserve(&quotes(&[25, 25])).unwrap_err();
+        assert_eq!(estimate.spent, AttoTokens::from_u64(60));
+        assert_eq!(estimate.total, AttoTokens::from_u64(50));
+
+        // released reservations can be spent again
+        budget.release(reserved, AttoTokens::zero());
+        assert!(budget.reserve(&quotes(&[25, 25])).is_ok());
+        assert_eq!(budget.spent(), AttoTokens::from_u64(50));
+
+        // no limits, no checks
+        assert!(CostBudget::default().reserve(&quotes(&[u64::MAX])).is_ok());
+    }
+
+    #[test]
+    fn payments_draw_from_what_was_set_aside() {
+        let budget = CostBudget::new(CostLimits {
+            max_total: Some(AttoTokens::from_u64(100)),
+            max_chunk_price: None,
+        });
+
+        // the whole upload is checked before the first payment
+        let estimate = budget.set_aside(&quotes(&[40, 40, 40])).unwrap_err();
+        assert_eq!(estimate.total, AttoTokens::from_u64(120));
+        assert_eq!(budget.spent(), AttoTokens::zero());
+
+        let estimate = budget.set_aside(&quotes(&[30, 30, 30])).unwrap();
+        assert_eq!(estimate.total, AttoTokens::from_u64(90));
+        assert_eq!(budget.spent(), AttoTokens::from_u64(90));
+
+        // payments within what was set aside do not count twice
+        assert!(budget.reserve(&quotes(&[30, 30])).is_ok());
+        assert_eq!(budget.spent(), AttoTokens::from_u64(90));
+
+        // a price rise is checked against the limits for what goes beyond it
+        assert!(budget.reserve(&quotes(&[41])).is_err());
+        let reserved = budget.reserve(&quotes(&[40])).unwrap();
+        assert_eq!(budget.spent(), AttoTokens::from_u64(100));
+
+        // what is left is given back
+        budget.release(reserved, AttoTokens::zero());
+        assert_eq!(budget.spent(), AttoTokens::from_u64(90));
+        budget.release_set_aside();
+        assert_eq!(budget.spent(), AttoTokens::from_u64(60));
+        assert!(budget.set_aside(&quotes(&[20])).is_ok());
+        budget.release_set_aside();
+        assert_eq!(budget.spent(), AttoTokens::from_u64(60));
+
+        // a failed payment drawn from what was set aside goes back to it, for the retry
+        assert!(budget.set_aside(&quotes(&[20, 20])).is_ok());
+        let reserved = budget.reserve(&quotes(&[15, 15])).unwrap();
+        budget.release(reserved, AttoTokens::from_u64(10));
+        assert_eq!(budget.spent(), AttoTokens::from_u64(100));
+        assert!(budget.reserve(&quotes(&[30])).is_ok());
+        budget.release_set_aside();
+        assert_eq!(budget.spent(), AttoTokens::from_u64(100));
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! On-disk cache of downloaded chunks.
+//!
+//! Chunks are stored in a file named after their address and checked against it when read, so a
+//! corrupted or tampered file is never returned. Files are written atomically, which lets several
+//! processes share the same cache directory. When the cache grows over its size, the least
+//! recently used chunks are removed.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use ant_protocol::storage::Chunk;
+use bytes::Bytes;
+use xor_name::XorName;
+
+use crate::Client;
+
+/// Configuration of the chunk cache, see [`ClientConfig`](crate::ClientConfig).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkCacheConfig {
+    /// Directory holding the cached chunks. It can be shared by several clients.
+    pub dir: PathBuf,
+    /// Size in bytes the cache is kept under.
+    pub max_size: u64,
+}
+
+/// Cache of chunks in a directory, one file per chunk.
+#[derive(Debug)]
+pub(crate) struct ChunkCache {
+    config: ChunkCacheConfig,
+    /// Size of the cached chunks, as last counted plus what this process added since.
+    size: AtomicU64,
+}
+
+impl ChunkCache {
+    /// Open the cache in the configured directory, creating it if needed.
+    pub(crate) fn open(config: ChunkCacheConfig) -> Result<Self, std::io::Error> {
+        std::fs::create_dir_all(&config.dir)?;
+        let cache = Self {
+            config,
+            size: AtomicU64::new(0),
+        };
+        let size = cache.entries()?.iter().map(|(_, len, _)| len).sum();
+        cache.size.store(size, Ordering::Relaxed);
+        debug!(
+            "Opened chunk cache at {:?} holding {size} bytes",
+            cache.config.dir
+        );
+        Ok(cache)
+    }
+
+    /// The cached chunk at `addr`, if there is one with the right content.
+    pub(crate) async fn get(self: &Arc<Self>, addr: XorName) -> Option<Chunk> {
+        let cache = Arc::clone(self);
+        tokio::task::spawn_blocking(move || cache.get_blocking(&addr))
+            .await
+            .inspect_err(|err| warn!("Failed to read cached chunk {addr:?}: {err:?}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Add a chunk to the cache, evicting old chunks if the cache grows over its size.
+    pub(crate) async fn put(self: &Arc<Self>, chunk: Chunk) {
+        let cache = Arc::clone(self);
+        let addr = *chunk.name();
+        if let Err(err) = tokio::task::spawn_blocking(move || cache.put_blocking(&chunk)).await {
+            warn!("Failed to cache chunk {addr:?}: {err:?}");
+        }
+    }
+
+    fn get_blocking(&self, addr: &XorName) -> Option<Chunk> {
+        let path = self.path_of(addr);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return None,
+            Err(err) => {
+                warn!("Failed to read cached chunk {addr:?}: {err:?}");
+                return None;
+            }
+        };
+
+        if XorName::from_content(&bytes) != *addr {
+            warn!("Removing cached chunk {addr:?} that does not match its address");
+            self.remove(&path, bytes.len() as u64);
+            return None;
+        }
+
+        // Mark the chunk as recently used, so it is evicted last
+        if let Err(err) = std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            debug!("Failed to update the access time of cached chunk {addr:?}: {err:?}");
+        }
+
+        Some(Chunk::new(Bytes::from(bytes)))
+    }
+
+    fn put_blocking(&self, chunk: &Chunk) {
+        let path = self.path_of(chunk.name());
+        if path.exists() {
+            return;
+        }
+
+        if let Err(err) = write_atomically(&path, chunk.value()) {
+            warn!("Failed to cache chunk {:?}: {err:?}", chunk.name());
+            return;
+        }
+
+        let len = chunk.value().len() as u64;
+        let size = self.size.fetch_add(len, Ordering::Relaxed) + len;
+        if size > self.config.max_size {
+            if let Err(err) = self.evict() {
+                warn!("Failed to evict chunks from the cache: {err:?}");
+            }
+        }
+    }
+
+    /// Remove the least recently used chunks until the cache is well under its size, so that
+    /// eviction does not run for every chunk added.
+    fn evict(&self) -> Result<(), std::io::Error> {
+        let mut entries = self.entries()?;
+        entries.sort();
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        let target = self.config.max_size / 10 * 9;
+
+        for (_, len, path) in entries {
+            if size <= target {
+                break;
+            }
+            match std::fs::remove_file(&path) {
+                Ok(()) => size -= len,
+                // Evicted by another process sharing the cache
+                Err(err) if err.kind() == ErrorKind::NotFound => size -= len,
+                Err(err) => warn!("Failed to evict cached chunk {path:?}: {err:?}"),
+            }
+        }
+
+        debug!("Evicted chunks from the cache down to {size} bytes");
+        self.size.store(size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path, len: u64) {
+        if let Err(err) = std::fs::remove_file(path) {
+            warn!("Failed to remove cached chunk {path:?}: {err:?}");
+            return;
+        }
+        let _ = self
+            .size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |size| {
+                Some(size.saturating_sub(len))
+            });
+    }
+
+    /// Chunks are spread over subdirectories by the first byte of their address.
+    fn path_of(&self, addr: &XorName) -> PathBuf {
+        let name = hex::encode(addr);
+        self.config.dir.join(&name[..2]).join(name)
+    }
+
+    /// Last use, size and path of all chunks in the cache. Files other processes remove while
+    /// the cache is scanned are skipped, as are the temporary files of chunks being written.
+    fn entries(&self) -> Result<Vec<(SystemTime, u64, PathBuf)>, std::io::Error> {
+        let mut entries = vec![];
+        for dir in std::fs::read_dir(&self.config.dir)? {
+            let Some(dir) = skip_not_found(dir)? else {
+                continue;
+            };
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let Some(files) = skip_not_found(std::fs::read_dir(dir.path()))? else {
+                continue;
+            };
+            for file in files {
+                let Some(file) = skip_not_found(file)? else {
+                    continue;
+                };
+                let path = file.path();
+                if path.extension().is_some_and(|extension| extension == "tmp") {
+                    continue;
+                }
+                let Some(metadata) = skip_not_found(file.metadata())? else {
+                    continue;
+                };
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((modified, metadata.len(), path));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// `None` if what was looked up does not exist (anymore).
+fn skip_not_found<T>(result: Result<T, std::io::Error>) -> Result<Option<T>, std::io::Error> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Write to a temporary file first, so other processes never read a partly written chunk.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), std::io::Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension(format!("{:x}.tmp", rand::random::<u64>()));
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp_path);
+    })
+}
+
+impl Client {
+    /// Keep downloaded chunks in a cache in `dir`, so that fetching them again does not go to
+    /// the network. The cache is kept under `max_size` bytes and can be shared with other
+    /// clients and processes.
+    pub fn enable_chunk_cache(
+        &mut self,
+        dir: PathBuf,
+        max_size: u64,
+    ) -> Result<(), std::io::Error> {
+        let cache = ChunkCache::open(ChunkCacheConfig { dir, max_size })?;
+        self.chunk_cache = Some(Arc::new(cache));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(len: usize) -> Chunk {
+        Chunk::new(Bytes::from(
+            (0..len).map(|_| rand::random::<u8>()).collect::<Vec<_>>(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn cache_returns_valid_chunks_and_stays_under_its_size() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = Arc::new(ChunkCache::open(ChunkCacheConfig {
+            dir: dir.path().to_path_buf(),
+            max_size: 1000,
+        })?);
+
+        let first = chunk(400);
+        cache.put(first.clone()).await;
+        assert_eq!(cache.get(*first.name()).await, Some(first.clone()));
+        assert_eq!(
+            cache.get(XorName::random(&mut rand::thread_rng())).await,
+            None
+        );
+
+        // a corrupted file is not returned, and removed
+        let corrupted = chunk(100);
+        cache.put(corrupted.clone()).await;
+        std::fs::write(cache.path_of(corrupted.name()), b"corrupted")?;
+        assert_eq!(cache.get(*corrupted.name()).await, None);
+        assert!(!cache.path_of(corrupted.name()).exists());
+
+        // going over the size evicts the least recently used chunk
+        let second = chunk(400);
+        cache.put(second.clone()).await;
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert!(cache.get(*first.name()).await.is_some());
+        cache.put(chunk(400)).await;
+        assert!(cache.get(*first.name()).await.is_some());
+        assert!(cache.get(*second.name()).await.is_none());
+
+        // the cache is picked up again when reopened
+        let reopened = Arc::new(ChunkCache::open(cache.config.clone())?);
+        assert_eq!(reopened.size.load(Ordering::Relaxed), 800);
+        assert_eq!(reopened.get(*first.name()).await, Some(first));
+
+        // temporary files of chunks being written are not counted
+        std::fs::create_dir_all(dir.path().join("00"))?;
+        std::fs::write(dir.path().join("00").join("chunk.1234.tmp"), [0; 100])?;
+        assert_eq!(reopened.entries()?.len(), 2);
+        Ok(())
+    }
+}
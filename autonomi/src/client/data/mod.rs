@@ -6,24 +6,30 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use std::collections::{HashMap, HashSet};
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::LazyLock;
 
 use ant_evm::{Amount, EvmWalletError};
-use ant_networking::NetworkError;
-use ant_protocol::storage::Chunk;
+use ant_networking::{GetRecordError, NetworkError};
+use ant_protocol::storage::{Chunk, DataTypes};
 use ant_protocol::NetworkAddress;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use xor_name::XorName;
 
-use crate::client::payment::PaymentOption;
+use crate::client::budget::CostEstimate;
+use crate::client::journal::{source_hash_of_bytes, UploadJournal};
+use crate::client::payment::{PartialReceipt, PaymentOption, Receipt};
 use crate::client::{ClientEvent, UploadSummary};
 use crate::{self_encryption::encrypt, Client};
 
 pub mod public;
+pub mod stream;
+pub mod verify;
 
-/// Number of chunks to upload in parallel.
+/// Default number of chunks to upload in parallel, see
+/// [`ClientPolicy::chunk_upload_concurrency`](crate::client::policy::ClientPolicy::chunk_upload_concurrency).
 ///
 /// Can be overridden by the `CHUNK_UPLOAD_BATCH_SIZE` environment variable.
 pub(crate) static CHUNK_UPLOAD_BATCH_SIZE: LazyLock<usize> = LazyLock::new(|| {
@@ -40,7 +46,8 @@ pub(crate) static CHUNK_UPLOAD_BATCH_SIZE: LazyLock<usize> = LazyLock::new(|| {
     batch_size
 });
 
-/// Number of chunks to download in parallel.
+/// Default number of chunks to download in parallel, see
+/// [`ClientPolicy::chunk_download_concurrency`](crate::client::policy::ClientPolicy::chunk_download_concurrency).
 ///
 /// Can be overridden by the `CHUNK_DOWNLOAD_BATCH_SIZE` environment variable.
 pub static CHUNK_DOWNLOAD_BATCH_SIZE: LazyLock<usize> = LazyLock::new(|| {
@@ -57,7 +64,7 @@ pub static CHUNK_DOWNLOAD_BATCH_SIZE: LazyLock<usize> = LazyLock::new(|| {
     batch_size
 });
 
-/// Number of retries to upload chunks.
+/// Default number of retries to upload chunks.
 pub(crate) const RETRY_ATTEMPTS: usize = 3;
 
 /// Raw Data Address (points to a DataMap)
@@ -86,6 +93,8 @@ pub enum PutError {
     PaymentUnexpectedlyInvalid(NetworkAddress),
     #[error("The payment proof contains no payees.")]
     PayeesMissing,
+    #[error("IO failure")]
+    Io(#[from] std::io::Error),
 }
 
 /// Errors that can occur during the pay operation.
@@ -97,6 +106,38 @@ pub enum PayError {
     SelfEncryption(#[from] crate::self_encryption::Error),
     #[error("Cost error: {0:?}")]
     Cost(#[from] CostError),
+    #[error("Payment exceeds the cost limits: {0}")]
+    CostLimitExceeded(Box<CostEstimate>),
+    #[cfg(feature = "external-signer")]
+    #[error("Failed to form payment transactions: {0:?}")]
+    Transactions(#[from] ant_evm::external_signer::Error),
+    /// Some of the payment transactions went through before one failed. The receipt holds the
+    /// content addresses that were fully paid for, so they can still be uploaded, and the partial
+    /// receipt the ones of which only some quotes were paid, so a retry only pays the rest.
+    #[error("Wallet error after paying for {} content addresses and part of {} others: {error:?}", receipt.len(), partial.len())]
+    PartialPayment {
+        error: EvmWalletError,
+        receipt: Receipt,
+        partial: PartialReceipt,
+    },
+}
+
+impl PayError {
+    /// The payments that were made before the error occurred, if any.
+    pub fn paid_receipt(&self) -> Option<&Receipt> {
+        match self {
+            PayError::PartialPayment { receipt, .. } => Some(receipt),
+            _ => None,
+        }
+    }
+
+    /// The content addresses of which only some quotes were paid before the error occurred.
+    pub fn partial_receipt(&self) -> Option<&PartialReceipt> {
+        match self {
+            PayError::PartialPayment { partial, .. } => Some(partial),
+            _ => None,
+        }
+    }
 }
 
 /// Errors that can occur during the get operation.
@@ -114,6 +155,18 @@ pub enum GetError {
     Protocol(#[from] ant_protocol::Error),
 }
 
+impl GetError {
+    /// Whether there is no data at the address: no record at all, or a record of another type,
+    /// such as the pointer of a named archive.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            GetError::Network(NetworkError::GetRecordError(GetRecordError::RecordNotFound))
+                | GetError::Network(NetworkError::RecordKindMismatch(_))
+        )
+    }
+}
+
 /// Errors that can occur during the cost calculation.
 #[derive(Debug, thiserror::Error)]
 pub enum CostError {
@@ -129,11 +182,13 @@ pub enum CostError {
     Serialization(String),
     #[error("Market price error: {0:?}")]
     MarketPriceError(#[from] ant_evm::payment_vault::error::Error),
+    #[error("No market price was returned for some of the quoting metrics")]
+    MissingMarketPrice,
 }
 
 /// Private data on the network can be accessed with this
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct DataMapChunk(Chunk);
+pub struct DataMapChunk(pub(crate) Chunk);
 
 impl DataMapChunk {
     pub fn to_hex(&self) -> String {
@@ -216,24 +271,191 @@ impl Client {
         payment_option: PaymentOption,
     ) -> Result<DataMapChunk, PutError> {
         let now = ant_networking::time::Instant::now();
+        let mut journal = self.load_upload_journal(|| Ok(source_hash_of_bytes(&data)));
         let (data_map_chunk, chunks) = encrypt(data)?;
         debug!("Encryption took: {:.2?}", now.elapsed());
 
-        // Pay for all chunks
+        // Pay for and upload all chunks
+        let (records_paid, records_already_paid, tokens_spent) = self
+            .pay_and_upload_chunks(&chunks, payment_option, journal.as_mut())
+            .await?;
+        remove_upload_journal(journal);
+
+        // Reporting
+        self.send_upload_summary(UploadSummary {
+            records_paid,
+            records_already_paid,
+            tokens_spent,
+        });
+
+        Ok(DataMapChunk(data_map_chunk))
+    }
+
+    /// Pay for chunks and upload them with retries.
+    ///
+    /// If a `journal` is given, chunks it recorded as stored are skipped, its still valid
+    /// payments are reused and the progress of this upload is recorded in it.
+    ///
+    /// Returns the number of records paid, the number of records already paid for and the tokens spent.
+    pub(crate) async fn pay_and_upload_chunks(
+        &self,
+        chunks: &[Chunk],
+        payment_option: PaymentOption,
+        mut journal: Option<&mut UploadJournal>,
+    ) -> Result<(usize, usize, Amount), PutError> {
         let xor_names: Vec<_> = chunks.iter().map(|chunk| *chunk.name()).collect();
-        info!("Paying for {} addresses", xor_names.len());
-        let (receipt, skipped_payments) = self
-            .pay_for_content_addrs(xor_names.into_iter(), payment_option)
-            .await
-            .inspect_err(|err| error!("Error paying for data: {err:?}"))?;
 
-        // Upload the chunks with the payments
-        debug!("Uploading {} chunks", chunks.len());
+        let (mut to_pay, mut receipt, partial) = match journal.as_deref_mut() {
+            Some(journal) => journal.pending(&xor_names),
+            None => (xor_names, Receipt::new(), PartialReceipt::default()),
+        };
+        let reused_payments = receipt.len();
+        let already_stored = chunks.len() - to_pay.len() - reused_payments - partial.len();
+        if reused_payments > 0 || already_stored > 0 || !partial.is_empty() {
+            info!(
+                "Resuming upload: {already_stored} chunks already stored, reusing {reused_payments} payments, completing {} partial payments",
+                partial.len()
+            );
+        }
+
+        let mut new_payments = Receipt::new();
+        let mut skipped_payments = 0;
+        let mut pay_error = None;
+
+        // Only pay the quotes an earlier attempt did not pay for chunks it partly paid for
+        if !partial.is_empty() {
+            match &payment_option {
+                PaymentOption::Wallet(wallet) => {
+                    match self.pay_remaining_quotes(partial, wallet).await {
+                        Ok(payments) => new_payments = payments,
+                        Err(PayError::PartialPayment {
+                            error,
+                            receipt,
+                            partial,
+                        }) => {
+                            error!("Error completing partial payments: {error:?}");
+                            new_payments = receipt.clone();
+                            pay_error = Some(PayError::PartialPayment {
+                                error,
+                                receipt,
+                                partial,
+                            });
+                        }
+                        Err(err) => {
+                            error!("Error completing partial payments: {err:?}");
+                            return Err(err.into());
+                        }
+                    }
+                }
+                PaymentOption::Receipt(_) => to_pay.extend(partial.content_addrs()),
+            }
+        }
 
+        // Pay for all chunks without a valid payment
+        if !to_pay.is_empty() && pay_error.is_none() {
+            info!("Paying for {} addresses", to_pay.len());
+            let chunk_sizes: HashMap<_, _> = chunks
+                .iter()
+                .map(|chunk| (*chunk.name(), chunk.payload_size()))
+                .collect();
+            let content_addrs = to_pay
+                .iter()
+                .filter_map(|addr| Some((*addr, *chunk_sizes.get(addr)?)));
+            let payments = match self
+                .pay_for_content_addrs(DataTypes::Chunk, content_addrs, payment_option)
+                .await
+            {
+                Ok((payments, skipped)) => {
+                    skipped_payments = skipped;
+                    payments
+                }
+                // Still upload what was paid for, so that retrying only pays for the rest.
+                Err(PayError::PartialPayment {
+                    error,
+                    receipt,
+                    partial,
+                }) => {
+                    error!(
+                        "Error paying for data after paying for {} and part of {} of {} addresses: {error:?}",
+                        receipt.len(),
+                        partial.len(),
+                        to_pay.len()
+                    );
+                    pay_error = Some(PayError::PartialPayment {
+                        error,
+                        receipt: receipt.clone(),
+                        partial,
+                    });
+                    receipt
+                }
+                Err(err) => {
+                    error!("Error paying for data: {err:?}");
+                    return Err(err.into());
+                }
+            };
+            let to_pay: HashSet<_> = to_pay.iter().collect();
+            new_payments.extend(
+                payments
+                    .into_iter()
+                    .filter(|(addr, _)| to_pay.contains(addr)),
+            );
+        }
+
+        if let Some(journal) = journal.as_deref_mut() {
+            journal.record_payments(&new_payments);
+            if let Some(partial) = pay_error.as_ref().and_then(PayError::partial_receipt) {
+                journal.record_partial_payments(partial);
+            }
+            if let Err(err) = journal.save() {
+                error!("Failed to save upload journal: {err:?}");
+            }
+        }
+        let tokens_spent = new_payments
+            .values()
+            .map(|(_, cost)| cost.as_atto())
+            .sum::<Amount>();
+        receipt.extend(new_payments);
+
+        // Upload the chunks with the payments
+        let to_upload: Vec<&Chunk> = chunks
+            .iter()
+            .filter(|chunk| match journal.as_deref() {
+                Some(journal) => !journal.stored().contains(chunk.name()),
+                None => true,
+            })
+            // after a failed payment, chunks without a payment are not on the network yet
+            .filter(|chunk| pay_error.is_none() || receipt.contains_key(chunk.name()))
+            .collect();
+        debug!("Uploading {} chunks", to_upload.len());
         let mut failed_uploads = self
-            .upload_chunks_with_retries(chunks.iter().collect(), &receipt)
+            .upload_chunks_with_retries(to_upload.clone(), &receipt)
             .await;
 
+        if let Some(journal) = journal {
+            let failed: HashSet<_> = failed_uploads
+                .iter()
+                .map(|(chunk, _)| *chunk.name())
+                .collect();
+            journal.record_stored(
+                to_upload
+                    .iter()
+                    .map(|chunk| *chunk.name())
+                    .filter(|name| !failed.contains(name)),
+            );
+            if let Err(err) = journal.save() {
+                error!("Failed to save upload journal: {err:?}");
+            }
+        }
+
+        if let Some(pay_error) = pay_error {
+            info!(
+                "Uploaded the {} paid for chunks, {} chunks remain unpaid",
+                to_upload.len() - failed_uploads.len(),
+                chunks.len() - receipt.len() - already_stored
+            );
+            return Err(pay_error.into());
+        }
+
         // Return the last chunk upload error
         if let Some(last_chunk_fail) = failed_uploads.pop() {
             tracing::error!(
@@ -241,29 +463,29 @@ impl Client {
                 last_chunk_fail.0.address(),
                 last_chunk_fail.1
             );
+            self.send_event(ClientEvent::Error(format!(
+                "Failed to upload {} chunks: {}",
+                failed_uploads.len() + 1,
+                last_chunk_fail.1
+            )));
             return Err(last_chunk_fail.1);
         }
 
-        let record_count = chunks.len().saturating_sub(skipped_payments);
+        let records_already_paid = skipped_payments + already_stored;
+        Ok((
+            chunks.len().saturating_sub(records_already_paid),
+            records_already_paid,
+            tokens_spent,
+        ))
+    }
+}
 
-        // Reporting
-        if let Some(channel) = self.client_event_sender.as_ref() {
-            let tokens_spent = receipt
-                .values()
-                .map(|(_, cost)| cost.as_atto())
-                .sum::<Amount>();
-
-            let summary = UploadSummary {
-                records_paid: record_count,
-                records_already_paid: skipped_payments,
-                tokens_spent,
-            };
-            if let Err(err) = channel.send(ClientEvent::UploadComplete(summary)).await {
-                error!("Failed to send client event: {err:?}");
-            }
+/// Remove the journal of a completed upload.
+pub(crate) fn remove_upload_journal(journal: Option<UploadJournal>) {
+    if let Some(journal) = journal {
+        if let Err(err) = journal.remove() {
+            error!("Failed to remove upload journal: {err:?}");
         }
-
-        Ok(DataMapChunk(data_map_chunk))
     }
 }
 
@@ -278,4 +500,17 @@ mod tests {
         let data_map2 = DataMapChunk::from_hex(&hex).expect("Failed to decode hex");
         assert_eq!(data_map, data_map2);
     }
+
+    #[test]
+    fn test_not_found() {
+        let not_found =
+            GetError::Network(NetworkError::GetRecordError(GetRecordError::RecordNotFound));
+        assert!(not_found.is_not_found());
+        let other_kind = GetError::Network(NetworkError::RecordKindMismatch(
+            ant_protocol::storage::RecordKind::DataOnly(DataTypes::Chunk),
+        ));
+        assert!(other_kind.is_not_found());
+        let failed = GetError::Network(NetworkError::GetRecordError(GetRecordError::QueryTimeout));
+        assert!(!failed.is_not_found());
+    }
 }
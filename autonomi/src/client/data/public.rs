@@ -7,15 +7,14 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use bytes::Bytes;
-use libp2p::kad::Quorum;
-use std::collections::HashSet;
 
+use crate::client::journal::source_hash_of_bytes;
 use crate::client::payment::{PaymentOption, Receipt};
 use crate::client::utils::process_tasks_with_max_concurrency;
 use crate::client::{ClientEvent, UploadSummary};
 use crate::{self_encryption::encrypt, Client};
 use ant_evm::{Amount, AttoTokens};
-use ant_networking::{GetRecordCfg, NetworkError};
+use ant_networking::NetworkError;
 use ant_protocol::{
     storage::{try_deserialize_record, Chunk, ChunkAddress, DataTypes, RecordHeader, RecordKind},
     NetworkAddress,
@@ -45,66 +44,30 @@ impl Client {
         payment_option: PaymentOption,
     ) -> Result<DataAddr, PutError> {
         let now = ant_networking::time::Instant::now();
+        let mut journal = self.load_upload_journal(|| Ok(source_hash_of_bytes(&data)));
         let (data_map_chunk, chunks) = encrypt(data)?;
         let data_map_addr = data_map_chunk.address();
         debug!("Encryption took: {:.2?}", now.elapsed());
         info!("Uploading datamap chunk to the network at: {data_map_addr:?}");
 
         let map_xor_name = *data_map_chunk.address().xorname();
-        let mut xor_names = vec![map_xor_name];
 
-        for chunk in &chunks {
-            xor_names.push(*chunk.name());
-        }
-
-        // Pay for all chunks + data map chunk
-        info!("Paying for {} addresses", xor_names.len());
-        let (receipt, skipped_payments) = self
-            .pay_for_content_addrs(xor_names.into_iter(), payment_option)
-            .await
-            .inspect_err(|err| error!("Error paying for data: {err:?}"))?;
-
-        // Upload all the chunks in parallel including the data map chunk
-        debug!("Uploading {} chunks", chunks.len());
-
-        let mut failed_uploads = self
-            .upload_chunks_with_retries(
-                chunks
-                    .iter()
-                    .chain(std::iter::once(&data_map_chunk))
-                    .collect(),
-                &receipt,
-            )
-            .await;
-
-        // Return the last chunk upload error
-        if let Some(last_chunk_fail) = failed_uploads.pop() {
-            tracing::error!(
-                "Error uploading chunk ({:?}): {:?}",
-                last_chunk_fail.0.address(),
-                last_chunk_fail.1
-            );
-            return Err(last_chunk_fail.1);
-        }
-
-        let record_count = (chunks.len() + 1) - skipped_payments;
+        // Pay for and upload all chunks + data map chunk
+        let all_chunks: Vec<Chunk> = chunks
+            .into_iter()
+            .chain(std::iter::once(data_map_chunk))
+            .collect();
+        let (records_paid, records_already_paid, tokens_spent) = self
+            .pay_and_upload_chunks(&all_chunks, payment_option, journal.as_mut())
+            .await?;
+        remove_upload_journal(journal);
 
         // Reporting
-        if let Some(channel) = self.client_event_sender.as_ref() {
-            let tokens_spent = receipt
-                .values()
-                .map(|(_proof, price)| price.as_atto())
-                .sum::<Amount>();
-
-            let summary = UploadSummary {
-                records_paid: record_count,
-                records_already_paid: skipped_payments,
-                tokens_spent,
-            };
-            if let Err(err) = channel.send(ClientEvent::UploadComplete(summary)).await {
-                error!("Failed to send client event: {err:?}");
-            }
-        }
+        self.send_upload_summary(UploadSummary {
+            records_paid,
+            records_already_paid,
+            tokens_spent,
+        });
 
         Ok(map_xor_name)
     }
@@ -113,15 +76,27 @@ impl Client {
     pub async fn chunk_get(&self, addr: ChunkAddr) -> Result<Chunk, GetError> {
         info!("Getting chunk: {addr:?}");
 
+        if let Some(cache) = self.chunk_cache.as_ref() {
+            if let Some(chunk) = cache.get(addr).await {
+                debug!("Chunk {addr:?} found in the chunk cache");
+                self.send_event(ClientEvent::ChunkFetched(addr));
+                return Ok(chunk);
+            }
+        }
+
+        let chunk = self.chunk_get_from_network(addr).await?;
+        if let Some(cache) = self.chunk_cache.as_ref() {
+            cache.put(chunk.clone()).await;
+        }
+        self.send_event(ClientEvent::ChunkFetched(addr));
+        Ok(chunk)
+    }
+
+    /// Get a raw chunk from the network, bypassing the chunk cache.
+    pub(crate) async fn chunk_get_from_network(&self, addr: ChunkAddr) -> Result<Chunk, GetError> {
         let key = NetworkAddress::from_chunk_address(ChunkAddress::new(addr)).to_record_key();
         debug!("Fetching chunk from network at: {key:?}");
-        let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::One,
-            retry_strategy: None,
-            target_record: None,
-            expected_holders: HashSet::new(),
-            is_register: false,
-        };
+        let get_cfg = self.policy.chunks.get.record_cfg();
 
         let record = self
             .network
@@ -150,10 +125,10 @@ impl Client {
         debug!("Encryption took: {:.2?}", now.elapsed());
 
         let map_xor_name = *data_map_chunk.address().xorname();
-        let mut content_addrs = vec![map_xor_name];
+        let mut content_addrs = vec![(map_xor_name, data_map_chunk.payload_size())];
 
         for chunk in &chunks {
-            content_addrs.push(*chunk.name());
+            content_addrs.push((*chunk.name(), chunk.payload_size()));
         }
 
         info!(
@@ -162,7 +137,7 @@ impl Client {
         );
 
         let store_quote = self
-            .get_store_quotes(content_addrs.into_iter())
+            .get_store_quotes(DataTypes::Chunk, content_addrs.into_iter())
             .await
             .inspect_err(|err| error!("Error getting store quotes: {err:?}"))?;
 
@@ -177,12 +152,13 @@ impl Client {
         Ok(total_cost)
     }
 
-    // Upload chunks and retry failed uploads up to `RETRY_ATTEMPTS` times.
+    // Upload chunks and retry failed uploads up to `ClientPolicy::chunk_upload_retries` times.
     pub async fn upload_chunks_with_retries<'a>(
         &self,
         mut chunks: Vec<&'a Chunk>,
         receipt: &Receipt,
     ) -> Vec<(&'a Chunk, PutError)> {
+        let max_attempts = self.policy.chunk_upload_retries;
         let mut current_attempt: usize = 1;
 
         loop {
@@ -205,8 +181,11 @@ impl Client {
                         .map_err(|err| (chunk, err))
                 });
             }
-            let uploads =
-                process_tasks_with_max_concurrency(upload_tasks, *CHUNK_UPLOAD_BATCH_SIZE).await;
+            let uploads = process_tasks_with_max_concurrency(
+                upload_tasks,
+                self.policy.chunk_upload_concurrency,
+            )
+            .await;
 
             // Check for errors.
             let total_uploads = uploads.len();
@@ -222,14 +201,19 @@ impl Client {
             }
 
             // Max retries reached.
-            if current_attempt > RETRY_ATTEMPTS {
+            if current_attempt > max_attempts {
                 return uploads_failed;
             }
 
             tracing::info!(
-                "Retrying putting {} failed chunks (attempt {current_attempt}/3)",
+                "Retrying putting {} failed chunks (attempt {current_attempt}/{max_attempts})",
                 uploads_failed.len()
             );
+            self.send_event(ClientEvent::RetryScheduled {
+                chunks: uploads_failed.len(),
+                attempt: current_attempt,
+                max_attempts,
+            });
 
             // Re-iterate over the failed chunks
             chunks = uploads_failed.into_iter().map(|(chunk, _)| chunk).collect();
@@ -0,0 +1,436 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Streaming and ranged variants of the data API, keeping memory usage bounded regardless of the
+//! data size.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::{StreamExt, TryStreamExt};
+use self_encryption::{ChunkInfo, DataMap, EncryptedChunk};
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+
+use super::{DataAddr, DataMapChunk, GetError};
+use crate::Client;
+
+#[cfg(feature = "fs")]
+use {
+    super::{remove_upload_journal, PutError},
+    crate::client::journal::source_hash_of_file,
+    crate::client::payment::PaymentOption,
+    crate::client::UploadSummary,
+    crate::self_encryption::{EncryptedBatch, FileEncryptor},
+    ant_evm::Amount,
+    ant_protocol::storage::Chunk,
+    std::path::PathBuf,
+};
+
+impl Client {
+    /// Fetch a blob of (private) data from the network as a byte stream.
+    ///
+    /// Chunks are fetched in order, with at most [`chunk_download_concurrency`] chunks in flight,
+    /// and decrypted one by one, so the full data never has to fit in memory.
+    ///
+    /// [`chunk_download_concurrency`]: crate::client::policy::ClientPolicy::chunk_download_concurrency
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use autonomi::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = Client::init().await?;
+    /// # let data_map = todo!();
+    /// let mut reader = client.data_get_stream(data_map).await?;
+    /// let mut file = tokio::fs::File::create("video.mp4").await?;
+    /// tokio::io::copy(&mut reader, &mut file).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn data_get_stream(
+        &self,
+        data_map: DataMapChunk,
+    ) -> Result<impl AsyncRead + Unpin + Send + 'static, GetError> {
+        info!(
+            "Streaming private data from Data Map {:?}",
+            data_map.0.address()
+        );
+        let data_map = self.resolve_data_map(data_map.0.value()).await?;
+        Ok(self.stream_from_data_map(data_map))
+    }
+
+    /// Fetch a blob of public data from the network as a byte stream.
+    ///
+    /// See [`Client::data_get_stream`].
+    pub async fn data_get_stream_public(
+        &self,
+        addr: DataAddr,
+    ) -> Result<impl AsyncRead + Unpin + Send + 'static, GetError> {
+        info!("Streaming data from Data Address: {addr:?}");
+        let data_map_chunk = self.chunk_get(addr).await?;
+        let data_map = self.resolve_data_map(data_map_chunk.value()).await?;
+        Ok(self.stream_from_data_map(data_map))
+    }
+
+    /// Fetch the bytes in `range` of a blob of (private) data from the network.
+    ///
+    /// Only the chunks covering the range are fetched and decrypted, which makes seeking in large
+    /// files cheap. The range is clamped to the size of the data, so reading past the end returns
+    /// fewer bytes, or none at all.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use autonomi::Client;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = Client::init().await?;
+    /// # let data_map = todo!();
+    /// // the second mebibyte of the data
+    /// let bytes = client.data_get_range(data_map, 1024 * 1024..2 * 1024 * 1024).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn data_get_range(
+        &self,
+        data_map: DataMapChunk,
+        range: Range<u64>,
+    ) -> Result<Bytes, GetError> {
+        info!(
+            "Fetching range {range:?} of private data from Data Map {:?}",
+            data_map.0.address()
+        );
+        let data_map = self.resolve_data_map(data_map.0.value()).await?;
+        self.fetch_range_from_data_map(&data_map, range).await
+    }
+
+    /// Fetch the bytes in `range` of a blob of public data from the network.
+    ///
+    /// See [`Client::data_get_range`].
+    pub async fn data_get_range_public(
+        &self,
+        addr: DataAddr,
+        range: Range<u64>,
+    ) -> Result<Bytes, GetError> {
+        info!("Fetching range {range:?} of data from Data Address: {addr:?}");
+        let data_map_chunk = self.chunk_get(addr).await?;
+        let data_map = self.resolve_data_map(data_map_chunk.value()).await?;
+        self.fetch_range_from_data_map(&data_map, range).await
+    }
+
+    /// Get the size in bytes of a blob of (private) data, without fetching its content.
+    pub async fn data_size(&self, data_map: DataMapChunk) -> Result<u64, GetError> {
+        let data_map = self.resolve_data_map(data_map.0.value()).await?;
+        Ok(data_map.file_size() as u64)
+    }
+
+    /// Get the size in bytes of a blob of public data, without fetching its content.
+    pub async fn data_size_public(&self, addr: DataAddr) -> Result<u64, GetError> {
+        let data_map_chunk = self.chunk_get(addr).await?;
+        let data_map = self.resolve_data_map(data_map_chunk.value()).await?;
+        Ok(data_map.file_size() as u64)
+    }
+
+    /// Upload private data read from `reader` to the network. This data will be self-encrypted.
+    ///
+    /// Self-encryption needs to know the total size of the data upfront, so the reader is
+    /// first spooled to a temporary file. Chunks are then encrypted, paid for and uploaded
+    /// in batches of [`chunk_upload_concurrency`].
+    ///
+    /// Returns the [`DataMapChunk`] containing the map to the encrypted chunks.
+    ///
+    /// [`chunk_upload_concurrency`]: crate::client::policy::ClientPolicy::chunk_upload_concurrency
+    #[cfg(feature = "fs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+    pub async fn data_put_stream(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        payment_option: PaymentOption,
+    ) -> Result<DataMapChunk, PutError> {
+        let spool = spool_to_temp_file(reader).await?;
+        let data_map_chunk = self
+            .file_put_streaming(spool.path().to_path_buf(), payment_option, false)
+            .await?;
+        Ok(DataMapChunk(data_map_chunk))
+    }
+
+    /// Upload public data read from `reader` to the network.
+    ///
+    /// See [`Client::data_put_stream`]. Returns the Data Address at which the data was stored.
+    #[cfg(feature = "fs")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+    pub async fn data_put_stream_public(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        payment_option: PaymentOption,
+    ) -> Result<DataAddr, PutError> {
+        let spool = spool_to_temp_file(reader).await?;
+        let data_map_chunk = self
+            .file_put_streaming(spool.path().to_path_buf(), payment_option, true)
+            .await?;
+        Ok(*data_map_chunk.name())
+    }
+
+    /// Self-encrypt the file at `path` and pay for and upload its chunks batch by batch.
+    /// When `public` is set the data map chunk is uploaded as well.
+    ///
+    /// Returns the data map chunk.
+    #[cfg(feature = "fs")]
+    pub(crate) async fn file_put_streaming(
+        &self,
+        path: PathBuf,
+        payment_option: PaymentOption,
+        public: bool,
+    ) -> Result<Chunk, PutError> {
+        let now = ant_networking::time::Instant::now();
+        let mut journal = self.load_upload_journal(|| source_hash_of_file(&path));
+        let mut encryptor = FileEncryptor::new(path.clone())?;
+
+        let mut records_paid = 0;
+        let mut records_already_paid = 0;
+        let mut tokens_spent = Amount::ZERO;
+
+        let data_map_chunk = loop {
+            let EncryptedBatch {
+                mut chunks,
+                data_map,
+            } = encryptor.next_batch(self.policy.chunk_upload_concurrency)?;
+
+            let data_map_chunk = match data_map {
+                Some((data_map_chunk, additional_chunks)) => {
+                    chunks.extend(additional_chunks);
+                    if public {
+                        chunks.push(data_map_chunk.clone());
+                    }
+                    Some(data_map_chunk)
+                }
+                None => None,
+            };
+
+            if !chunks.is_empty() {
+                let (paid, already_paid, spent) = self
+                    .pay_and_upload_chunks(&chunks, payment_option.clone(), journal.as_mut())
+                    .await?;
+                records_paid += paid;
+                records_already_paid += already_paid;
+                tokens_spent += spent;
+            }
+
+            if let Some(data_map_chunk) = data_map_chunk {
+                break data_map_chunk;
+            }
+        };
+        remove_upload_journal(journal);
+        debug!(
+            "Streamed upload of {path:?} with {} chunks took: {:.2?}",
+            records_paid + records_already_paid,
+            now.elapsed()
+        );
+
+        // Reporting
+        self.send_upload_summary(UploadSummary {
+            records_paid,
+            records_already_paid,
+            tokens_spent,
+        });
+
+        Ok(data_map_chunk)
+    }
+
+    /// Fetch and decrypt the chunks of a data map covering `range`, and cut the range out of them.
+    pub(crate) async fn fetch_range_from_data_map(
+        &self,
+        data_map: &DataMap,
+        range: Range<u64>,
+    ) -> Result<Bytes, GetError> {
+        let (infos, first_offset) = chunks_covering(data_map, &range);
+        if infos.is_empty() {
+            return Ok(Bytes::new());
+        }
+        debug!(
+            "Fetching {} of {} chunks for range {range:?}",
+            infos.len(),
+            data_map.infos().len()
+        );
+
+        let decrypted: Vec<Bytes> = futures::stream::iter(infos)
+            .map(|info| async move {
+                let chunk = self.chunk_get(info.dst_hash).await.inspect_err(|err| {
+                    error!("Error fetching chunk {:?}: {err:?}", info.dst_hash)
+                })?;
+                let encrypted_chunk = EncryptedChunk {
+                    index: info.index,
+                    content: chunk.value,
+                };
+                decrypt_chunk(data_map, encrypted_chunk).map_err(GetError::Decryption)
+            })
+            .buffered(self.policy.chunk_download_concurrency)
+            .try_collect()
+            .await?;
+
+        Ok(cut_range(decrypted, first_offset, &range))
+    }
+
+    /// Fetch the chunks of a data map in order and decrypt them one at a time.
+    fn stream_from_data_map(&self, data_map: DataMap) -> impl AsyncRead + Unpin + Send + 'static {
+        let client = self.clone();
+        let concurrency = self.policy.chunk_download_concurrency;
+        let data_map = Arc::new(data_map);
+        let infos = data_map.infos();
+
+        let decrypted =
+            futures::stream::iter(infos)
+                .map(move |info| {
+                    let client = client.clone();
+                    async move {
+                        let chunk = client.chunk_get(info.dst_hash).await.inspect_err(|err| {
+                            error!("Error fetching chunk {:?}: {err:?}", info.dst_hash)
+                        })?;
+                        Ok::<_, GetError>(EncryptedChunk {
+                            index: info.index,
+                            content: chunk.value,
+                        })
+                    }
+                })
+                .buffered(concurrency)
+                .and_then(move |encrypted_chunk| {
+                    let data_map = Arc::clone(&data_map);
+                    async move {
+                        decrypt_chunk(&data_map, encrypted_chunk).map_err(GetError::Decryption)
+                    }
+                })
+                .map_err(std::io::Error::other);
+
+        StreamReader::new(Box::pin(decrypted))
+    }
+}
+
+/// Decrypt a single chunk of the given data map.
+fn decrypt_chunk(
+    data_map: &DataMap,
+    encrypted_chunk: EncryptedChunk,
+) -> Result<Bytes, crate::self_encryption::Error> {
+    let index = encrypted_chunk.index;
+    let bytes = self_encryption::decrypt_range(data_map, &[encrypted_chunk], 0, usize::MAX)
+        .inspect_err(|err| error!("Error decrypting chunk {index}: {err:?}"))?;
+    Ok(bytes)
+}
+
+/// The chunks of a data map holding bytes of `range`, in order, and the offset in the data of the
+/// first one.
+fn chunks_covering(data_map: &DataMap, range: &Range<u64>) -> (Vec<ChunkInfo>, u64) {
+    let mut covering = vec![];
+    let mut first_offset = None;
+    let mut offset = 0;
+    for info in data_map.infos() {
+        let end = offset + info.src_size as u64;
+        if offset < range.end && range.start < end {
+            let _ = first_offset.get_or_insert(offset);
+            covering.push(info);
+        }
+        offset = end;
+    }
+    (covering, first_offset.unwrap_or(range.start))
+}
+
+/// Cut `range` out of consecutive decrypted chunks, the first of which starts at `first_offset`.
+fn cut_range(decrypted: Vec<Bytes>, first_offset: u64, range: &Range<u64>) -> Bytes {
+    let mut bytes = BytesMut::with_capacity(decrypted.iter().map(Bytes::len).sum());
+    for chunk in decrypted {
+        bytes.extend_from_slice(&chunk);
+    }
+    let start = (range.start.saturating_sub(first_offset) as usize).min(bytes.len());
+    let end = (range.end.saturating_sub(first_offset) as usize).min(bytes.len());
+    bytes.freeze().slice(start..end.max(start))
+}
+
+/// Write all of `reader` into a temporary file, removed once the returned handle is dropped.
+#[cfg(feature = "fs")]
+async fn spool_to_temp_file(
+    mut reader: impl AsyncRead + Unpin,
+) -> Result<tempfile::NamedTempFile, std::io::Error> {
+    use tokio::io::AsyncWriteExt;
+
+    let spool = tempfile::NamedTempFile::new()?;
+    let mut file = tokio::fs::File::create(spool.path()).await?;
+    let written = tokio::io::copy(&mut reader, &mut file).await?;
+    file.flush().await?;
+    debug!("Spooled {written} bytes to {:?}", spool.path());
+    Ok(spool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    #[test]
+    fn chunk_by_chunk_decryption_restores_data() -> eyre::Result<()> {
+        let mut data = vec![0u8; 3 * *self_encryption::MAX_CHUNK_SIZE + 42];
+        rand::thread_rng().fill_bytes(&mut data);
+        let data = Bytes::from(data);
+
+        let (data_map, mut encrypted_chunks) = self_encryption::encrypt(data.clone())?;
+        encrypted_chunks.sort_by_key(|chunk| chunk.index);
+
+        let mut decrypted = Vec::with_capacity(data.len());
+        for encrypted_chunk in encrypted_chunks {
+            decrypted.extend_from_slice(&decrypt_chunk(&data_map, encrypted_chunk)?);
+        }
+
+        assert_eq!(Bytes::from(decrypted), data);
+        Ok(())
+    }
+
+    #[test]
+    fn range_only_needs_covering_chunks() -> eyre::Result<()> {
+        let chunk_size = *self_encryption::MAX_CHUNK_SIZE as u64;
+        let mut data = vec![0u8; 5 * chunk_size as usize + 42];
+        rand::thread_rng().fill_bytes(&mut data);
+        let data = Bytes::from(data);
+        let (data_map, encrypted_chunks) = self_encryption::encrypt(data.clone())?;
+
+        let read_range = |range: Range<u64>| -> eyre::Result<(usize, Bytes)> {
+            let (infos, first_offset) = chunks_covering(&data_map, &range);
+            let mut decrypted = vec![];
+            for info in &infos {
+                let encrypted_chunk = encrypted_chunks
+                    .iter()
+                    .find(|chunk| chunk.index == info.index)
+                    .ok_or(eyre::eyre!("missing chunk {}", info.index))?;
+                decrypted.push(decrypt_chunk(&data_map, encrypted_chunk.clone())?);
+            }
+            Ok((infos.len(), cut_range(decrypted, first_offset, &range)))
+        };
+
+        let len = data.len() as u64;
+        let (fetched, bytes) = read_range(chunk_size - 10..chunk_size + 10)?;
+        assert_eq!(fetched, 2);
+        assert_eq!(
+            bytes,
+            data.slice(chunk_size as usize - 10..chunk_size as usize + 10)
+        );
+
+        let (fetched, bytes) = read_range(2 * chunk_size + 1..2 * chunk_size + 2)?;
+        assert_eq!(fetched, 1);
+        assert_eq!(
+            bytes,
+            data.slice(2 * chunk_size as usize + 1..2 * chunk_size as usize + 2)
+        );
+
+        let (_, bytes) = read_range(len - 5..len + 100)?;
+        assert_eq!(bytes, data.slice(len as usize - 5..));
+
+        let (fetched, bytes) = read_range(len + 1..len + 100)?;
+        assert_eq!((fetched, bytes.len()), (0, 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Checking that every chunk of a piece of data is still stored on the network.
+//!
+//! Unlike downloading, verifying does not stop at the first missing chunk and never uses the
+//! chunk cache, so it reports exactly which chunks the network lost.
+
+use ant_protocol::{
+    messages::ChunkProof,
+    storage::{try_serialize_record, Chunk, ChunkAddress, DataTypes, RecordKind},
+    NetworkAddress,
+};
+use bytes::Bytes;
+use libp2p::kad::Quorum;
+use rand::{thread_rng, Rng};
+use self_encryption::{decrypt_full_set, DataMap, EncryptedChunk};
+use xor_name::XorName;
+
+use super::{DataAddr, DataMapChunk, GetError};
+use crate::client::utils::process_tasks_with_max_concurrency;
+use crate::self_encryption::DataMapLevel;
+use crate::Client;
+
+/// How thoroughly [`Client::data_verify`] checks each chunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerifyMode {
+    /// Check that the chunk can be fetched from the network.
+    #[default]
+    Fetch,
+    /// Also ask the nodes holding the chunk for a `GetChunkExistenceProof` with a fresh nonce,
+    /// and require a majority of them to prove they store it.
+    Prove,
+}
+
+/// Result of verifying the chunks of a piece of data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataHealth {
+    /// Number of chunks checked, including the ones holding the data map.
+    pub chunks_checked: usize,
+    /// Chunks that could not be fetched from the network.
+    pub missing: Vec<XorName>,
+    /// Chunks that could be fetched, but whose holders failed to prove they store them.
+    pub unproven: Vec<XorName>,
+    /// A chunk holding (part of) the data map is missing, so the chunks of the data itself
+    /// could not be listed and were not checked.
+    pub data_map_missing: bool,
+}
+
+impl DataHealth {
+    /// Whether every chunk was found (and proven, if asked for).
+    pub fn is_healthy(&self) -> bool {
+        self.missing.is_empty() && self.unproven.is_empty()
+    }
+
+    fn record(&mut self, addr: XorName, status: ChunkStatus) {
+        self.chunks_checked += 1;
+        match status {
+            ChunkStatus::Stored => {}
+            ChunkStatus::Unproven => self.unproven.push(addr),
+            ChunkStatus::Missing => self.missing.push(addr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkStatus {
+    Stored,
+    Unproven,
+    Missing,
+}
+
+impl Client {
+    /// Check that every chunk of private data is still stored on the network.
+    ///
+    /// Missing chunks are reported in the returned [`DataHealth`] rather than as an error.
+    /// Errors are only returned when the data map itself is invalid.
+    pub async fn data_verify(
+        &self,
+        data_map: &DataMapChunk,
+        mode: VerifyMode,
+    ) -> Result<DataHealth, GetError> {
+        info!(
+            "Verifying private data from Data Map {:?}",
+            data_map.address()
+        );
+        let mut health = DataHealth::default();
+        self.verify_from_data_map_chunk(data_map.0.value(), mode, &mut health)
+            .await?;
+        info!(
+            "Verified {} chunks of private data: {} missing, {} unproven",
+            health.chunks_checked,
+            health.missing.len(),
+            health.unproven.len()
+        );
+        Ok(health)
+    }
+
+    /// Check that every chunk of public data, including its data map chunk, is still stored on
+    /// the network.
+    pub async fn data_verify_public(
+        &self,
+        addr: DataAddr,
+        mode: VerifyMode,
+    ) -> Result<DataHealth, GetError> {
+        info!("Verifying data at Data Address: {addr:?}");
+        let mut health = DataHealth::default();
+        let (status, data_map_chunk) = self.verify_chunk(addr, mode).await;
+        health.record(addr, status);
+
+        match data_map_chunk {
+            Some(data_map_chunk) => {
+                self.verify_from_data_map_chunk(data_map_chunk.value(), mode, &mut health)
+                    .await?;
+            }
+            None => health.data_map_missing = true,
+        }
+        info!(
+            "Verified {} chunks of data at {addr:?}: {} missing, {} unproven",
+            health.chunks_checked,
+            health.missing.len(),
+            health.unproven.len()
+        );
+        Ok(health)
+    }
+
+    /// Verify the chunks listed by a wrapped data map, going through any additional levels.
+    async fn verify_from_data_map_chunk(
+        &self,
+        data_map_bytes: &Bytes,
+        mode: VerifyMode,
+        health: &mut DataHealth,
+    ) -> Result<(), GetError> {
+        let mut data_map_level: DataMapLevel = rmp_serde::from_slice(data_map_bytes)
+            .map_err(GetError::InvalidDataMap)
+            .inspect_err(|err| error!("Error deserializing data map: {err:?}"))?;
+
+        loop {
+            let map = match data_map_level {
+                DataMapLevel::First(map) => {
+                    // The content of the data is not needed, only its availability.
+                    for (_, addr, status, _) in self.verify_chunks(&map, mode, false).await {
+                        health.record(addr, status);
+                    }
+                    return Ok(());
+                }
+                DataMapLevel::Additional(map) => map,
+            };
+
+            let mut encrypted_chunks = vec![];
+            for (index, addr, status, chunk) in self.verify_chunks(&map, mode, true).await {
+                health.record(addr, status);
+                if let Some(chunk) = chunk {
+                    encrypted_chunks.push(EncryptedChunk {
+                        index,
+                        content: chunk.value,
+                    });
+                }
+            }
+            if encrypted_chunks.len() < map.infos().len() {
+                warn!(
+                    "Chunks of the data map are missing, the chunks of the data cannot be listed"
+                );
+                health.data_map_missing = true;
+                return Ok(());
+            }
+
+            let data = decrypt_full_set(&map, &encrypted_chunks).map_err(|e| {
+                error!("Error decrypting data map chunks: {e:?}");
+                GetError::Decryption(crate::self_encryption::Error::SelfEncryption(e))
+            })?;
+            data_map_level = rmp_serde::from_slice(&data).map_err(|err| {
+                error!("Error deserializing data map: {err:?}");
+                GetError::InvalidDataMap(err)
+            })?;
+        }
+    }
+
+    /// Verify all chunks of a data map, returning their index, address, status and, if
+    /// `keep_content` is set, the fetched chunk.
+    async fn verify_chunks(
+        &self,
+        data_map: &DataMap,
+        mode: VerifyMode,
+        keep_content: bool,
+    ) -> Vec<(usize, XorName, ChunkStatus, Option<Chunk>)> {
+        let tasks = data_map.infos().into_iter().map(|info| async move {
+            let (status, chunk) = self.verify_chunk(info.dst_hash, mode).await;
+            (
+                info.index,
+                info.dst_hash,
+                status,
+                chunk.filter(|_| keep_content),
+            )
+        });
+        process_tasks_with_max_concurrency(tasks, self.policy.chunk_download_concurrency).await
+    }
+
+    /// Fetch a chunk from the network and, in [`VerifyMode::Prove`], challenge its holders.
+    async fn verify_chunk(&self, addr: XorName, mode: VerifyMode) -> (ChunkStatus, Option<Chunk>) {
+        let chunk = match self.chunk_get_from_network(addr).await {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                warn!("Chunk {addr:?} could not be fetched: {err:?}");
+                return (ChunkStatus::Missing, None);
+            }
+        };
+
+        if mode == VerifyMode::Fetch {
+            return (ChunkStatus::Stored, Some(chunk));
+        }
+        match self.prove_chunk(&chunk).await {
+            Ok(()) => (ChunkStatus::Stored, Some(chunk)),
+            Err(err) => {
+                warn!("Holders of chunk {addr:?} failed to prove they store it: {err:?}");
+                (ChunkStatus::Unproven, Some(chunk))
+            }
+        }
+    }
+
+    async fn prove_chunk(&self, chunk: &Chunk) -> Result<(), GetError> {
+        let stored_on_node =
+            try_serialize_record(chunk, RecordKind::DataOnly(DataTypes::Chunk))?.to_vec();
+        let nonce = thread_rng().gen::<u64>();
+        let expected_proof = ChunkProof::new(&stored_on_node, nonce);
+
+        self.network
+            .verify_chunk_existence(
+                NetworkAddress::from_chunk_address(ChunkAddress::new(*chunk.name())),
+                nonce,
+                expected_proof,
+                Quorum::Majority,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -1,10 +1,30 @@
-use crate::client::data::PutError;
+//! Paying for uploads with a wallet outside of the client, e.g. a browser wallet or an air-gapped signer.
+//!
+//! The offline workflow exchanges two JSON files with the signer:
+//!
+//! 1. [`Client::prepare_payment`] quotes the content addresses to upload and returns a
+//!    [`PaymentRequest`], holding the chosen quotes and the transactions paying for them.
+//! 2. The signer sends the [`PaymentRequest::approve`] transaction, then each of the
+//!    [`PaymentRequest::payments`], and records their hashes in a [`PaymentResult`].
+//! 3. [`PaymentRequest::receipt`] turns the request and the result into a [`Receipt`], which pays
+//!    for the upload as [`PaymentOption::Receipt`](crate::client::payment::PaymentOption::Receipt).
+//!
+//! Both files carry a `version`, currently [`PAYMENT_FILE_VERSION`]. Files with another version are
+//! rejected.
+
+use crate::client::data::{PayError, PutError};
+use crate::client::payment::{receipt_from_paid_quotes, Receipt};
+use crate::client::quote::StoreQuote;
 use crate::self_encryption::encrypt;
 use crate::Client;
-use ant_evm::QuotePayment;
-use ant_protocol::storage::Chunk;
+use ant_evm::{
+    Amount, Calldata, EncodedPeerId, EvmAddress, EvmNetwork, PaymentQuote, QuotePayment,
+};
+use ant_evm::{QuoteHash, TxHash};
+use ant_protocol::storage::{Chunk, DataTypes};
 use bytes::Bytes;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use xor_name::XorName;
 
 #[allow(unused_imports)]
@@ -12,12 +32,141 @@ pub use ant_evm::external_signer::*;
 
 use super::quote::QuoteForAddress;
 
+/// Version of the [`PaymentRequest`] and [`PaymentResult`] file format.
+pub const PAYMENT_FILE_VERSION: u32 = 2;
+
+/// Errors reading payment request and payment result files.
+#[derive(Debug, thiserror::Error)]
+pub enum PaymentFileError {
+    #[error("Unsupported payment file version: {0}, expected version {PAYMENT_FILE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid payment file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid peer id in payment request: {0}")]
+    InvalidPeerId(#[from] libp2p::identity::ParseError),
+}
+
+/// A transaction for the signer to sign and send.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentTransaction {
+    /// Contract the transaction is sent to.
+    pub to: EvmAddress,
+    /// Input data of the transaction.
+    pub calldata: Calldata,
+    /// Quotes the transaction pays for, empty for the approval.
+    pub quote_hashes: Vec<QuoteHash>,
+}
+
+/// Everything a signer needs to pay for storing a set of content addresses, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentRequest {
+    /// Format version, [`PAYMENT_FILE_VERSION`].
+    pub version: u32,
+    /// The EVM network the transactions are to be sent on.
+    pub network: EvmNetwork,
+    /// All content addresses of the upload, including the ones already stored which need no payment.
+    pub content_addrs: Vec<XorName>,
+    /// The quotes chosen for each content address to pay for, with the price to pay each node.
+    pub quotes: BTreeMap<XorName, Vec<(EncodedPeerId, PaymentQuote, Amount)>>,
+    /// Total amount of tokens paid.
+    pub total_amount: Amount,
+    /// Transaction approving the payment vault to spend [`PaymentRequest::total_amount`], to be sent first.
+    pub approve: PaymentTransaction,
+    /// Transactions paying for batches of quotes.
+    pub payments: Vec<PaymentTransaction>,
+}
+
+impl PaymentRequest {
+    /// Serialize to the JSON file format.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Parse the JSON file format.
+    pub fn from_json(json: &str) -> Result<Self, PaymentFileError> {
+        check_version(json)?;
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Form the [`Receipt`] of the quotes paid for in `result`.
+    ///
+    /// Content addresses with quotes that were not all paid for are left out, uploading them fails.
+    pub fn receipt(&self, result: &PaymentResult) -> Result<Receipt, PaymentFileError> {
+        let mut quotes = HashMap::new();
+        for (addr, addr_quotes) in &self.quotes {
+            let addr_quotes = addr_quotes
+                .iter()
+                .map(|(peer, quote, price)| Ok((peer.to_peer_id()?, quote.clone(), *price)))
+                .collect::<Result<Vec<_>, PaymentFileError>>()?;
+            let _ = quotes.insert(*addr, QuoteForAddress(addr_quotes));
+        }
+        Ok(receipt_from_paid_quotes(
+            StoreQuote(quotes),
+            &result.tx_hashes,
+        ))
+    }
+}
+
+/// The transactions a signer sent for a [`PaymentRequest`], see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentResult {
+    /// Format version, [`PAYMENT_FILE_VERSION`].
+    pub version: u32,
+    /// Hash of the transaction that paid each quote.
+    pub tx_hashes: BTreeMap<QuoteHash, TxHash>,
+}
+
+impl Default for PaymentResult {
+    fn default() -> Self {
+        Self {
+            version: PAYMENT_FILE_VERSION,
+            tx_hashes: BTreeMap::new(),
+        }
+    }
+}
+
+impl PaymentResult {
+    /// Record that `transaction` of the request was sent, with the given hash.
+    pub fn record(&mut self, transaction: &PaymentTransaction, tx_hash: TxHash) {
+        for quote_hash in &transaction.quote_hashes {
+            let _ = self.tx_hashes.insert(*quote_hash, tx_hash);
+        }
+    }
+
+    /// Serialize to the JSON file format.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Parse the JSON file format.
+    pub fn from_json(json: &str) -> Result<Self, PaymentFileError> {
+        check_version(json)?;
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Check the version before parsing the rest, so files of other versions fail clearly.
+fn check_version(json: &str) -> Result<(), PaymentFileError> {
+    #[derive(Deserialize)]
+    struct Versioned {
+        version: u32,
+    }
+
+    let Versioned { version } = serde_json::from_str(json)?;
+    if version != PAYMENT_FILE_VERSION {
+        return Err(PaymentFileError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
 impl Client {
-    /// Get quotes for data.
+    /// Get quotes for data of `data_type` at the content addresses, with the size of the data
+    /// stored at each.
     /// Returns a cost map, data payments to be executed and a list of free (already paid for) chunks.
     pub async fn get_quotes_for_content_addresses(
         &self,
-        content_addrs: impl Iterator<Item = XorName> + Clone,
+        data_type: DataTypes,
+        content_addrs: impl Iterator<Item = (XorName, usize)> + Clone,
     ) -> Result<
         (
             HashMap<XorName, QuoteForAddress>,
@@ -26,15 +175,80 @@ impl Client {
         ),
         PutError,
     > {
-        let quote = self.get_store_quotes(content_addrs.clone()).await?;
+        let quote = self
+            .get_store_quotes(data_type, content_addrs.clone())
+            .await?;
         let payments = quote.payments();
         let free_chunks = content_addrs
+            .map(|(addr, _)| addr)
             .filter(|addr| !quote.0.contains_key(addr))
             .collect();
         let quotes_per_addr: HashMap<_, _> = quote.0.into_iter().collect();
 
         Ok((quotes_per_addr, payments, free_chunks))
     }
+
+    /// Quote the content addresses, with the size of the data of `data_type` stored at each, and
+    /// form the [`PaymentRequest`] paying for them.
+    ///
+    /// Addresses already stored on the network get no quotes and need no payment.
+    pub async fn prepare_payment(
+        &self,
+        data_type: DataTypes,
+        content_addrs: impl Iterator<Item = (XorName, usize)> + Clone,
+    ) -> Result<PaymentRequest, PayError> {
+        let store_quote = self
+            .get_store_quotes(data_type, content_addrs.clone())
+            .await?;
+
+        // the cheapest quotes are not paid, see `Client::get_store_quotes`
+        let payments: Vec<QuotePayment> = store_quote
+            .payments()
+            .into_iter()
+            .filter(|(_, _, amount)| *amount > Amount::ZERO)
+            .collect();
+        let calldata = pay_for_quotes_calldata(&self.evm_network, payments)?;
+        let (approve_calldata, approve_to) = approve_to_spend_tokens_calldata(
+            &self.evm_network,
+            calldata.approve_spender,
+            calldata.approve_amount,
+        );
+
+        let quotes = store_quote
+            .0
+            .into_iter()
+            .map(|(addr, QuoteForAddress(quotes))| {
+                let quotes = quotes
+                    .into_iter()
+                    .map(|(peer, quote, price)| (EncodedPeerId::from(peer), quote, price))
+                    .collect();
+                (addr, quotes)
+            })
+            .collect();
+        let payments = calldata
+            .batched_calldata_map
+            .into_iter()
+            .map(|(input, quote_hashes)| PaymentTransaction {
+                to: calldata.to,
+                calldata: input,
+                quote_hashes,
+            })
+            .collect();
+
+        Ok(PaymentRequest {
+            version: PAYMENT_FILE_VERSION,
+            network: self.evm_network.clone(),
+            content_addrs: content_addrs.map(|(addr, _)| addr).collect(),
+            quotes,
+            total_amount: calldata.approve_amount,
+            approve: PaymentTransaction {
+                to: approve_to,
+                calldata: approve_calldata,
+                quote_hashes: vec![],
+            },
+            payments,
+        })
+    }
 }
 
 /// Encrypts data as chunks.
@@ -48,3 +262,66 @@ pub fn encrypt_data(data: Bytes) -> Result<(Chunk, Vec<Chunk>), PutError> {
 
     Ok((result.0, result.1))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_evm::utils::dummy_address;
+    use libp2p::PeerId;
+
+    #[test]
+    fn payment_files_round_trip_into_a_receipt() -> eyre::Result<()> {
+        let addr = XorName::random(&mut rand::thread_rng());
+        let quotes: Vec<_> = (0..5)
+            .map(|i| {
+                let mut quote = PaymentQuote::test_dummy(addr);
+                quote.quoting_metrics.close_records_stored = i;
+                let price = Amount::from(i);
+                (EncodedPeerId::from(PeerId::random()), quote, price)
+            })
+            .collect();
+        let payment = PaymentTransaction {
+            to: dummy_address(),
+            calldata: Calldata::from(vec![1, 2, 3]),
+            quote_hashes: quotes.iter().skip(1).map(|(_, q, _)| q.hash()).collect(),
+        };
+        let request = PaymentRequest {
+            version: PAYMENT_FILE_VERSION,
+            network: EvmNetwork::ArbitrumSepolia,
+            content_addrs: vec![addr],
+            quotes: BTreeMap::from([(addr, quotes)]),
+            total_amount: Amount::from(10),
+            approve: PaymentTransaction {
+                to: dummy_address(),
+                calldata: Calldata::from(vec![4, 5, 6]),
+                quote_hashes: vec![],
+            },
+            payments: vec![payment.clone()],
+        };
+
+        let request = PaymentRequest::from_json(&request.to_json()?)?;
+        let mut result = PaymentResult::default();
+        assert!(request.receipt(&result)?.is_empty());
+
+        result.record(&payment, TxHash::repeat_byte(7));
+        let result = PaymentResult::from_json(&result.to_json()?)?;
+        let receipt = request.receipt(&result)?;
+        assert_eq!(receipt.keys().collect::<Vec<_>>(), vec![&addr]);
+        let (proof, price) = &receipt[&addr];
+        assert_eq!(proof.peer_quotes.len(), 5);
+        assert_eq!(price.as_atto(), Amount::from(10));
+
+        let newer = r#"{"version": 3, "tx_hashes": {}}"#;
+        assert!(matches!(
+            PaymentResult::from_json(newer),
+            Err(PaymentFileError::UnsupportedVersion(3))
+        ));
+        // quotes of version 1 files do not name the data they are for
+        let older = r#"{"version": 1, "tx_hashes": {}}"#;
+        assert!(matches!(
+            PaymentResult::from_json(older),
+            Err(PaymentFileError::UnsupportedVersion(1))
+        ));
+        Ok(())
+    }
+}
@@ -33,7 +33,9 @@ pub enum RenameError {
     FileNotFound(PathBuf),
 }
 
-/// Metadata for a file in an archive. Time values are UNIX timestamps.
+/// Metadata for a file, directory or symlink in an archive. Time values are UNIX timestamps.
+///
+/// Fields added after the first archive format default to empty when reading older archives.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Metadata {
     /// File creation time on local file system. See [`std::fs::Metadata::created`] for details per OS.
@@ -42,6 +44,12 @@ pub struct Metadata {
     pub modified: u64,
     /// File size in bytes
     pub size: u64,
+    /// Unix file mode (type and permission bits), if known.
+    #[serde(default)]
+    pub mode: Option<u32>,
+    /// Extended attributes, by name.
+    #[serde(default)]
+    pub xattrs: BTreeMap<String, Vec<u8>>,
 }
 
 impl Metadata {
@@ -56,6 +64,8 @@ impl Metadata {
             created: now,
             modified: now,
             size,
+            mode: None,
+            xattrs: BTreeMap::new(),
         }
     }
 }
@@ -66,15 +76,24 @@ impl Metadata {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct PrivateArchive {
     map: BTreeMap<PathBuf, (DataMapChunk, Metadata)>,
+    #[serde(default)]
+    dirs: BTreeMap<PathBuf, Metadata>,
+    #[serde(default)]
+    symlinks: BTreeMap<PathBuf, (PathBuf, Metadata)>,
+}
+
+/// Versioned encoding of a [`PrivateArchive`]. Archives written before the format was versioned
+/// are plain [`PrivateArchive`] structs and are still read by [`PrivateArchive::from_bytes`].
+#[derive(Serialize, Deserialize)]
+enum PrivateArchiveVersioned {
+    V1(PrivateArchive),
 }
 
 impl PrivateArchive {
     /// Create a new emtpy local archive
     /// Note that this does not upload the archive to the network
     pub fn new() -> Self {
-        Self {
-            map: BTreeMap::new(),
-        }
+        Self::default()
     }
 
     /// Rename a file in an archive
@@ -101,6 +120,34 @@ impl PrivateArchive {
         debug!("Added a new file to the archive, path: {:?}", path);
     }
 
+    /// Add a directory to a local archive, so it is restored even when empty.
+    /// Note that this does not upload the archive to the network
+    pub fn add_dir(&mut self, path: PathBuf, meta: Metadata) {
+        debug!("Added a new directory to the archive, path: {path:?}");
+        self.dirs.insert(path, meta);
+    }
+
+    /// Add a symbolic link pointing to `target` to a local archive
+    /// Note that this does not upload the archive to the network
+    pub fn add_symlink(&mut self, path: PathBuf, target: PathBuf, meta: Metadata) {
+        debug!("Added a new symlink to the archive, path: {path:?} target: {target:?}");
+        self.symlinks.insert(path, (target, meta));
+    }
+
+    /// Iterate over the directories in the archive.
+    pub fn dirs(&self) -> impl DoubleEndedIterator<Item = (&PathBuf, &Metadata)> {
+        self.dirs.iter()
+    }
+
+    /// Iterate over the symbolic links in the archive.
+    ///
+    /// Returns an iterator over (path, target, [`Metadata`])
+    pub fn symlinks(&self) -> impl Iterator<Item = (&PathBuf, &PathBuf, &Metadata)> {
+        self.symlinks
+            .iter()
+            .map(|(path, (target, meta))| (path, target, meta))
+    }
+
     /// List all files in the archive
     pub fn files(&self) -> Vec<(PathBuf, Metadata)> {
         self.map
@@ -132,15 +179,18 @@ impl PrivateArchive {
     }
 
     /// Deserialize from bytes.
+    ///
+    /// Archives written before the format was versioned are read as well.
     pub fn from_bytes(data: Bytes) -> Result<PrivateArchive, rmp_serde::decode::Error> {
-        let root: PrivateArchive = rmp_serde::from_slice(&data[..])?;
-
-        Ok(root)
+        match rmp_serde::from_slice::<PrivateArchiveVersioned>(&data[..]) {
+            Ok(PrivateArchiveVersioned::V1(root)) => Ok(root),
+            Err(_) => rmp_serde::from_slice(&data[..]),
+        }
     }
 
-    /// Serialize to bytes.
+    /// Serialize to bytes, using the latest archive format.
     pub fn to_bytes(&self) -> Result<Bytes, rmp_serde::encode::Error> {
-        let root_serialized = rmp_serde::to_vec(&self)?;
+        let root_serialized = rmp_serde::to_vec(&PrivateArchiveVersioned::V1(self.clone()))?;
         let root_serialized = Bytes::from(root_serialized);
 
         Ok(root_serialized)
@@ -178,3 +228,58 @@ impl Client {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_protocol::storage::Chunk;
+
+    /// The archive format before it was versioned.
+    #[derive(Serialize)]
+    struct LegacyPrivateArchive {
+        map: BTreeMap<PathBuf, (DataMapChunk, LegacyMetadata)>,
+    }
+
+    #[derive(Serialize)]
+    struct LegacyMetadata {
+        created: u64,
+        modified: u64,
+        size: u64,
+    }
+
+    #[test]
+    fn reads_archives_written_before_versioning() -> eyre::Result<()> {
+        let data_map = DataMapChunk::from(Chunk::new(Bytes::from_static(b"data map")));
+        let meta = LegacyMetadata {
+            created: 1,
+            modified: 2,
+            size: 3,
+        };
+        let legacy = LegacyPrivateArchive {
+            map: BTreeMap::from([(PathBuf::from("dir/file.txt"), (data_map.clone(), meta))]),
+        };
+        let bytes = Bytes::from(rmp_serde::to_vec(&legacy)?);
+
+        let archive = PrivateArchive::from_bytes(bytes)?;
+        let (got, meta) = archive
+            .map()
+            .get(Path::new("dir/file.txt"))
+            .ok_or(eyre::eyre!("file missing"))?;
+        assert_eq!(got, &data_map);
+        assert_eq!((meta.created, meta.modified, meta.size), (1, 2, 3));
+        assert_eq!(meta.mode, None);
+        assert!(meta.xattrs.is_empty());
+        assert_eq!(archive.dirs().count(), 0);
+
+        let mut meta = Metadata::new_with_size(0);
+        meta.mode = Some(0o40755);
+        let _ = meta
+            .xattrs
+            .insert("user.tag".to_string(), b"value".to_vec());
+        let mut archive = archive;
+        archive.add_dir(PathBuf::from("dir/empty"), meta.clone());
+        archive.add_symlink(PathBuf::from("dir/link"), PathBuf::from("file.txt"), meta);
+        assert_eq!(PrivateArchive::from_bytes(archive.to_bytes()?)?, archive);
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::sync::LazyLock;
+
+use ant_evm::EvmWallet;
+use ant_networking::time::{Duration, SystemTime, UNIX_EPOCH};
+use ant_networking::{GetRecordError, NetworkError};
+use ant_protocol::storage::{ChunkAddress, Pointer, PointerAddress, PointerTarget};
+use bls::SecretKey;
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use super::archive::{PrivateArchive, PrivateArchiveAccess};
+use super::archive_public::{ArchiveAddr, PublicArchive};
+use crate::client::{
+    data::{GetError, PutError},
+    payment::PaymentOption,
+    pointer::PointerError,
+    vault::{app_name_to_vault_content_type, VaultContentType, VaultError},
+    Client,
+};
+
+/// Content type of the scratchpads holding the current version of a private mutable archive.
+pub static MUTABLE_ARCHIVE_CONTENT_TYPE: LazyLock<VaultContentType> =
+    LazyLock::new(|| app_name_to_vault_content_type("MutableArchive"));
+
+/// Errors that can occur while publishing or fetching a mutable archive.
+#[derive(Error, Debug)]
+pub enum MutableArchiveError {
+    #[error("Failed to upload archive: {0}")]
+    Put(#[from] PutError),
+    #[error("Failed to fetch archive: {0}")]
+    Get(#[from] GetError),
+    #[error("Pointer error: {0}")]
+    Pointer(#[from] PointerError),
+    #[error("Scratchpad error: {0}")]
+    Scratchpad(#[from] VaultError),
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+    #[error("Pointer at {0:?} does not point to an archive version")]
+    UnexpectedTarget(PointerAddress),
+    #[error("Scratchpad does not hold a mutable archive, found content type {0}")]
+    UnexpectedContentType(VaultContentType),
+}
+
+impl MutableArchiveError {
+    /// Whether there is no mutable archive at the address: no pointer, or no data it refers to.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            MutableArchiveError::Get(err) => err.is_not_found(),
+            MutableArchiveError::Pointer(PointerError::Network(err)) => is_record_not_found(err),
+            _ => false,
+        }
+    }
+}
+
+/// One published version of a mutable archive.
+///
+/// Versions are uploaded as data of their own and link back to the version they replaced,
+/// the stable address of the archive always refers to the latest one.
+/// For public archives `A` is an [`ArchiveAddr`], for private archives a [`PrivateArchiveAccess`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArchiveVersion<A> {
+    /// Version number, starting at 0 for the first published version.
+    pub version: u64,
+    /// UNIX timestamp of when this version was published.
+    pub published: u64,
+    /// The archive of this version.
+    pub archive: A,
+    /// Where the previous version is stored, if any.
+    pub previous: Option<A>,
+}
+
+impl<A: Serialize + DeserializeOwned> ArchiveVersion<A> {
+    fn new(archive: A, previous: Option<(A, &ArchiveVersion<A>)>) -> Self {
+        let published = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0))
+            .as_secs();
+        let (version, previous) = match previous {
+            Some((addr, prev)) => (prev.version + 1, Some(addr)),
+            None => (0, None),
+        };
+        Self {
+            version,
+            published,
+            archive,
+            previous,
+        }
+    }
+
+    /// Deserialize from bytes.
+    pub fn from_bytes(data: Bytes) -> Result<Self, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(&data[..])
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> Result<Bytes, rmp_serde::encode::Error> {
+        Ok(Bytes::from(rmp_serde::to_vec(self)?))
+    }
+}
+
+impl Client {
+    /// Derive the key owning the mutable archive called `name` from the `owner` key.
+    ///
+    /// The same owner and name always give the same key, and so the same stable address.
+    pub fn mutable_archive_key(owner: &SecretKey, name: &str) -> SecretKey {
+        owner.derive_child(name.as_bytes())
+    }
+
+    /// Publish a [`PublicArchive`] as the new version of the mutable archive owned by `key`.
+    ///
+    /// The archive is uploaded, then a version record linking to the previous version, and finally
+    /// the [`Pointer`] at the stable address is created or retargeted to that record.
+    /// Files referenced by the archive are expected to be uploaded already. Chunks that are already
+    /// stored on the network, such as those of unchanged files, are not paid for again.
+    ///
+    /// The stable address is the [`PointerAddress`] of `key`'s public key.
+    pub async fn archive_update_public(
+        &self,
+        archive: &PublicArchive,
+        key: &SecretKey,
+        wallet: &EvmWallet,
+    ) -> Result<ArchiveVersion<ArchiveAddr>, MutableArchiveError> {
+        let address = PointerAddress::from_owner(key.public_key());
+        let previous = match self.pointer_get(address).await {
+            Ok(pointer) => {
+                let version_addr = version_addr_from_pointer(&pointer)
+                    .ok_or(MutableArchiveError::UnexpectedTarget(address))?;
+                let version = self.archive_version_at_public(version_addr).await?;
+                Some((version_addr, version))
+            }
+            Err(PointerError::Network(err)) if is_record_not_found(&err) => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        let archive_addr = self.archive_put_public(archive, wallet).await?;
+        let version = ArchiveVersion::new(
+            archive_addr,
+            previous.as_ref().map(|(addr, version)| (*addr, version)),
+        );
+        let bytes = version
+            .to_bytes()
+            .map_err(|e| MutableArchiveError::Serialization(format!("{e:?}")))?;
+        let version_addr = self.data_put_public(bytes, wallet.into()).await?;
+
+        let target = PointerTarget::ChunkAddress(ChunkAddress::new(version_addr));
+        if previous.is_some() {
+            self.pointer_update(key, target).await?;
+        } else {
+            let pointer = Pointer::new(key.public_key(), 0, target, key);
+            self.pointer_put(pointer, wallet).await?;
+        }
+
+        info!(
+            "Published version {} of public archive {archive_addr:?} at {address:?}",
+            version.version
+        );
+        Ok(version)
+    }
+
+    /// Fetch the latest version of the public mutable archive at `address`.
+    pub async fn archive_get_mutable_public(
+        &self,
+        address: PointerAddress,
+    ) -> Result<PublicArchive, MutableArchiveError> {
+        let version = self.archive_version_public(address).await?;
+        Ok(self.archive_get_public(version.archive).await?)
+    }
+
+    /// Fetch the latest version record of the public mutable archive at `address`.
+    pub async fn archive_version_public(
+        &self,
+        address: PointerAddress,
+    ) -> Result<ArchiveVersion<ArchiveAddr>, MutableArchiveError> {
+        let pointer = self.pointer_get(address).await?;
+        let version_addr = version_addr_from_pointer(&pointer)
+            .ok_or(MutableArchiveError::UnexpectedTarget(address))?;
+        self.archive_version_at_public(version_addr).await
+    }
+
+    /// Fetch all version records of the public mutable archive at `address`, latest first.
+    pub async fn archive_history_public(
+        &self,
+        address: PointerAddress,
+    ) -> Result<Vec<ArchiveVersion<ArchiveAddr>>, MutableArchiveError> {
+        let mut history = vec![self.archive_version_public(address).await?];
+        while let Some(previous) = history.last().and_then(|version| version.previous) {
+            history.push(self.archive_version_at_public(previous).await?);
+        }
+        debug!("Found {} versions of archive at {address:?}", history.len());
+        Ok(history)
+    }
+
+    /// Publish a [`PrivateArchive`] as the new version of the mutable archive owned by `key`.
+    ///
+    /// Works like [`Client::archive_update_public`], except that the archive and version record
+    /// are uploaded privately and the latest version is kept in a [`ant_protocol::storage::Scratchpad`]
+    /// encrypted with `key`, so only holders of `key` can read it.
+    pub async fn archive_update(
+        &self,
+        archive: &PrivateArchive,
+        key: &SecretKey,
+        payment_option: PaymentOption,
+    ) -> Result<ArchiveVersion<PrivateArchiveAccess>, MutableArchiveError> {
+        let previous = match self.archive_version_access(key).await {
+            Ok(access) => {
+                let version = self.archive_version_at(access.clone()).await?;
+                Some((access, version))
+            }
+            Err(MutableArchiveError::Scratchpad(VaultError::Network(err)))
+                if is_record_not_found(&err) =>
+            {
+                None
+            }
+            Err(err) => return Err(err),
+        };
+
+        let archive_access = self.archive_put(archive, payment_option.clone()).await?;
+        let version = ArchiveVersion::new(
+            archive_access,
+            previous
+                .as_ref()
+                .map(|(access, version)| (access.clone(), version)),
+        );
+        let bytes = version
+            .to_bytes()
+            .map_err(|e| MutableArchiveError::Serialization(format!("{e:?}")))?;
+        let version_access = self.data_put(bytes, payment_option.clone()).await?;
+
+        let bytes = rmp_serde::to_vec(&version_access)
+            .map_err(|e| MutableArchiveError::Serialization(format!("{e:?}")))?;
+        let _ = self
+            .write_bytes_to_vault(
+                Bytes::from(bytes),
+                payment_option,
+                key,
+                *MUTABLE_ARCHIVE_CONTENT_TYPE,
+            )
+            .await?;
+
+        info!(
+            "Published version {} of private archive owned by {:?}",
+            version.version,
+            key.public_key()
+        );
+        Ok(version)
+    }
+
+    /// Fetch the latest version of the private mutable archive owned by `key`.
+    pub async fn archive_get_mutable(
+        &self,
+        key: &SecretKey,
+    ) -> Result<PrivateArchive, MutableArchiveError> {
+        let version = self.archive_version(key).await?;
+        Ok(self.archive_get(version.archive).await?)
+    }
+
+    /// Fetch the latest version record of the private mutable archive owned by `key`.
+    pub async fn archive_version(
+        &self,
+        key: &SecretKey,
+    ) -> Result<ArchiveVersion<PrivateArchiveAccess>, MutableArchiveError> {
+        let access = self.archive_version_access(key).await?;
+        self.archive_version_at(access).await
+    }
+
+    /// Fetch all version records of the private mutable archive owned by `key`, latest first.
+    pub async fn archive_history(
+        &self,
+        key: &SecretKey,
+    ) -> Result<Vec<ArchiveVersion<PrivateArchiveAccess>>, MutableArchiveError> {
+        let mut history = vec![self.archive_version(key).await?];
+        while let Some(previous) = history.last().and_then(|version| version.previous.clone()) {
+            history.push(self.archive_version_at(previous).await?);
+        }
+        debug!(
+            "Found {} versions of private archive owned by {:?}",
+            history.len(),
+            key.public_key()
+        );
+        Ok(history)
+    }
+
+    async fn archive_version_at_public(
+        &self,
+        addr: ArchiveAddr,
+    ) -> Result<ArchiveVersion<ArchiveAddr>, MutableArchiveError> {
+        let data = self.data_get_public(addr).await?;
+        ArchiveVersion::from_bytes(data).map_err(|e| {
+            MutableArchiveError::Serialization(format!("Failed to deserialize version: {e:?}"))
+        })
+    }
+
+    async fn archive_version_at(
+        &self,
+        access: PrivateArchiveAccess,
+    ) -> Result<ArchiveVersion<PrivateArchiveAccess>, MutableArchiveError> {
+        let data = self.data_get(access).await?;
+        ArchiveVersion::from_bytes(data).map_err(|e| {
+            MutableArchiveError::Serialization(format!("Failed to deserialize version: {e:?}"))
+        })
+    }
+
+    /// Read where the latest version record is stored from the scratchpad owned by `key`.
+    async fn archive_version_access(
+        &self,
+        key: &SecretKey,
+    ) -> Result<PrivateArchiveAccess, MutableArchiveError> {
+        let (bytes, content_type) = self.fetch_and_decrypt_vault(key).await?;
+        if content_type != *MUTABLE_ARCHIVE_CONTENT_TYPE {
+            return Err(MutableArchiveError::UnexpectedContentType(content_type));
+        }
+        rmp_serde::from_slice(&bytes).map_err(|e| {
+            MutableArchiveError::Serialization(format!("Failed to deserialize access: {e:?}"))
+        })
+    }
+}
+
+/// Address of the version record a mutable archive pointer targets.
+fn version_addr_from_pointer(pointer: &Pointer) -> Option<ArchiveAddr> {
+    match pointer.target() {
+        PointerTarget::ChunkAddress(addr) => Some(*addr.xorname()),
+        target => {
+            error!("Mutable archive pointer targets {target:?} instead of a version record");
+            None
+        }
+    }
+}
+
+fn is_record_not_found(err: &NetworkError) -> bool {
+    matches!(
+        err,
+        NetworkError::GetRecordError(GetRecordError::RecordNotFound)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xor_name::XorName;
+
+    #[test]
+    fn versions_link_back_to_previous() -> eyre::Result<()> {
+        let rng = &mut rand::thread_rng();
+        let first = ArchiveVersion::new(XorName::random(rng), None);
+        assert_eq!(first.version, 0);
+        assert_eq!(first.previous, None);
+
+        let first_addr = XorName::random(rng);
+        let second = ArchiveVersion::new(XorName::random(rng), Some((first_addr, &first)));
+        assert_eq!(second.version, 1);
+        assert_eq!(second.previous, Some(first_addr));
+
+        let decoded = ArchiveVersion::<ArchiveAddr>::from_bytes(second.to_bytes()?)?;
+        assert_eq!(decoded, second);
+        Ok(())
+    }
+}
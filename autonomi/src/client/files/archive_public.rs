@@ -35,15 +35,24 @@ pub type ArchiveAddr = XorName;
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct PublicArchive {
     map: BTreeMap<PathBuf, (DataAddr, Metadata)>,
+    #[serde(default)]
+    dirs: BTreeMap<PathBuf, Metadata>,
+    #[serde(default)]
+    symlinks: BTreeMap<PathBuf, (PathBuf, Metadata)>,
+}
+
+/// Versioned encoding of a [`PublicArchive`]. Archives written before the format was versioned
+/// are plain [`PublicArchive`] structs and are still read by [`PublicArchive::from_bytes`].
+#[derive(Serialize, Deserialize)]
+enum PublicArchiveVersioned {
+    V1(PublicArchive),
 }
 
 impl PublicArchive {
     /// Create a new emtpy local archive
     /// Note that this does not upload the archive to the network
     pub fn new() -> Self {
-        Self {
-            map: BTreeMap::new(),
-        }
+        Self::default()
     }
 
     /// Rename a file in an archive
@@ -70,6 +79,34 @@ impl PublicArchive {
         debug!("Added a new file to the archive, path: {:?}", path);
     }
 
+    /// Add a directory to a local archive, so it is restored even when empty.
+    /// Note that this does not upload the archive to the network
+    pub fn add_dir(&mut self, path: PathBuf, meta: Metadata) {
+        debug!("Added a new directory to the archive, path: {path:?}");
+        self.dirs.insert(path, meta);
+    }
+
+    /// Add a symbolic link pointing to `target` to a local archive
+    /// Note that this does not upload the archive to the network
+    pub fn add_symlink(&mut self, path: PathBuf, target: PathBuf, meta: Metadata) {
+        debug!("Added a new symlink to the archive, path: {path:?} target: {target:?}");
+        self.symlinks.insert(path, (target, meta));
+    }
+
+    /// Iterate over the directories in the archive.
+    pub fn dirs(&self) -> impl DoubleEndedIterator<Item = (&PathBuf, &Metadata)> {
+        self.dirs.iter()
+    }
+
+    /// Iterate over the symbolic links in the archive.
+    ///
+    /// Returns an iterator over (path, target, [`Metadata`])
+    pub fn symlinks(&self) -> impl Iterator<Item = (&PathBuf, &PathBuf, &Metadata)> {
+        self.symlinks
+            .iter()
+            .map(|(path, (target, meta))| (path, target, meta))
+    }
+
     /// List all files in the archive
     pub fn files(&self) -> Vec<(PathBuf, Metadata)> {
         self.map
@@ -97,15 +134,18 @@ impl PublicArchive {
     }
 
     /// Deserialize from bytes.
+    ///
+    /// Archives written before the format was versioned are read as well.
     pub fn from_bytes(data: Bytes) -> Result<PublicArchive, rmp_serde::decode::Error> {
-        let root: PublicArchive = rmp_serde::from_slice(&data[..])?;
-
-        Ok(root)
+        match rmp_serde::from_slice::<PublicArchiveVersioned>(&data[..]) {
+            Ok(PublicArchiveVersioned::V1(root)) => Ok(root),
+            Err(_) => rmp_serde::from_slice(&data[..]),
+        }
     }
 
-    /// Serialize to bytes.
+    /// Serialize to bytes, using the latest archive format.
     pub fn to_bytes(&self) -> Result<Bytes, rmp_serde::encode::Error> {
-        let root_serialized = rmp_serde::to_vec(&self)?;
+        let root_serialized = rmp_serde::to_vec(&PublicArchiveVersioned::V1(self.clone()))?;
         let root_serialized = Bytes::from(root_serialized);
 
         Ok(root_serialized)
@@ -180,3 +220,56 @@ impl Client {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The archive format before it was versioned.
+    #[derive(Serialize)]
+    struct LegacyPublicArchive {
+        map: BTreeMap<PathBuf, (DataAddr, LegacyMetadata)>,
+    }
+
+    #[derive(Serialize)]
+    struct LegacyMetadata {
+        created: u64,
+        modified: u64,
+        size: u64,
+    }
+
+    #[test]
+    fn reads_archives_written_before_versioning() -> eyre::Result<()> {
+        let addr = DataAddr::random(&mut rand::thread_rng());
+        let meta = LegacyMetadata {
+            created: 1,
+            modified: 2,
+            size: 3,
+        };
+        let legacy = LegacyPublicArchive {
+            map: BTreeMap::from([(PathBuf::from("dir/file.txt"), (addr, meta))]),
+        };
+        let bytes = Bytes::from(rmp_serde::to_vec(&legacy)?);
+
+        let archive = PublicArchive::from_bytes(bytes)?;
+        let (_, meta) = archive
+            .map()
+            .get(Path::new("dir/file.txt"))
+            .ok_or(eyre::eyre!("file missing"))?;
+        assert_eq!((meta.created, meta.modified, meta.size), (1, 2, 3));
+        assert_eq!(meta.mode, None);
+        assert!(meta.xattrs.is_empty());
+        assert_eq!(archive.dirs().count(), 0);
+
+        let mut meta = Metadata::new_with_size(0);
+        meta.mode = Some(0o40755);
+        let _ = meta
+            .xattrs
+            .insert("user.tag".to_string(), b"value".to_vec());
+        let mut archive = archive;
+        archive.add_dir(PathBuf::from("dir/empty"), meta.clone());
+        archive.add_symlink(PathBuf::from("dir/link"), PathBuf::from("file.txt"), meta);
+        assert_eq!(PublicArchive::from_bytes(archive.to_bytes()?)?, archive);
+        Ok(())
+    }
+}
@@ -14,16 +14,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::archive::Metadata;
 use super::archive::{PrivateArchive, PrivateArchiveAccess};
+use super::fs_public::{
+    check_dirs_and_symlinks, create_dirs_and_symlinks, download_dest, restore_dir_metadata,
+    restore_metadata, write_download, DirsAndSymlinks,
+};
 use crate::client::data::{CostError, DataMapChunk, GetError, PutError};
 use crate::client::files::get_relative_file_path_from_abs_file_and_folder_path;
+use crate::client::payment::PaymentOption;
 use crate::client::utils::process_tasks_with_max_concurrency;
 use crate::client::Client;
+use crate::self_encryption::{encrypt, file_chunk_addrs};
 use ant_evm::EvmWallet;
-use bytes::Bytes;
+use ant_protocol::storage::Chunk;
 use std::{path::PathBuf, sync::LazyLock};
+use xor_name::XorName;
 
-/// Number of files to upload in parallel.
+/// Default number of files to upload in parallel, see
+/// [`ClientPolicy::file_upload_concurrency`](crate::client::policy::ClientPolicy::file_upload_concurrency).
 ///
 /// Can be overridden by the `FILE_UPLOAD_BATCH_SIZE` environment variable.
 pub static FILE_UPLOAD_BATCH_SIZE: LazyLock<usize> = LazyLock::new(|| {
@@ -64,6 +73,17 @@ pub enum DownloadError {
     GetError(#[from] GetError),
     #[error("IO failure")]
     IoError(#[from] std::io::Error),
+    #[error("Refusing to write outside the destination directory: {0}")]
+    UnsafePath(#[from] UnsafePathError),
+}
+
+/// A path of an archive that would be written outside the download destination.
+#[derive(Debug, thiserror::Error)]
+pub enum UnsafePathError {
+    #[error("archive path {0:?} leads outside the destination directory")]
+    Path(PathBuf),
+    #[error("archive symlink {0:?} points outside the destination directory, to {1:?}")]
+    Symlink(PathBuf, PathBuf),
 }
 
 /// Errors that can occur during the file cost calculation.
@@ -88,14 +108,15 @@ impl Client {
         data_access: DataMapChunk,
         to_dest: PathBuf,
     ) -> Result<(), DownloadError> {
-        let data = self.data_get(data_access).await?;
-        if let Some(parent) = to_dest.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-            debug!("Created parent directories for {to_dest:?}");
-        }
-        tokio::fs::write(to_dest.clone(), data).await?;
-        debug!("Downloaded file to {to_dest:?}");
-        Ok(())
+        let download = async {
+            let reader = self.data_get_stream(data_access).await?;
+            let written = write_download(reader, &to_dest).await?;
+            debug!("Downloaded file of {written} bytes to {to_dest:?}");
+            Ok(())
+        };
+        let result = download.await;
+        self.report_file(&to_dest, "download", &result);
+        result
     }
 
     /// Download a private directory from network to local file system
@@ -105,9 +126,14 @@ impl Client {
         to_dest: PathBuf,
     ) -> Result<(), DownloadError> {
         let archive = self.archive_get(archive_access).await?;
-        for (path, addr, _meta) in archive.iter() {
-            self.file_download(addr.clone(), to_dest.join(path)).await?;
+        check_dirs_and_symlinks(archive.dirs(), archive.symlinks())?;
+        for (path, addr, meta) in archive.iter() {
+            let dest = download_dest(&to_dest, path)?;
+            self.file_download(addr.clone(), dest.clone()).await?;
+            restore_metadata(&dest, meta);
         }
+        create_dirs_and_symlinks(&to_dest, archive.dirs(), archive.symlinks())?;
+        restore_dir_metadata(&to_dest, archive.dirs());
         debug!("Downloaded directory to {to_dest:?}");
         Ok(())
     }
@@ -118,35 +144,49 @@ impl Client {
         &self,
         dir_path: PathBuf,
         wallet: &EvmWallet,
+    ) -> Result<PrivateArchive, UploadError> {
+        self.dir_upload_with_payment(dir_path, wallet.into()).await
+    }
+
+    /// Same as [`Client::dir_upload`], paying with the given [`PaymentOption`], e.g. a [`Receipt`](crate::client::payment::Receipt)
+    /// of payments made for the content addresses from [`Client::dir_and_archive_content_addrs`].
+    pub async fn dir_upload_with_payment(
+        &self,
+        dir_path: PathBuf,
+        payment_option: PaymentOption,
     ) -> Result<PrivateArchive, UploadError> {
         info!("Uploading directory as private: {dir_path:?}");
         let start = tokio::time::Instant::now();
 
         // start upload of file in parallel
+        let payment_option = &payment_option;
         let mut upload_tasks = Vec::new();
+        let mut dirs_and_symlinks = DirsAndSymlinks::default();
         for entry in walkdir::WalkDir::new(dir_path.clone()) {
             let entry = entry?;
-            if !entry.file_type().is_file() {
+            if dirs_and_symlinks.record(&entry, &dir_path) || !entry.file_type().is_file() {
                 continue;
             }
 
             let metadata = super::fs_public::metadata_from_entry(&entry);
             let path = entry.path().to_path_buf();
             upload_tasks.push(async move {
-                let file = self.file_upload(path.clone(), wallet).await;
+                let file = self.file_upload(path.clone(), payment_option.clone()).await;
                 (path, metadata, file)
             });
         }
 
         // wait for all files to be uploaded
         let uploads =
-            process_tasks_with_max_concurrency(upload_tasks, *FILE_UPLOAD_BATCH_SIZE).await;
+            process_tasks_with_max_concurrency(upload_tasks, self.policy.file_upload_concurrency)
+                .await;
         info!(
             "Upload of {} files completed in {:?}",
             uploads.len(),
             start.elapsed()
         );
         let mut archive = PrivateArchive::new();
+        dirs_and_symlinks.add_to_private(&mut archive);
         for (path, metadata, maybe_file) in uploads.into_iter() {
             let rel_path = get_relative_file_path_from_abs_file_and_folder_path(&path, &dir_path);
 
@@ -178,20 +218,84 @@ impl Client {
     }
 
     /// Upload a private file to the network.
-    /// Reads file, splits into chunks, uploads chunks, uploads datamap, returns [`DataMapChunk`] (pointing to the datamap)
-    async fn file_upload(
+    /// Streams the file from disk, encrypting and uploading its chunks in batches,
+    /// returns [`DataMapChunk`] (pointing to the datamap)
+    pub(crate) async fn file_upload(
         &self,
         path: PathBuf,
-        wallet: &EvmWallet,
+        payment_option: PaymentOption,
     ) -> Result<DataMapChunk, UploadError> {
         info!("Uploading file: {path:?}");
         #[cfg(feature = "loud")]
         println!("Uploading file: {path:?}");
 
-        let data = tokio::fs::read(path).await?;
-        let data = Bytes::from(data);
-        let addr = self.data_put(data, wallet.into()).await?;
+        let result = self
+            .file_put_streaming(path.clone(), payment_option, false)
+            .await;
+        self.report_file(&path, "upload", &result);
+        let addr = DataMapChunk::from(result?);
         debug!("Uploaded file successfully in the privateAchive: {addr:?}");
         Ok(addr)
     }
+
+    /// Content addresses and sizes of the chunks [`Client::dir_and_archive_upload`] stores for the
+    /// directory, including the ones of the archive, found by self-encrypting it locally without
+    /// uploading anything.
+    pub async fn dir_and_archive_content_addrs(
+        &self,
+        dir_path: PathBuf,
+    ) -> Result<Vec<(XorName, usize)>, UploadError> {
+        let (mut content_addrs, files, dirs_and_symlinks) = dir_file_chunk_addrs(&dir_path).await?;
+
+        let mut archive = PrivateArchive::new();
+        dirs_and_symlinks.add_to_private(&mut archive);
+        for (path, data_map_chunk, metadata) in files {
+            archive.add_file(path, DataMapChunk::from(data_map_chunk), metadata);
+        }
+
+        let (_, archive_chunks) = encrypt(archive.to_bytes()?).map_err(PutError::from)?;
+        content_addrs.extend(
+            archive_chunks
+                .iter()
+                .map(|chunk| (*chunk.name(), chunk.payload_size())),
+        );
+        Ok(content_addrs)
+    }
+}
+
+/// Self-encrypt every file in the directory, like uploading it does, without uploading anything.
+///
+/// Returns the addresses and sizes of the chunks uploading the files stores, except their data map
+/// chunks, and the relative path, data map chunk and metadata of each file.
+pub(crate) async fn dir_file_chunk_addrs(
+    dir_path: &PathBuf,
+) -> Result<
+    (
+        Vec<(XorName, usize)>,
+        Vec<(PathBuf, Chunk, Metadata)>,
+        DirsAndSymlinks,
+    ),
+    UploadError,
+> {
+    let mut content_addrs = vec![];
+    let mut files = vec![];
+    let mut dirs_and_symlinks = DirsAndSymlinks::default();
+    for entry in walkdir::WalkDir::new(dir_path) {
+        let entry = entry?;
+        if dirs_and_symlinks.record(&entry, dir_path) || !entry.file_type().is_file() {
+            continue;
+        }
+
+        let metadata = super::fs_public::metadata_from_entry(&entry);
+        let path = entry.path().to_path_buf();
+        let rel_path = get_relative_file_path_from_abs_file_and_folder_path(&path, dir_path);
+        let (data_map_chunk, chunk_addrs) =
+            tokio::task::spawn_blocking(move || file_chunk_addrs(path))
+                .await
+                .map_err(std::io::Error::other)?
+                .map_err(PutError::from)?;
+        content_addrs.extend(chunk_addrs);
+        files.push((rel_path, data_map_chunk, metadata));
+    }
+    Ok((content_addrs, files, dirs_and_symlinks))
 }
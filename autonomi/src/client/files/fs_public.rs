@@ -6,17 +6,22 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::archive::PrivateArchive;
 use super::archive_public::{ArchiveAddr, PublicArchive};
 use super::fs::*;
-use crate::client::data::DataAddr;
+use crate::client::data::{DataAddr, PutError};
 use crate::client::files::archive::Metadata;
 use crate::client::files::get_relative_file_path_from_abs_file_and_folder_path;
+use crate::client::payment::PaymentOption;
 use crate::client::utils::process_tasks_with_max_concurrency;
-use crate::client::Client;
+use crate::client::{Client, ClientEvent};
 use ant_evm::EvmWallet;
 use ant_networking::time::{Duration, SystemTime};
 use bytes::Bytes;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use xor_name::XorName;
 
 impl Client {
     /// Download file from network to local file system
@@ -25,14 +30,15 @@ impl Client {
         data_addr: DataAddr,
         to_dest: PathBuf,
     ) -> Result<(), DownloadError> {
-        let data = self.data_get_public(data_addr).await?;
-        if let Some(parent) = to_dest.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-            debug!("Created parent directories {parent:?} for {to_dest:?}");
-        }
-        tokio::fs::write(to_dest.clone(), data).await?;
-        debug!("Downloaded file to {to_dest:?} from the network address {data_addr:?}");
-        Ok(())
+        let download = async {
+            let reader = self.data_get_stream_public(data_addr).await?;
+            let written = write_download(reader, &to_dest).await?;
+            debug!("Downloaded file of {written} bytes to {to_dest:?} from the network address {data_addr:?}");
+            Ok(())
+        };
+        let result = download.await;
+        self.report_file(&to_dest, "download", &result);
+        result
     }
 
     /// Download directory from network to local file system
@@ -43,9 +49,14 @@ impl Client {
     ) -> Result<(), DownloadError> {
         let archive = self.archive_get_public(archive_addr).await?;
         debug!("Downloaded archive for the directory from the network at {archive_addr:?}");
-        for (path, addr, _meta) in archive.iter() {
-            self.file_download_public(*addr, to_dest.join(path)).await?;
+        check_dirs_and_symlinks(archive.dirs(), archive.symlinks())?;
+        for (path, addr, meta) in archive.iter() {
+            let dest = download_dest(&to_dest, path)?;
+            self.file_download_public(*addr, dest.clone()).await?;
+            restore_metadata(&dest, meta);
         }
+        create_dirs_and_symlinks(&to_dest, archive.dirs(), archive.symlinks())?;
+        restore_dir_metadata(&to_dest, archive.dirs());
         debug!(
             "All files in the directory downloaded to {:?} from the network address {:?}",
             to_dest.parent(),
@@ -63,35 +74,52 @@ impl Client {
         &self,
         dir_path: PathBuf,
         wallet: &EvmWallet,
+    ) -> Result<PublicArchive, UploadError> {
+        self.dir_upload_public_with_payment(dir_path, wallet.into())
+            .await
+    }
+
+    /// Same as [`Client::dir_upload_public`], paying with the given [`PaymentOption`], e.g. a [`Receipt`](crate::client::payment::Receipt)
+    /// of payments made for the content addresses from [`Client::dir_and_archive_content_addrs_public`].
+    pub async fn dir_upload_public_with_payment(
+        &self,
+        dir_path: PathBuf,
+        payment_option: PaymentOption,
     ) -> Result<PublicArchive, UploadError> {
         info!("Uploading directory: {dir_path:?}");
         let start = tokio::time::Instant::now();
 
         // start upload of files in parallel
+        let payment_option = &payment_option;
         let mut upload_tasks = Vec::new();
+        let mut dirs_and_symlinks = DirsAndSymlinks::default();
         for entry in walkdir::WalkDir::new(dir_path.clone()) {
             let entry = entry?;
-            if !entry.file_type().is_file() {
+            if dirs_and_symlinks.record(&entry, &dir_path) || !entry.file_type().is_file() {
                 continue;
             }
 
             let metadata = metadata_from_entry(&entry);
             let path = entry.path().to_path_buf();
             upload_tasks.push(async move {
-                let file = self.file_upload_public(path.clone(), wallet).await;
+                let file = self
+                    .file_upload_public(path.clone(), payment_option.clone())
+                    .await;
                 (path, metadata, file)
             });
         }
 
         // wait for all files to be uploaded
         let uploads =
-            process_tasks_with_max_concurrency(upload_tasks, *FILE_UPLOAD_BATCH_SIZE).await;
+            process_tasks_with_max_concurrency(upload_tasks, self.policy.file_upload_concurrency)
+                .await;
         info!(
             "Upload of {} files completed in {:?}",
             uploads.len(),
             start.elapsed()
         );
         let mut archive = PublicArchive::new();
+        dirs_and_symlinks.add_to_public(&mut archive);
         for (path, metadata, maybe_file) in uploads.into_iter() {
             let rel_path = get_relative_file_path_from_abs_file_and_folder_path(&path, &dir_path);
 
@@ -123,23 +151,68 @@ impl Client {
     }
 
     /// Upload a file to the network.
-    /// Reads file, splits into chunks, uploads chunks, uploads datamap, returns DataAddr (pointing to the datamap)
-    async fn file_upload_public(
+    /// Streams the file from disk, encrypting and uploading its chunks in batches,
+    /// uploads datamap, returns DataAddr (pointing to the datamap)
+    pub(crate) async fn file_upload_public(
         &self,
         path: PathBuf,
-        wallet: &EvmWallet,
+        payment_option: PaymentOption,
     ) -> Result<DataAddr, UploadError> {
         info!("Uploading file: {path:?}");
         #[cfg(feature = "loud")]
         println!("Uploading file: {path:?}");
 
-        let data = tokio::fs::read(path.clone()).await?;
-        let data = Bytes::from(data);
-        let addr = self.data_put_public(data, wallet.into()).await?;
+        let result = self
+            .file_put_streaming(path.clone(), payment_option, true)
+            .await
+            .map(|data_map_chunk| *data_map_chunk.name());
+        self.report_file(&path, "upload", &result);
+        let addr = result?;
         debug!("File {path:?} uploaded to the network at {addr:?}");
         Ok(addr)
     }
 
+    /// Report the outcome of uploading or downloading the file at `path` in the client events.
+    pub(crate) fn report_file<T, E: std::fmt::Display>(
+        &self,
+        path: &Path,
+        action: &str,
+        result: &Result<T, E>,
+    ) {
+        let event = match result {
+            Ok(_) => ClientEvent::FileCompleted(path.to_path_buf()),
+            Err(err) => ClientEvent::Error(format!("Failed to {action} {path:?}: {err}")),
+        };
+        self.send_event(event);
+    }
+
+    /// Content addresses and sizes of the chunks [`Client::dir_and_archive_upload_public`] stores for the
+    /// directory, including the ones of the archive, found by self-encrypting it locally without
+    /// uploading anything.
+    pub async fn dir_and_archive_content_addrs_public(
+        &self,
+        dir_path: PathBuf,
+    ) -> Result<Vec<(XorName, usize)>, UploadError> {
+        let (mut content_addrs, files, dirs_and_symlinks) = dir_file_chunk_addrs(&dir_path).await?;
+
+        let mut archive = PublicArchive::new();
+        dirs_and_symlinks.add_to_public(&mut archive);
+        for (path, data_map_chunk, metadata) in files {
+            content_addrs.push((*data_map_chunk.name(), data_map_chunk.payload_size()));
+            archive.add_file(path, *data_map_chunk.name(), metadata);
+        }
+
+        let (archive_map, archive_chunks) =
+            crate::self_encryption::encrypt(archive.to_bytes()?).map_err(PutError::from)?;
+        content_addrs.extend(
+            archive_chunks
+                .iter()
+                .map(|chunk| (*chunk.name(), chunk.payload_size())),
+        );
+        content_addrs.push((*archive_map.name(), archive_map.payload_size()));
+        Ok(content_addrs)
+    }
+
     /// Get the cost to upload a file/dir to the network.
     /// quick and dirty implementation, please refactor once files are cleanly implemented
     pub async fn file_cost(&self, path: &PathBuf) -> Result<ant_evm::AttoTokens, FileCostError> {
@@ -173,9 +246,9 @@ impl Client {
             archive.add_file(path, map_xor_name, metadata);
         }
 
-        let root_serialized = rmp_serde::to_vec(&archive)?;
+        let root_serialized = archive.to_bytes()?;
 
-        let archive_cost = self.data_cost(Bytes::from(root_serialized)).await?;
+        let archive_cost = self.data_cost(root_serialized).await?;
 
         total_cost += archive_cost.as_atto();
         debug!("Total cost for the directory: {total_cost:?}");
@@ -197,6 +270,8 @@ pub(crate) fn metadata_from_entry(entry: &walkdir::DirEntry) -> Metadata {
                 created: 0,
                 modified: 0,
                 size: 0,
+                mode: None,
+                xattrs: Default::default(),
             };
         }
     };
@@ -222,9 +297,475 @@ pub(crate) fn metadata_from_entry(entry: &walkdir::DirEntry) -> Metadata {
     let created = unix_time("created", fs_metadata.created());
     let modified = unix_time("modified", fs_metadata.modified());
 
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(fs_metadata.permissions().mode())
+    };
+    #[cfg(not(unix))]
+    let mode = None;
+
     Metadata {
         created,
         modified,
         size: fs_metadata.len(),
+        mode,
+        xattrs: read_xattrs(entry.path()),
+    }
+}
+
+// Read the extended attributes of `path`, without following symlinks. Attributes that cannot be
+// read, or have a name that is not valid UTF-8, are skipped with a warning.
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> BTreeMap<String, Vec<u8>> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(err) => {
+            tracing::warn!(
+                "Failed to list extended attributes of `{}`: {err}",
+                path.display()
+            );
+            return BTreeMap::new();
+        }
+    };
+
+    let mut xattrs = BTreeMap::new();
+    for name in names {
+        let Some(name_str) = name.to_str() else {
+            tracing::warn!(
+                "Skipping non UTF-8 extended attribute {name:?} of `{}`",
+                path.display()
+            );
+            continue;
+        };
+        match xattr::get(path, &name) {
+            Ok(Some(value)) => {
+                xattrs.insert(name_str.to_string(), value);
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!(
+                "Failed to read extended attribute {name_str} of `{}`: {err}",
+                path.display()
+            ),
+        }
+    }
+    xattrs
+}
+
+#[cfg(not(unix))]
+fn read_xattrs(_path: &Path) -> BTreeMap<String, Vec<u8>> {
+    BTreeMap::new()
+}
+
+/// Write a file being downloaded to `to_dest`, returning the number of bytes written.
+/// The download is written to a temporary file next to `to_dest` and only moved into place once
+/// complete, so a failed download leaves nothing behind.
+pub(crate) async fn write_download(
+    mut reader: impl AsyncRead + Unpin,
+    to_dest: &Path,
+) -> Result<u64, DownloadError> {
+    if let Some(parent) = to_dest.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+        debug!("Created parent directories {parent:?} for {to_dest:?}");
+    }
+    let tmp_path = to_dest.with_extension(format!("{:x}.tmp", rand::random::<u64>()));
+    let write = async {
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        let written = tokio::io::copy(&mut reader, &mut file).await?;
+        file.flush().await?;
+        drop(file);
+        tokio::fs::rename(&tmp_path, to_dest).await?;
+        Ok(written)
+    };
+    let result = write.await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Directories and symlinks found while walking a directory, with their relative archive paths.
+#[derive(Debug, Default)]
+pub(crate) struct DirsAndSymlinks {
+    pub(crate) dirs: Vec<(PathBuf, Metadata)>,
+    pub(crate) symlinks: Vec<(PathBuf, PathBuf, Metadata)>,
+}
+
+impl DirsAndSymlinks {
+    /// Record `entry` if it is a directory or a symlink (which are never followed).
+    /// Returns whether it was one, in which case there is no file content to upload.
+    pub(crate) fn record(&mut self, entry: &walkdir::DirEntry, dir_path: &Path) -> bool {
+        let file_type = entry.file_type();
+        if !file_type.is_dir() && !file_type.is_symlink() {
+            return false;
+        }
+
+        let rel_path = get_relative_file_path_from_abs_file_and_folder_path(entry.path(), dir_path);
+        let metadata = metadata_from_entry(entry);
+        if file_type.is_dir() {
+            self.dirs.push((rel_path, metadata));
+            return true;
+        }
+
+        match std::fs::read_link(entry.path()) {
+            Ok(target) => self.symlinks.push((rel_path, target, metadata)),
+            Err(err) => tracing::warn!(
+                "Failed to read symlink `{}`, skipping it: {err}",
+                entry.path().display()
+            ),
+        }
+        true
+    }
+
+    pub(crate) fn add_to_public(self, archive: &mut PublicArchive) {
+        for (path, meta) in self.dirs {
+            archive.add_dir(path, meta);
+        }
+        for (path, target, meta) in self.symlinks {
+            archive.add_symlink(path, target, meta);
+        }
+    }
+
+    pub(crate) fn add_to_private(self, archive: &mut PrivateArchive) {
+        for (path, meta) in self.dirs {
+            archive.add_dir(path, meta);
+        }
+        for (path, target, meta) in self.symlinks {
+            archive.add_symlink(path, target, meta);
+        }
+    }
+}
+
+/// Where to write the file at `path` in an archive under `to_dest`.
+///
+/// Archives are untrusted, so paths that lead outside `to_dest`, or go through an existing
+/// symlink that could, are refused.
+pub fn download_dest(to_dest: &Path, path: &Path) -> Result<PathBuf, UnsafePathError> {
+    let mut dest = to_dest.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => dest.push(name),
+            Component::CurDir => continue,
+            _ => return Err(UnsafePathError::Path(path.to_path_buf())),
+        }
+        if dest
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            warn!("Refusing to write {path:?} through the symlink {dest:?}");
+            return Err(UnsafePathError::Path(path.to_path_buf()));
+        }
+    }
+    Ok(dest)
+}
+
+/// Check that the archived directories and symlinks stay within the download destination,
+/// before anything is written.
+pub fn check_dirs_and_symlinks<'a>(
+    dirs: impl Iterator<Item = (&'a PathBuf, &'a Metadata)>,
+    symlinks: impl Iterator<Item = (&'a PathBuf, &'a PathBuf, &'a Metadata)>,
+) -> Result<(), UnsafePathError> {
+    let is_relative = |path: &Path| {
+        path.components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    };
+    for (path, _meta) in dirs {
+        if !is_relative(path) {
+            return Err(UnsafePathError::Path(path.clone()));
+        }
+    }
+    for (path, target, _meta) in symlinks {
+        if !is_relative(path) || path.file_name().is_none() {
+            return Err(UnsafePathError::Path(path.clone()));
+        }
+        if !is_symlink_target_inside(path, target) {
+            warn!("Refusing to create the symlink {path:?} pointing outside of the destination, to {target:?}");
+            return Err(UnsafePathError::Symlink(path.clone(), target.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the symlink at `path` in an archive points within the archive.
+///
+/// The target must be relative, and can only go up with leading `..`, not more than `path` is
+/// deep. Going up after going down could go through another symlink, so it is not allowed.
+fn is_symlink_target_inside(path: &Path, target: &Path) -> bool {
+    let mut depth = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .count()
+        })
+        .unwrap_or(0);
+    let mut going_down = false;
+    for component in target.components() {
+        match component {
+            Component::ParentDir if !going_down && depth > 0 => depth -= 1,
+            Component::Normal(_) => going_down = true,
+            Component::CurDir => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Create the archived directories and symlinks under `to_dest`, once the files are written,
+/// so that no file is written through an archived symlink. Existing symlinks are replaced.
+pub fn create_dirs_and_symlinks<'a>(
+    to_dest: &Path,
+    dirs: impl Iterator<Item = (&'a PathBuf, &'a Metadata)>,
+    symlinks: impl Iterator<Item = (&'a PathBuf, &'a PathBuf, &'a Metadata)>,
+) -> Result<(), std::io::Error> {
+    let dirs: Vec<_> = dirs.collect();
+    let symlinks: Vec<_> = symlinks.collect();
+    check_dirs_and_symlinks(dirs.iter().copied(), symlinks.iter().copied())
+        .map_err(std::io::Error::other)?;
+
+    for (path, _meta) in dirs {
+        std::fs::create_dir_all(download_dest(to_dest, path).map_err(std::io::Error::other)?)?;
+    }
+    for (path, target, meta) in symlinks {
+        // the symlink itself may exist already, and is replaced
+        let name = path.file_name().unwrap_or_default();
+        let dest = download_dest(to_dest, path.parent().unwrap_or(Path::new("")))
+            .map_err(std::io::Error::other)?
+            .join(name);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if dest
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            std::fs::remove_file(&dest)?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target, &dest)?;
+        #[cfg(not(unix))]
+        tracing::warn!(
+            "Symlinks are not supported on this platform, skipping {dest:?} -> {target:?}"
+        );
+        apply_xattrs(&dest, meta);
+    }
+    Ok(())
+}
+
+/// Apply the archived metadata to the directories under `to_dest`, once their content is in
+/// place. Children come before their parents, so that restrictive modes do not get in the way.
+pub fn restore_dir_metadata<'a>(
+    to_dest: &Path,
+    dirs: impl DoubleEndedIterator<Item = (&'a PathBuf, &'a Metadata)>,
+) {
+    for (path, meta) in dirs.rev() {
+        restore_metadata(&to_dest.join(path), meta);
+    }
+}
+
+/// Apply the archived extended attributes, modification time and mode to a downloaded file or
+/// directory. This is best effort: failures are logged, not returned.
+pub fn restore_metadata(path: &Path, meta: &Metadata) {
+    apply_xattrs(path, meta);
+
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(meta.modified);
+    // opened for writing, as setting the time of a read-only handle fails on Windows
+    if let Err(err) = std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(modified))
+    {
+        tracing::warn!("Failed to set modification time of {path:?}: {err}");
+    }
+
+    #[cfg(unix)]
+    if let Some(mode) = meta.mode {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(err) = std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)) {
+            tracing::warn!("Failed to set mode {mode:o} of {path:?}: {err}");
+        }
+    }
+}
+
+fn apply_xattrs(path: &Path, meta: &Metadata) {
+    #[cfg(unix)]
+    for (name, value) in &meta.xattrs {
+        if let Err(err) = xattr::set(path, name, value) {
+            tracing::warn!("Failed to set extended attribute {name} of {path:?}: {err}");
+        }
+    }
+    #[cfg(not(unix))]
+    if !meta.xattrs.is_empty() {
+        tracing::warn!(
+            "Extended attributes are not supported on this platform, skipping them for {path:?}"
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn dirs_symlinks_and_modes_survive_a_round_trip() -> eyre::Result<()> {
+        let src = tempfile::tempdir()?;
+        let dir = src.path().join("backup");
+        std::fs::create_dir_all(dir.join("empty"))?;
+        std::fs::write(dir.join("script.sh"), "#!/bin/sh")?;
+        std::fs::set_permissions(
+            dir.join("script.sh"),
+            std::fs::Permissions::from_mode(0o750),
+        )?;
+        std::os::unix::fs::symlink("script.sh", dir.join("run"))?;
+
+        let mut archive = PublicArchive::new();
+        let mut dirs_and_symlinks = DirsAndSymlinks::default();
+        let mut files = vec![];
+        for entry in walkdir::WalkDir::new(&dir) {
+            let entry = entry?;
+            if !dirs_and_symlinks.record(&entry, &dir) {
+                files.push((entry.path().to_path_buf(), metadata_from_entry(&entry)));
+            }
+        }
+        dirs_and_symlinks.add_to_public(&mut archive);
+        let archive = PublicArchive::from_bytes(archive.to_bytes()?)?;
+
+        let dirs: Vec<_> = archive.dirs().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            dirs,
+            vec![PathBuf::from("backup"), PathBuf::from("backup/empty")]
+        );
+        let symlinks: Vec<_> = archive
+            .symlinks()
+            .map(|(path, target, _)| (path.clone(), target.clone()))
+            .collect();
+        assert_eq!(
+            symlinks,
+            vec![(PathBuf::from("backup/run"), PathBuf::from("script.sh"))]
+        );
+
+        // restore without the network: copy the file content over, as a download would
+        let dest = tempfile::tempdir()?;
+        create_dirs_and_symlinks(dest.path(), archive.dirs(), archive.symlinks())?;
+        for (path, meta) in files {
+            let rel_path = get_relative_file_path_from_abs_file_and_folder_path(&path, &dir);
+            let restored = dest.path().join(rel_path);
+            let _ = std::fs::copy(&path, &restored)?;
+            std::fs::set_permissions(&restored, std::fs::Permissions::from_mode(0o600))?;
+            restore_metadata(&restored, &meta);
+        }
+        restore_dir_metadata(dest.path(), archive.dirs());
+
+        let restored = dest.path().join("backup");
+        assert!(restored.join("empty").is_dir());
+        assert_eq!(
+            std::fs::read_link(restored.join("run"))?,
+            PathBuf::from("script.sh")
+        );
+        let script = std::fs::metadata(restored.join("script.sh"))?;
+        assert_eq!(script.permissions().mode() & 0o777, 0o750);
+        let secs = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+        };
+        assert_eq!(
+            secs(script.modified()?)?,
+            secs(std::fs::metadata(dir.join("script.sh"))?.modified()?)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn malicious_archive_cannot_write_outside_the_destination() -> eyre::Result<()> {
+        let root = tempfile::tempdir()?;
+        let outside = root.path().join("outside");
+        std::fs::create_dir_all(&outside)?;
+        let to_dest = root.path().join("dest");
+        std::fs::create_dir_all(&to_dest)?;
+        let meta = Metadata::new_with_size(0);
+
+        // paths leaving the destination
+        for path in ["../outside/file", "/etc/passwd", "a/../../outside/file"] {
+            assert!(matches!(
+                download_dest(&to_dest, Path::new(path)),
+                Err(UnsafePathError::Path(_))
+            ));
+        }
+
+        // an archive holding `d -> <outside>` and `d/authorized_keys`: the file is written
+        // first, and the symlink is refused
+        let mut archive = PublicArchive::new();
+        archive.add_symlink(PathBuf::from("d"), outside.clone(), meta.clone());
+        let file = download_dest(&to_dest, Path::new("d/authorized_keys"))?;
+        assert_eq!(file, to_dest.join("d/authorized_keys"));
+        std::fs::create_dir_all(to_dest.join("d"))?;
+        std::fs::write(&file, "key")?;
+        assert!(matches!(
+            check_dirs_and_symlinks(archive.dirs(), archive.symlinks()),
+            Err(UnsafePathError::Symlink(..))
+        ));
+        assert!(create_dirs_and_symlinks(&to_dest, archive.dirs(), archive.symlinks()).is_err());
+        assert_eq!(std::fs::read_dir(&outside)?.count(), 0);
+
+        // relative targets leaving the destination, directly or through another symlink
+        for (path, target) in [
+            ("d", "../outside"),
+            ("a/b", "../../outside"),
+            ("m", "x/l/.."),
+            ("a/b", "../a/../.."),
+        ] {
+            let mut archive = PublicArchive::new();
+            archive.add_symlink(PathBuf::from(path), PathBuf::from(target), meta.clone());
+            assert!(
+                matches!(
+                    check_dirs_and_symlinks(archive.dirs(), archive.symlinks()),
+                    Err(UnsafePathError::Symlink(..))
+                ),
+                "{path} -> {target} is refused"
+            );
+        }
+
+        // an existing symlink is never written through
+        std::os::unix::fs::symlink(&outside, to_dest.join("link"))?;
+        assert!(matches!(
+            download_dest(&to_dest, Path::new("link/authorized_keys")),
+            Err(UnsafePathError::Path(_))
+        ));
+
+        // targets within the destination are fine
+        let mut archive = PublicArchive::new();
+        archive.add_symlink(
+            PathBuf::from("a/b/run"),
+            PathBuf::from("../../d/authorized_keys"),
+            meta.clone(),
+        );
+        archive.add_symlink(PathBuf::from("up"), PathBuf::from("."), meta);
+        create_dirs_and_symlinks(&to_dest, archive.dirs(), archive.symlinks())?;
+        assert_eq!(std::fs::read_to_string(to_dest.join("a/b/run"))?, "key");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_download_leaves_no_file() -> eyre::Result<()> {
+        let dest = tempfile::tempdir()?;
+        let to_dest = dest.path().join("file.txt");
+
+        let failing = tokio_util::io::StreamReader::new(futures::stream::iter(vec![
+            Ok(Bytes::from_static(b"first part")),
+            Err(std::io::Error::other("network failure")),
+        ]));
+        assert!(write_download(failing, &to_dest).await.is_err());
+        assert_eq!(std::fs::read_dir(dest.path())?.count(), 0);
+
+        let reader =
+            tokio_util::io::StreamReader::new(futures::stream::iter(vec![
+                Ok::<_, std::io::Error>(Bytes::from_static(b"whole file")),
+            ]));
+        assert_eq!(write_download(reader, &to_dest).await?, 10);
+        assert_eq!(std::fs::read(&to_dest)?, b"whole file");
+        assert_eq!(std::fs::read_dir(dest.path())?.count(), 1);
+        Ok(())
     }
 }
@@ -2,6 +2,9 @@
 use std::path::{Path, PathBuf};
 
 pub mod archive;
+#[cfg(feature = "vault")]
+#[cfg_attr(docsrs, doc(cfg(feature = "vault")))]
+pub mod archive_mutable;
 pub mod archive_public;
 #[cfg(feature = "fs")]
 #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
@@ -9,6 +12,13 @@ pub mod fs;
 #[cfg(feature = "fs")]
 #[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
 pub mod fs_public;
+pub mod mount;
+#[cfg(feature = "fs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+pub mod sync;
+#[cfg(feature = "fs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+pub mod verify;
 
 #[cfg(feature = "fs")]
 pub(crate) fn get_relative_file_path_from_abs_file_and_folder_path(
@@ -0,0 +1,448 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! A read-only filesystem view of an archive, for FUSE bindings like `ant mount`.
+//!
+//! The [`InodeTable`] is built once from the archive, so looking up and listing entries needs no
+//! network access. Reading a file only fetches the chunks covering the read, see
+//! [`Client::mount_read`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use bytes::Bytes;
+use self_encryption::DataMap;
+
+use super::archive::{Metadata, PrivateArchive};
+use super::archive_public::PublicArchive;
+use crate::client::data::{DataAddr, DataMapChunk, GetError};
+use crate::Client;
+
+/// Number of an entry of an [`InodeTable`].
+pub type Inode = u64;
+
+/// Inode of the archive root directory.
+pub const ROOT_INODE: Inode = 1;
+
+/// Permissions of files of archives without modes.
+const DEFAULT_FILE_PERMISSIONS: u16 = 0o444;
+/// Permissions of directories of archives without modes.
+const DEFAULT_DIR_PERMISSIONS: u16 = 0o555;
+
+/// Where the content of an archived file is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileData {
+    Public(DataAddr),
+    Private(DataMapChunk),
+}
+
+/// The kind of an archive entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InodeKind {
+    File(FileData),
+    Dir,
+    /// A symbolic link to the given target.
+    Symlink(PathBuf),
+}
+
+/// An entry of an [`InodeTable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InodeEntry {
+    pub inode: Inode,
+    /// The directory holding this entry. The root directory is its own parent.
+    pub parent: Inode,
+    pub name: OsString,
+    pub kind: InodeKind,
+    /// The archived metadata. Directories that are only implied by the paths of other entries
+    /// get the times of the first entry that implied them.
+    pub meta: Metadata,
+}
+
+impl InodeEntry {
+    /// Size in bytes: the file size for files, the length of the target for symlinks.
+    pub fn size(&self) -> u64 {
+        match &self.kind {
+            InodeKind::File(_) => self.meta.size,
+            InodeKind::Dir => 0,
+            InodeKind::Symlink(target) => target.as_os_str().len() as u64,
+        }
+    }
+
+    /// Permission bits of the entry, without any write permission as the view is read-only.
+    pub fn permissions(&self) -> u16 {
+        let default = match self.kind {
+            InodeKind::File(_) => DEFAULT_FILE_PERMISSIONS,
+            InodeKind::Dir => DEFAULT_DIR_PERMISSIONS,
+            InodeKind::Symlink(_) => return 0o777,
+        };
+        self.meta
+            .mode
+            .map(|mode| (mode & 0o7777) as u16)
+            .unwrap_or(default)
+            & !0o222
+    }
+}
+
+/// The entries of an archive by inode, with the root directory at [`ROOT_INODE`].
+///
+/// Directories are the explicit ones of the archive and the ones implied by the paths of its
+/// files and symlinks. Entries whose path leads outside the archive, goes through a file, or
+/// repeats the path of an earlier entry are skipped with a warning.
+#[derive(Debug, Clone)]
+pub struct InodeTable {
+    entries: Vec<InodeEntry>,
+    children: HashMap<Inode, BTreeMap<OsString, Inode>>,
+}
+
+impl InodeTable {
+    /// Build the inode table of a public archive.
+    pub fn from_public_archive(archive: &PublicArchive) -> Self {
+        Self::new(
+            archive.dirs(),
+            archive
+                .iter()
+                .map(|(path, addr, meta)| (path, FileData::Public(*addr), meta)),
+            archive.symlinks(),
+        )
+    }
+
+    /// Build the inode table of a private archive.
+    pub fn from_private_archive(archive: &PrivateArchive) -> Self {
+        Self::new(
+            archive.dirs(),
+            archive
+                .iter()
+                .map(|(path, data_map, meta)| (path, FileData::Private(data_map.clone()), meta)),
+            archive.symlinks(),
+        )
+    }
+
+    fn new<'a>(
+        dirs: impl Iterator<Item = (&'a PathBuf, &'a Metadata)>,
+        files: impl Iterator<Item = (&'a PathBuf, FileData, &'a Metadata)>,
+        symlinks: impl Iterator<Item = (&'a PathBuf, &'a PathBuf, &'a Metadata)>,
+    ) -> Self {
+        let root = InodeEntry {
+            inode: ROOT_INODE,
+            parent: ROOT_INODE,
+            name: OsString::new(),
+            kind: InodeKind::Dir,
+            meta: Metadata::new_with_size(0),
+        };
+        let mut table = Self {
+            entries: vec![root],
+            children: HashMap::new(),
+        };
+        // directories first, so that their metadata wins over the one of implied directories
+        for (path, meta) in dirs {
+            table.insert(path, InodeKind::Dir, meta);
+        }
+        for (path, data, meta) in files {
+            table.insert(path, InodeKind::File(data), meta);
+        }
+        for (path, target, meta) in symlinks {
+            table.insert(path, InodeKind::Symlink(target.clone()), meta);
+        }
+        table
+    }
+
+    /// Insert an entry at `path`, with the directories leading to it.
+    fn insert(&mut self, path: &Path, kind: InodeKind, meta: &Metadata) {
+        let mut names = vec![];
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::CurDir => {}
+                _ => {
+                    warn!("Skipping archive entry {path:?}, it leads outside the archive");
+                    return;
+                }
+            }
+        }
+        let Some((name, dirs)) = names.split_last() else {
+            warn!("Skipping archive entry with an empty path");
+            return;
+        };
+
+        let mut parent = ROOT_INODE;
+        for dir in dirs {
+            parent = match self.lookup(parent, dir) {
+                Some(entry) if entry.kind == InodeKind::Dir => entry.inode,
+                Some(_) => {
+                    warn!("Skipping archive entry {path:?}, {dir:?} is not a directory");
+                    return;
+                }
+                None => {
+                    let implied = Metadata {
+                        size: 0,
+                        mode: None,
+                        xattrs: Default::default(),
+                        ..meta.clone()
+                    };
+                    self.push(parent, dir, InodeKind::Dir, implied)
+                }
+            };
+        }
+
+        match self.lookup(parent, name) {
+            Some(entry) if entry.kind == InodeKind::Dir && kind == InodeKind::Dir => {
+                debug!("Archive directory {path:?} is listed twice");
+            }
+            Some(_) => warn!("Skipping archive entry {path:?}, the path is already taken"),
+            None => {
+                self.push(parent, name, kind, meta.clone());
+            }
+        }
+    }
+
+    fn push(&mut self, parent: Inode, name: &OsStr, kind: InodeKind, meta: Metadata) -> Inode {
+        let inode = self.entries.len() as Inode + ROOT_INODE;
+        self.entries.push(InodeEntry {
+            inode,
+            parent,
+            name: name.to_owned(),
+            kind,
+            meta,
+        });
+        self.children
+            .entry(parent)
+            .or_default()
+            .insert(name.to_owned(), inode);
+        inode
+    }
+
+    /// Get an entry by inode.
+    pub fn get(&self, inode: Inode) -> Option<&InodeEntry> {
+        let index = inode.checked_sub(ROOT_INODE)?;
+        self.entries.get(usize::try_from(index).ok()?)
+    }
+
+    /// Find the entry named `name` in the directory `parent`.
+    pub fn lookup(&self, parent: Inode, name: &OsStr) -> Option<&InodeEntry> {
+        let inode = self.children.get(&parent)?.get(name)?;
+        self.get(*inode)
+    }
+
+    /// The entries of the directory `inode`, sorted by name. Empty for anything but a directory.
+    pub fn children(&self, inode: Inode) -> impl Iterator<Item = &InodeEntry> {
+        self.children
+            .get(&inode)
+            .into_iter()
+            .flat_map(|children| children.values())
+            .filter_map(|inode| self.get(*inode))
+    }
+
+    /// Number of entries, the root directory included.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Always false, the table holds at least the root directory.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// An archived file opened for reading, holding its resolved data map so that reads do not
+/// fetch it again.
+#[derive(Debug, Clone)]
+pub struct OpenFile {
+    data_map: DataMap,
+    size: u64,
+}
+
+impl OpenFile {
+    /// Size of the file content in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// The byte range of a file of `file_size` bytes covered by a read of `size` bytes at `offset`,
+/// cut at the end of the file.
+pub fn read_range(offset: u64, size: u32, file_size: u64) -> Range<u64> {
+    let start = offset.min(file_size);
+    let end = offset.saturating_add(size as u64).min(file_size);
+    start..end
+}
+
+impl Client {
+    /// Open an archived file for reading with [`Client::mount_read`].
+    pub async fn mount_open(&self, data: &FileData) -> Result<OpenFile, GetError> {
+        let data_map = match data {
+            FileData::Public(addr) => {
+                let data_map_chunk = self.chunk_get(*addr).await?;
+                self.resolve_data_map(data_map_chunk.value()).await?
+            }
+            FileData::Private(data_map) => self.resolve_data_map(data_map.0.value()).await?,
+        };
+        let size = data_map.file_size() as u64;
+        Ok(OpenFile { data_map, size })
+    }
+
+    /// Read `size` bytes at `offset` of an open file, fetching only the chunks the read covers.
+    /// Returns fewer bytes at the end of the file.
+    pub async fn mount_read(
+        &self,
+        file: &OpenFile,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes, GetError> {
+        let range = read_range(offset, size, file.size);
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        self.fetch_range_from_data_map(&file.data_map, range).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(mode: Option<u32>) -> Metadata {
+        Metadata {
+            created: 1,
+            modified: 2,
+            size: 3,
+            mode,
+            xattrs: Default::default(),
+        }
+    }
+
+    fn public_archive() -> PublicArchive {
+        let mut archive = PublicArchive::new();
+        archive.add_file(
+            PathBuf::from("photos/2024/beach.jpg"),
+            DataAddr::random(&mut rand::thread_rng()),
+            meta(Some(0o100644)),
+        );
+        archive.add_file(
+            PathBuf::from("photos/notes.txt"),
+            DataAddr::random(&mut rand::thread_rng()),
+            meta(None),
+        );
+        archive.add_dir(PathBuf::from("photos/empty"), meta(Some(0o40750)));
+        archive.add_symlink(
+            PathBuf::from("photos/latest.jpg"),
+            PathBuf::from("2024/beach.jpg"),
+            meta(None),
+        );
+        archive
+    }
+
+    fn names<'a>(entries: impl Iterator<Item = &'a InodeEntry>) -> Vec<&'a OsStr> {
+        entries.map(|entry| entry.name.as_os_str()).collect()
+    }
+
+    #[test]
+    fn builds_nested_empty_and_implied_directories() {
+        let table = InodeTable::from_public_archive(&public_archive());
+
+        let photos = table
+            .lookup(ROOT_INODE, OsStr::new("photos"))
+            .expect("implied directory");
+        assert_eq!(photos.kind, InodeKind::Dir);
+        assert_eq!(photos.parent, ROOT_INODE);
+        assert_eq!(
+            names(table.children(photos.inode)),
+            ["2024", "empty", "latest.jpg", "notes.txt"]
+        );
+
+        let year = table
+            .lookup(photos.inode, OsStr::new("2024"))
+            .expect("nested directory");
+        assert_eq!(names(table.children(year.inode)), ["beach.jpg"]);
+
+        let empty = table
+            .lookup(photos.inode, OsStr::new("empty"))
+            .expect("empty directory");
+        assert_eq!(empty.kind, InodeKind::Dir);
+        assert_eq!(table.children(empty.inode).count(), 0);
+
+        let link = table
+            .lookup(photos.inode, OsStr::new("latest.jpg"))
+            .expect("symlink");
+        assert_eq!(
+            link.kind,
+            InodeKind::Symlink(PathBuf::from("2024/beach.jpg"))
+        );
+        assert_eq!(link.size(), "2024/beach.jpg".len() as u64);
+
+        // root, photos, 2024, empty, beach.jpg, notes.txt, latest.jpg
+        assert_eq!(table.len(), 7);
+        for entry in table.children(ROOT_INODE) {
+            assert_eq!(table.get(entry.inode), Some(entry));
+        }
+    }
+
+    #[test]
+    fn permissions_default_and_drop_write_bits() {
+        let table = InodeTable::from_public_archive(&public_archive());
+        let photos = table
+            .lookup(ROOT_INODE, OsStr::new("photos"))
+            .expect("implied directory");
+        let permissions = |name: &str| {
+            table
+                .lookup(photos.inode, OsStr::new(name))
+                .expect("entry")
+                .permissions()
+        };
+
+        assert_eq!(photos.permissions(), 0o555);
+        assert_eq!(permissions("notes.txt"), 0o444);
+        assert_eq!(permissions("empty"), 0o550);
+        let year = table
+            .lookup(photos.inode, OsStr::new("2024"))
+            .expect("nested directory");
+        let beach = table
+            .lookup(year.inode, OsStr::new("beach.jpg"))
+            .expect("file");
+        assert_eq!(beach.permissions(), 0o444);
+        assert_eq!(beach.size(), 3);
+    }
+
+    #[test]
+    fn skips_unsafe_and_conflicting_paths() {
+        let mut archive = PrivateArchive::new();
+        let data_map = DataMapChunk::from(ant_protocol::storage::Chunk::new(Bytes::from("map")));
+        archive.add_file(PathBuf::from("../escape"), data_map.clone(), meta(None));
+        archive.add_file(PathBuf::from("/etc/passwd"), data_map.clone(), meta(None));
+        archive.add_file(PathBuf::from("dir/file"), data_map.clone(), meta(None));
+        archive.add_file(
+            PathBuf::from("dir/file/nested"),
+            data_map.clone(),
+            meta(None),
+        );
+        archive.add_symlink(PathBuf::from("dir/file"), PathBuf::from("x"), meta(None));
+
+        let table = InodeTable::from_private_archive(&archive);
+
+        assert_eq!(names(table.children(ROOT_INODE)), ["dir"]);
+        let dir = table
+            .lookup(ROOT_INODE, OsStr::new("dir"))
+            .expect("implied directory");
+        let file = table.lookup(dir.inode, OsStr::new("file")).expect("file");
+        assert_eq!(file.kind, InodeKind::File(FileData::Private(data_map)));
+        assert_eq!(table.children(file.inode).count(), 0);
+        assert_eq!(table.len(), 3);
+        assert!(table.get(0).is_none());
+        assert!(table.get(4).is_none());
+    }
+
+    #[test]
+    fn read_range_is_cut_at_the_end_of_the_file() {
+        assert_eq!(read_range(0, 10, 100), 0..10);
+        assert_eq!(read_range(95, 10, 100), 95..100);
+        assert!(read_range(100, 10, 100).is_empty());
+        assert!(read_range(200, 10, 100).is_empty());
+        assert_eq!(read_range(u64::MAX, u32::MAX, u64::MAX), u64::MAX..u64::MAX);
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Incremental synchronisation between a local directory and an archive.
+//!
+//! Files are compared by size and modification time first. Only when those are inconclusive is
+//! the local file self-encrypted (without uploading anything) and its data map compared to the
+//! archived one, so unchanged files never cost any network round trip.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use ant_evm::EvmWallet;
+use ant_protocol::storage::Chunk;
+
+use super::archive::{Metadata, PrivateArchive};
+use super::archive_public::PublicArchive;
+use super::fs::{DownloadError, UploadError};
+use super::fs_public::{
+    check_dirs_and_symlinks, create_dirs_and_symlinks, download_dest, metadata_from_entry,
+    restore_dir_metadata, restore_metadata, DirsAndSymlinks,
+};
+use super::get_relative_file_path_from_abs_file_and_folder_path;
+use crate::client::data::DataMapChunk;
+use crate::client::utils::process_tasks_with_max_concurrency;
+use crate::client::Client;
+use crate::self_encryption::file_data_map_chunk;
+
+/// Changes found while syncing a local directory with an archive. Paths are relative archive paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Files only present on the side being synced from.
+    pub added: Vec<PathBuf>,
+    /// Files present on both sides with different content.
+    pub modified: Vec<PathBuf>,
+    /// Files only present on the side being synced to. When uploading these are dropped from
+    /// the archive, when downloading they are left untouched on disk.
+    pub removed: Vec<PathBuf>,
+    /// Files with the same content on both sides.
+    pub unchanged: Vec<PathBuf>,
+}
+
+impl SyncReport {
+    /// Whether the sync found no changes at all.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    fn sort(&mut self) {
+        self.added.sort();
+        self.modified.sort();
+        self.removed.sort();
+        self.unchanged.sort();
+    }
+}
+
+impl Client {
+    /// Sync a local directory into an existing [`PublicArchive`].
+    ///
+    /// Only new and changed files are uploaded, files no longer present locally are dropped and
+    /// unchanged files keep their address. Returns the new archive, which is not uploaded (!), and
+    /// a report of the changes.
+    pub async fn dir_sync_public(
+        &self,
+        dir_path: PathBuf,
+        archive: &PublicArchive,
+        wallet: &EvmWallet,
+    ) -> Result<(PublicArchive, SyncReport), UploadError> {
+        info!("Syncing directory {dir_path:?} into public archive");
+        // Comparing files may self-encrypt them, so keep it off the async runtime.
+        let (plan_dir, archived) = (dir_path.clone(), archive.map().clone());
+        let mut plan = tokio::task::spawn_blocking(move || {
+            plan_upload(&plan_dir, &archived, |addr, data_map_chunk| {
+                addr == data_map_chunk.name()
+            })
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        let dirs_and_symlinks = std::mem::take(&mut plan.dirs_and_symlinks);
+        let (files, report) = plan
+            .execute(self.policy.file_upload_concurrency, |path| {
+                self.file_upload_public(path, wallet.into())
+            })
+            .await?;
+
+        let mut new_archive = PublicArchive::new();
+        dirs_and_symlinks.add_to_public(&mut new_archive);
+        for (path, addr, meta) in files {
+            new_archive.add_file(path, addr, meta);
+        }
+        Ok((new_archive, report))
+    }
+
+    /// Sync a local directory into an existing [`PrivateArchive`].
+    ///
+    /// See [`Client::dir_sync_public`].
+    pub async fn dir_sync(
+        &self,
+        dir_path: PathBuf,
+        archive: &PrivateArchive,
+        wallet: &EvmWallet,
+    ) -> Result<(PrivateArchive, SyncReport), UploadError> {
+        info!("Syncing directory {dir_path:?} into private archive");
+        let (plan_dir, archived) = (dir_path.clone(), archive.map().clone());
+        let mut plan = tokio::task::spawn_blocking(move || {
+            plan_upload(&plan_dir, &archived, |access, data_map_chunk| {
+                *access == DataMapChunk::from(data_map_chunk.clone())
+            })
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        let dirs_and_symlinks = std::mem::take(&mut plan.dirs_and_symlinks);
+        let (files, report) = plan
+            .execute(self.policy.file_upload_concurrency, |path| {
+                self.file_upload(path, wallet.into())
+            })
+            .await?;
+
+        let mut new_archive = PrivateArchive::new();
+        dirs_and_symlinks.add_to_private(&mut new_archive);
+        for (path, access, meta) in files {
+            new_archive.add_file(path, access, meta);
+        }
+        Ok((new_archive, report))
+    }
+
+    /// Sync a [`PublicArchive`] into a local directory, downloading only new and changed files.
+    ///
+    /// Local files that are not in the archive are left in place and listed as removed.
+    pub async fn dir_download_sync_public(
+        &self,
+        archive: &PublicArchive,
+        to_dest: PathBuf,
+    ) -> Result<SyncReport, DownloadError> {
+        info!("Syncing public archive into directory {to_dest:?}");
+        // Comparing files may self-encrypt them, so keep it off the async runtime.
+        let (plan_dest, archived) = (to_dest.clone(), archive.map().clone());
+        let DownloadPlan { download, report } = tokio::task::spawn_blocking(move || {
+            plan_download(&plan_dest, &archived, |addr, data_map_chunk| {
+                addr == data_map_chunk.name()
+            })
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        check_dirs_and_symlinks(archive.dirs(), archive.symlinks())?;
+        for (path, addr, meta) in download {
+            let dest = download_dest(&to_dest, &path)?;
+            self.file_download_public(addr, dest.clone()).await?;
+            restore_metadata(&dest, &meta);
+        }
+        create_dirs_and_symlinks(&to_dest, archive.dirs(), archive.symlinks())?;
+        restore_dir_metadata(&to_dest, archive.dirs());
+        Ok(report)
+    }
+
+    /// Sync a [`PrivateArchive`] into a local directory, downloading only new and changed files.
+    ///
+    /// See [`Client::dir_download_sync_public`].
+    pub async fn dir_download_sync(
+        &self,
+        archive: &PrivateArchive,
+        to_dest: PathBuf,
+    ) -> Result<SyncReport, DownloadError> {
+        info!("Syncing private archive into directory {to_dest:?}");
+        let (plan_dest, archived) = (to_dest.clone(), archive.map().clone());
+        let DownloadPlan { download, report } = tokio::task::spawn_blocking(move || {
+            plan_download(&plan_dest, &archived, |access, data_map_chunk| {
+                *access == DataMapChunk::from(data_map_chunk.clone())
+            })
+        })
+        .await
+        .map_err(std::io::Error::from)??;
+        check_dirs_and_symlinks(archive.dirs(), archive.symlinks())?;
+        for (path, access, meta) in download {
+            let dest = download_dest(&to_dest, &path)?;
+            self.file_download(access, dest.clone()).await?;
+            restore_metadata(&dest, &meta);
+        }
+        create_dirs_and_symlinks(&to_dest, archive.dirs(), archive.symlinks())?;
+        restore_dir_metadata(&to_dest, archive.dirs());
+        Ok(report)
+    }
+}
+
+/// Files of a directory sorted into the ones to keep as archived and the ones to upload.
+struct UploadPlan<A> {
+    /// Relative path, archived address and local metadata of unchanged files.
+    keep: Vec<(PathBuf, A, Metadata)>,
+    /// Absolute path, relative path and local metadata of new and changed files.
+    upload: Vec<(PathBuf, PathBuf, Metadata)>,
+    /// Directories and symlinks, which are always taken as found locally.
+    dirs_and_symlinks: DirsAndSymlinks,
+    report: SyncReport,
+}
+
+impl<A> UploadPlan<A> {
+    /// Upload the new and changed files, `concurrency` at a time, returning the entries of the
+    /// new archive.
+    async fn execute<F, Fut>(
+        self,
+        concurrency: usize,
+        upload_file: F,
+    ) -> Result<(Vec<(PathBuf, A, Metadata)>, SyncReport), UploadError>
+    where
+        F: Fn(PathBuf) -> Fut,
+        Fut: Future<Output = Result<A, UploadError>> + Send,
+        A: Send,
+    {
+        let UploadPlan {
+            mut keep,
+            upload,
+            mut report,
+            ..
+        } = self;
+        info!(
+            "Uploading {} new or changed files, keeping {} unchanged",
+            upload.len(),
+            keep.len()
+        );
+
+        let upload_tasks = upload.into_iter().map(|(path, rel_path, meta)| {
+            let uploading = upload_file(path.clone());
+            async move { (path, rel_path, meta, uploading.await) }
+        });
+        let uploads = process_tasks_with_max_concurrency(upload_tasks, concurrency).await;
+
+        for (path, rel_path, meta, result) in uploads {
+            match result {
+                Ok(addr) => keep.push((rel_path, addr, meta)),
+                Err(err) => {
+                    error!("Failed to upload file: {path:?}: {err:?}");
+                    return Err(err);
+                }
+            }
+        }
+
+        report.sort();
+        Ok((keep, report))
+    }
+}
+
+/// Compare the files under `dir_path` with the `archived` ones.
+/// `same_data_map` tells whether an archived address matches a local file's data map chunk.
+fn plan_upload<A: Clone>(
+    dir_path: &Path,
+    archived: &BTreeMap<PathBuf, (A, Metadata)>,
+    same_data_map: impl Fn(&A, &Chunk) -> bool,
+) -> Result<UploadPlan<A>, walkdir::Error> {
+    let mut plan = UploadPlan {
+        keep: vec![],
+        upload: vec![],
+        dirs_and_symlinks: DirsAndSymlinks::default(),
+        report: SyncReport::default(),
+    };
+    let mut seen = BTreeSet::new();
+
+    for entry in walkdir::WalkDir::new(dir_path) {
+        let entry = entry?;
+        if plan.dirs_and_symlinks.record(&entry, dir_path) || !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path().to_path_buf();
+        let rel_path = get_relative_file_path_from_abs_file_and_folder_path(&path, dir_path);
+        let meta = metadata_from_entry(&entry);
+        let _ = seen.insert(rel_path.clone());
+
+        match archived.get(&rel_path) {
+            Some((addr, archived_meta))
+                if same_content(&path, &meta, addr, archived_meta, &same_data_map) =>
+            {
+                plan.report.unchanged.push(rel_path.clone());
+                plan.keep.push((rel_path, addr.clone(), meta));
+            }
+            Some(_) => {
+                plan.report.modified.push(rel_path.clone());
+                plan.upload.push((path, rel_path, meta));
+            }
+            None => {
+                plan.report.added.push(rel_path.clone());
+                plan.upload.push((path, rel_path, meta));
+            }
+        }
+    }
+
+    plan.report.removed = archived
+        .keys()
+        .filter(|path| !seen.contains(*path))
+        .cloned()
+        .collect();
+    debug!(
+        "Sync plan for {dir_path:?}: {} added, {} modified, {} removed, {} unchanged",
+        plan.report.added.len(),
+        plan.report.modified.len(),
+        plan.report.removed.len(),
+        plan.report.unchanged.len()
+    );
+    Ok(plan)
+}
+
+/// Archived files to download into a local directory.
+struct DownloadPlan<A> {
+    /// Relative path, archived address and archived metadata of new and changed files.
+    download: Vec<(PathBuf, A, Metadata)>,
+    report: SyncReport,
+}
+
+/// Compare the `archived` files with the ones under `to_dest`.
+fn plan_download<A: Clone>(
+    to_dest: &Path,
+    archived: &BTreeMap<PathBuf, (A, Metadata)>,
+    same_data_map: impl Fn(&A, &Chunk) -> bool,
+) -> Result<DownloadPlan<A>, std::io::Error> {
+    let mut local = BTreeMap::new();
+    if to_dest.exists() {
+        for entry in walkdir::WalkDir::new(to_dest) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let rel_path = entry
+                .path()
+                .strip_prefix(to_dest)
+                .unwrap_or(entry.path())
+                .to_path_buf();
+            let _ = local.insert(rel_path, metadata_from_entry(&entry));
+        }
+    }
+
+    let mut to_download = vec![];
+    let mut report = SyncReport::default();
+    for (rel_path, (addr, archived_meta)) in archived {
+        match local.remove(rel_path) {
+            Some(meta)
+                if same_content(
+                    &to_dest.join(rel_path),
+                    &meta,
+                    addr,
+                    archived_meta,
+                    &same_data_map,
+                ) =>
+            {
+                report.unchanged.push(rel_path.clone());
+                continue;
+            }
+            Some(_) => report.modified.push(rel_path.clone()),
+            None => report.added.push(rel_path.clone()),
+        }
+        to_download.push((rel_path.clone(), addr.clone(), archived_meta.clone()));
+    }
+    report.removed = local.into_keys().collect();
+    report.sort();
+
+    debug!(
+        "Download sync plan for {to_dest:?}: {} to download, {} unchanged",
+        to_download.len(),
+        report.unchanged.len()
+    );
+    Ok(DownloadPlan {
+        download: to_download,
+        report,
+    })
+}
+
+/// Whether the local file at `path` has the same content as the archived one.
+fn same_content<A>(
+    path: &Path,
+    meta: &Metadata,
+    addr: &A,
+    archived_meta: &Metadata,
+    same_data_map: impl Fn(&A, &Chunk) -> bool,
+) -> bool {
+    if meta.size != archived_meta.size {
+        return false;
+    }
+    if meta.modified == archived_meta.modified {
+        return true;
+    }
+    match file_data_map_chunk(path.to_path_buf()) {
+        Ok(data_map_chunk) => same_data_map(addr, &data_map_chunk),
+        Err(err) => {
+            warn!("Failed to compute data map of {path:?}, treating it as changed: {err:?}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::data::DataAddr;
+    use std::fs;
+
+    fn same_addr(addr: &DataAddr, data_map_chunk: &Chunk) -> bool {
+        addr == data_map_chunk.name()
+    }
+
+    fn archived(path: &Path) -> eyre::Result<(DataAddr, Metadata)> {
+        let entry = walkdir::WalkDir::new(path)
+            .into_iter()
+            .next()
+            .ok_or(eyre::eyre!("no entry"))??;
+        let data_map_chunk = file_data_map_chunk(path.to_path_buf())?;
+        Ok((*data_map_chunk.name(), metadata_from_entry(&entry)))
+    }
+
+    #[test]
+    fn upload_plan_only_uploads_changes() -> eyre::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().join("backup");
+        fs::create_dir_all(&dir)?;
+        for name in ["same", "touched", "edited", "deleted"] {
+            fs::write(dir.join(name), format!("content of {name}"))?;
+        }
+
+        let mut archive = BTreeMap::new();
+        for name in ["same", "touched", "edited", "deleted"] {
+            let _ = archive.insert(Path::new("backup").join(name), archived(&dir.join(name))?);
+        }
+
+        // same content with a different modification time, same size with other content
+        let (_, touched_meta) = archive
+            .get_mut(&Path::new("backup").join("touched"))
+            .ok_or(eyre::eyre!("missing"))?;
+        touched_meta.modified += 10;
+        let (_, edited_meta) = archive
+            .get_mut(&Path::new("backup").join("edited"))
+            .ok_or(eyre::eyre!("missing"))?;
+        edited_meta.modified += 10;
+        fs::write(dir.join("edited"), "content of EDITED")?;
+        fs::remove_file(dir.join("deleted"))?;
+        fs::write(dir.join("added"), "content of added")?;
+
+        let plan = plan_upload(&dir, &archive, same_addr)?;
+        let mut report = plan.report;
+        report.sort();
+        assert_eq!(report.added, vec![Path::new("backup").join("added")]);
+        assert_eq!(report.modified, vec![Path::new("backup").join("edited")]);
+        assert_eq!(report.removed, vec![Path::new("backup").join("deleted")]);
+        assert_eq!(
+            report.unchanged,
+            vec![
+                Path::new("backup").join("same"),
+                Path::new("backup").join("touched")
+            ]
+        );
+        assert_eq!(plan.upload.len(), 2);
+        assert_eq!(plan.keep.len(), 2);
+
+        // downloading the archive back over the directory only fetches what differs
+        let DownloadPlan { download, report } = plan_download(tmp.path(), &archive, same_addr)?;
+        let mut downloads: Vec<_> = download.into_iter().map(|(path, ..)| path).collect();
+        downloads.sort();
+        assert_eq!(
+            downloads,
+            vec![
+                Path::new("backup").join("deleted"),
+                Path::new("backup").join("edited")
+            ]
+        );
+        assert_eq!(report.removed, vec![Path::new("backup").join("added")]);
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Integrity reports for archives, and repairing them from a local copy.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use ant_evm::Amount;
+use ant_protocol::storage::Chunk;
+
+use super::archive::PrivateArchiveAccess;
+use super::archive_public::ArchiveAddr;
+use crate::client::data::verify::{DataHealth, VerifyMode};
+use crate::client::data::{DataAddr, DataMapChunk, GetError, PutError};
+use crate::client::payment::PaymentOption;
+use crate::client::Client;
+use crate::self_encryption::file_chunks;
+
+/// Result of verifying an archive and all the files in it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveHealth {
+    /// Health of the chunks of the archive itself.
+    pub archive: DataHealth,
+    /// Health of each file, by its path in the archive. Empty if the archive could not be fetched.
+    pub files: BTreeMap<PathBuf, DataHealth>,
+}
+
+impl ArchiveHealth {
+    /// Whether the archive and all of its files are healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.archive.is_healthy() && self.files.values().all(DataHealth::is_healthy)
+    }
+
+    /// The files with missing or unproven chunks.
+    pub fn unhealthy_files(&self) -> impl Iterator<Item = (&PathBuf, &DataHealth)> {
+        self.files.iter().filter(|(_, health)| !health.is_healthy())
+    }
+}
+
+/// What [`Client::dir_repair`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairSummary {
+    /// Number of files whose missing chunks were uploaded again.
+    pub files_repaired: usize,
+    /// Number of chunks paid for and uploaded.
+    pub chunks_uploaded: usize,
+    /// Tokens spent on the payments.
+    pub tokens_spent: Amount,
+    /// Files with missing chunks that have no local copy, or whose local copy differs from the
+    /// one in the archive, so they could not be repaired.
+    pub unrepairable: Vec<PathBuf>,
+}
+
+/// The data map of a file in an archive.
+enum FileDataMap<'a> {
+    /// Kept in the archive, not on the network.
+    Private(&'a DataMapChunk),
+    /// Stored on the network at this address.
+    Public(&'a DataAddr),
+}
+
+impl FileDataMap<'_> {
+    /// Whether `data_map_chunk`, from self-encrypting a local copy, is the data map of this file.
+    fn is_of(&self, data_map_chunk: &Chunk) -> bool {
+        match self {
+            Self::Private(data_map) => DataMapChunk::from(data_map_chunk.clone()) == **data_map,
+            Self::Public(addr) => data_map_chunk.name() == *addr,
+        }
+    }
+}
+
+/// Errors that can occur while repairing an archive.
+#[derive(Debug, thiserror::Error)]
+pub enum RepairError {
+    #[error("Failed to fetch the archive")]
+    GetError(#[from] GetError),
+    #[error("Failed to upload the missing chunks")]
+    PutError(#[from] PutError),
+}
+
+impl Client {
+    /// Check every chunk of a private archive and of the files in it, without downloading them
+    /// to disk. Unlike [`Client::dir_download`], this goes on past missing chunks and reports
+    /// all of them, per file.
+    pub async fn dir_verify(
+        &self,
+        archive_access: PrivateArchiveAccess,
+        mode: VerifyMode,
+    ) -> Result<ArchiveHealth, GetError> {
+        let mut health = ArchiveHealth {
+            archive: self.data_verify(&archive_access, mode).await?,
+            files: BTreeMap::new(),
+        };
+        if !health.archive.missing.is_empty() {
+            warn!("Chunks of the archive are missing, its files cannot be listed");
+            return Ok(health);
+        }
+
+        let archive = self.archive_get(archive_access).await?;
+        for (path, data_map, _) in archive.iter() {
+            let file_health = self.data_verify(data_map, mode).await?;
+            health.files.insert(path.clone(), file_health);
+        }
+        Ok(health)
+    }
+
+    /// Check every chunk of a public archive and of the files in it, see [`Client::dir_verify`].
+    pub async fn dir_verify_public(
+        &self,
+        archive_addr: ArchiveAddr,
+        mode: VerifyMode,
+    ) -> Result<ArchiveHealth, GetError> {
+        let mut health = ArchiveHealth {
+            archive: self.data_verify_public(archive_addr, mode).await?,
+            files: BTreeMap::new(),
+        };
+        if !health.archive.missing.is_empty() {
+            warn!(
+                "Chunks of the archive at {archive_addr:?} are missing, its files cannot be listed"
+            );
+            return Ok(health);
+        }
+
+        let archive = self.archive_get_public(archive_addr).await?;
+        for (path, addr, _) in archive.iter() {
+            let file_health = self.data_verify_public(*addr, mode).await?;
+            health.files.insert(path.clone(), file_health);
+        }
+        Ok(health)
+    }
+
+    /// Upload the chunks that `health` (from [`Client::dir_verify`]) reports missing again, with
+    /// fresh payments, taking them from a local copy of the files.
+    ///
+    /// The local copy of each file is looked up at its path in the archive under `from`, where
+    /// [`Client::dir_download`] would have written it. Copies that differ from the file in the
+    /// archive are not uploaded. The archive itself must still be available.
+    pub async fn dir_repair(
+        &self,
+        archive_access: PrivateArchiveAccess,
+        health: &ArchiveHealth,
+        from: PathBuf,
+        payment_option: PaymentOption,
+    ) -> Result<RepairSummary, RepairError> {
+        let archive = self.archive_get(archive_access).await?;
+        let mut summary = RepairSummary::default();
+        for (path, data_map, _) in archive.iter() {
+            let Some(file_health) = health.files.get(path) else {
+                continue;
+            };
+            self.repair_file(
+                path,
+                &from,
+                file_health,
+                FileDataMap::Private(data_map),
+                payment_option.clone(),
+                &mut summary,
+            )
+            .await?;
+        }
+        info!("Repaired archive: {summary:?}");
+        Ok(summary)
+    }
+
+    /// Repair a public archive from a local copy, see [`Client::dir_repair`].
+    pub async fn dir_repair_public(
+        &self,
+        archive_addr: ArchiveAddr,
+        health: &ArchiveHealth,
+        from: PathBuf,
+        payment_option: PaymentOption,
+    ) -> Result<RepairSummary, RepairError> {
+        let archive = self.archive_get_public(archive_addr).await?;
+        let mut summary = RepairSummary::default();
+        for (path, addr, _) in archive.iter() {
+            let Some(file_health) = health.files.get(path) else {
+                continue;
+            };
+            self.repair_file(
+                path,
+                &from,
+                file_health,
+                FileDataMap::Public(addr),
+                payment_option.clone(),
+                &mut summary,
+            )
+            .await?;
+        }
+        info!("Repaired archive at {archive_addr:?}: {summary:?}");
+        Ok(summary)
+    }
+
+    /// Upload the missing chunks of the file at `path` in the archive from its local copy under
+    /// `from`, if the copy has the same data map as the file in the archive.
+    async fn repair_file(
+        &self,
+        path: &PathBuf,
+        from: &Path,
+        health: &DataHealth,
+        data_map: FileDataMap<'_>,
+        payment_option: PaymentOption,
+        summary: &mut RepairSummary,
+    ) -> Result<(), RepairError> {
+        if health.missing.is_empty() {
+            return Ok(());
+        }
+
+        // Without the data map the missing chunks are unknown, so upload them all: the ones
+        // still stored are not paid for again.
+        let wanted: Option<HashSet<_>> =
+            (!health.data_map_missing).then(|| health.missing.iter().copied().collect());
+        let local_path = from.join(path);
+        let encrypted = tokio::task::spawn_blocking(move || {
+            file_chunks(local_path, wanted.as_ref()).map(|chunks| (chunks, wanted))
+        })
+        .await;
+
+        let ((data_map_chunk, mut chunks), wanted) = match encrypted {
+            Ok(Ok(encrypted)) => encrypted,
+            Ok(Err(err)) => {
+                warn!("Cannot repair {path:?}, failed to encrypt its local copy: {err:?}");
+                summary.unrepairable.push(path.clone());
+                return Ok(());
+            }
+            Err(err) => {
+                error!("Cannot repair {path:?}, encryption task failed: {err:?}");
+                summary.unrepairable.push(path.clone());
+                return Ok(());
+            }
+        };
+        if !data_map.is_of(&data_map_chunk) {
+            warn!("Cannot repair {path:?}, its local copy differs from the one in the archive");
+            summary.unrepairable.push(path.clone());
+            return Ok(());
+        }
+        if matches!(data_map, FileDataMap::Public(_))
+            && wanted.is_none_or(|wanted| wanted.contains(data_map_chunk.name()))
+        {
+            chunks.push(data_map_chunk);
+        }
+
+        info!("Repairing {path:?} by uploading {} chunks", chunks.len());
+        let (paid, _, tokens_spent) = self
+            .pay_and_upload_chunks(&chunks, payment_option, None)
+            .await?;
+        summary.files_repaired += 1;
+        summary.chunks_uploaded += paid;
+        summary.tokens_spent += tokens_spent;
+        Ok(())
+    }
+}
@@ -6,24 +6,27 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+
 use crate::client::data::PayError;
+use crate::client::utils::process_tasks_with_max_concurrency;
 use crate::client::Client;
-use crate::client::ClientEvent;
 use crate::client::UploadSummary;
 
 use ant_evm::Amount;
 use ant_evm::AttoTokens;
 pub use ant_protocol::storage::GraphEntry;
 use ant_protocol::storage::GraphEntryAddress;
-pub use bls::SecretKey;
+pub use bls::{PublicKey, SecretKey};
 
 use ant_evm::{EvmWallet, EvmWalletError};
-use ant_networking::{GetRecordCfg, NetworkError, PutRecordCfg, VerificationKind};
+use ant_networking::{get_graph_entry_from_record, NetworkError, PutRecordCfg, VerificationKind};
 use ant_protocol::{
-    storage::{try_serialize_record, DataTypes, RecordKind, RetryStrategy},
+    storage::{try_serialize_record, DataTypes, RecordKind},
     NetworkAddress,
 };
-use libp2p::kad::{Quorum, Record};
+use libp2p::kad::Record;
 
 use super::data::CostError;
 
@@ -47,13 +50,82 @@ pub enum GraphError {
     AlreadyExists(GraphEntryAddress),
 }
 
+/// The part of the graph reached by walking [`GraphEntry`] links from a root address,
+/// as returned by [`Client::graph_walk_descendants`] and [`Client::graph_walk_ancestors`].
+#[derive(Debug, Clone)]
+pub struct GraphWalk {
+    /// Address the walk started from.
+    pub root: GraphEntryAddress,
+    /// Valid entries found at each visited address. More than one entry means the address is forked.
+    pub entries: BTreeMap<GraphEntryAddress, Vec<GraphEntry>>,
+    /// Addresses linked to from each visited address, in the direction of the walk.
+    pub links: BTreeMap<GraphEntryAddress, BTreeSet<GraphEntryAddress>>,
+    /// Number of hops from the root to each visited address, along the shortest path.
+    pub depths: BTreeMap<GraphEntryAddress, usize>,
+    /// Linked addresses for which no valid entry could be fetched.
+    pub missing: BTreeSet<GraphEntryAddress>,
+}
+
+impl GraphWalk {
+    /// Addresses holding more than one entry, along with the conflicting entries.
+    pub fn forks(&self) -> impl Iterator<Item = (&GraphEntryAddress, &[GraphEntry])> {
+        self.entries
+            .iter()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|(addr, entries)| (addr, entries.as_slice()))
+    }
+
+    /// Whether any of the visited addresses is forked.
+    pub fn is_forked(&self) -> bool {
+        self.forks().next().is_some()
+    }
+
+    /// Visited addresses that link nowhere further: the tips when walking descendants,
+    /// or the genesis entries when walking ancestors.
+    pub fn ends(&self) -> impl Iterator<Item = &GraphEntryAddress> {
+        self.links
+            .iter()
+            .filter(|(_, links)| links.is_empty())
+            .map(|(addr, _)| addr)
+    }
+}
+
+/// Which links of a [`GraphEntry`] a walk follows.
+#[derive(Debug, Clone, Copy)]
+enum WalkDirection {
+    /// Follow the outputs.
+    Descendants,
+    /// Follow the parents.
+    Ancestors,
+}
+
+impl WalkDirection {
+    fn links(self, entry: &GraphEntry) -> Vec<GraphEntryAddress> {
+        let keys: Vec<PublicKey> = match self {
+            WalkDirection::Descendants => entry
+                .outputs
+                .iter()
+                .flatten()
+                .map(|(key, _)| *key)
+                .collect(),
+            WalkDirection::Ancestors => entry.parents.clone(),
+        };
+        keys.into_iter()
+            .map(GraphEntryAddress::from_owner)
+            .collect()
+    }
+}
+
 impl Client {
     /// Fetches a Transaction from the network.
     pub async fn transaction_get(
         &self,
         address: GraphEntryAddress,
     ) -> Result<Vec<GraphEntry>, GraphError> {
-        let transactions = self.network.get_graph_entry(address).await?;
+        let key = NetworkAddress::from_graph_entry_address(address).to_record_key();
+        let get_cfg = self.policy.graph_entries.get.record_cfg();
+        let record = self.network.get_record_from_network(key, &get_cfg).await?;
+        let transactions = get_graph_entry_from_record(&record)?;
 
         Ok(transactions)
     }
@@ -69,7 +141,11 @@ impl Client {
         let xor_name = address.xorname();
         debug!("Paying for transaction at address: {address:?}");
         let (payment_proofs, skipped_payments) = self
-            .pay(std::iter::once(*xor_name), wallet)
+            .pay(
+                DataTypes::GraphEntry,
+                std::iter::once((*xor_name, transaction.size())),
+                wallet,
+            )
             .await
             .inspect_err(|err| {
                 error!("Failed to pay for transaction at address: {address:?} : {err}")
@@ -98,16 +174,11 @@ impl Client {
             publisher: None,
             expires: None,
         };
-        let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::Majority,
-            retry_strategy: Some(RetryStrategy::default()),
-            target_record: None,
-            expected_holders: Default::default(),
-            is_register: false,
-        };
+        let put_policy = self.policy.graph_entries.put;
+        let get_cfg = put_policy.verification.record_cfg();
         let put_cfg = PutRecordCfg {
-            put_quorum: Quorum::All,
-            retry_strategy: None,
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
             use_put_record_to: Some(payees),
             verification: Some((VerificationKind::Crdt, get_cfg)),
         };
@@ -122,28 +193,58 @@ impl Client {
             })?;
 
         // send client event
-        if let Some(channel) = self.client_event_sender.as_ref() {
-            let summary = UploadSummary {
-                records_paid: 1usize.saturating_sub(skipped_payments),
-                records_already_paid: skipped_payments,
-                tokens_spent: price.as_atto(),
-            };
-            if let Err(err) = channel.send(ClientEvent::UploadComplete(summary)).await {
-                error!("Failed to send client event: {err}");
-            }
-        }
+        self.send_upload_summary(UploadSummary {
+            records_paid: 1usize.saturating_sub(skipped_payments),
+            records_already_paid: skipped_payments,
+            tokens_spent: price.as_atto(),
+        });
 
         Ok(())
     }
 
-    /// Get the cost to create a transaction
+    /// Walk the graph from `root` along the entries' outputs, breadth first.
+    ///
+    /// The entries of each level are fetched concurrently. Forks, i.e. addresses holding more
+    /// than one entry, are followed along all their branches and reported in the returned [`GraphWalk`].
+    /// Fails only if the root itself cannot be fetched, unreachable descendants are listed as missing.
+    pub async fn graph_walk_descendants(
+        &self,
+        root: GraphEntryAddress,
+    ) -> Result<GraphWalk, GraphError> {
+        info!("Walking graph descendants of {root:?}");
+        let concurrency = self.policy.chunk_download_concurrency;
+        walk_graph(root, WalkDirection::Descendants, concurrency, |addr| {
+            self.transaction_get(addr)
+        })
+        .await
+    }
+
+    /// Walk the graph from `root` along the entries' parents, breadth first.
+    ///
+    /// See [`Client::graph_walk_descendants`].
+    pub async fn graph_walk_ancestors(
+        &self,
+        root: GraphEntryAddress,
+    ) -> Result<GraphWalk, GraphError> {
+        info!("Walking graph ancestors of {root:?}");
+        let concurrency = self.policy.chunk_download_concurrency;
+        walk_graph(root, WalkDirection::Ancestors, concurrency, |addr| {
+            self.transaction_get(addr)
+        })
+        .await
+    }
+
+    /// Get the cost to create a transaction, quoted for an entry without parents or outputs
     pub async fn transaction_cost(&self, key: SecretKey) -> Result<AttoTokens, GraphError> {
         let pk = key.public_key();
         trace!("Getting cost for transaction of {pk:?}");
 
         let address = GraphEntryAddress::from_owner(pk);
         let xor = *address.xorname();
-        let store_quote = self.get_store_quotes(std::iter::once(xor)).await?;
+        let entry = GraphEntry::new(pk, vec![], [0; 32], None, &key);
+        let store_quote = self
+            .get_store_quotes(DataTypes::GraphEntry, std::iter::once((xor, entry.size())))
+            .await?;
         let total_cost = AttoTokens::from_atto(
             store_quote
                 .0
@@ -155,3 +256,161 @@ impl Client {
         Ok(total_cost)
     }
 }
+
+/// Breadth first walk from `root`, fetching the entries of each level with `fetch`, at most
+/// `concurrency` at a time.
+async fn walk_graph<F, Fut>(
+    root: GraphEntryAddress,
+    direction: WalkDirection,
+    concurrency: usize,
+    fetch: F,
+) -> Result<GraphWalk, GraphError>
+where
+    F: Fn(GraphEntryAddress) -> Fut,
+    Fut: Future<Output = Result<Vec<GraphEntry>, GraphError>> + Send,
+{
+    let mut walk = GraphWalk {
+        root,
+        entries: BTreeMap::new(),
+        links: BTreeMap::new(),
+        depths: BTreeMap::new(),
+        missing: BTreeSet::new(),
+    };
+    let mut level = BTreeSet::from([root]);
+    let mut depth = 0;
+
+    while !level.is_empty() {
+        let tasks = level.iter().map(|addr| {
+            let addr = *addr;
+            let fetching = fetch(addr);
+            async move { (addr, fetching.await) }
+        });
+        let results = process_tasks_with_max_concurrency(tasks, concurrency).await;
+
+        let mut next_level = BTreeSet::new();
+        for (addr, result) in results {
+            let entries = match result {
+                Ok(entries) => valid_entries(addr, entries),
+                Err(err) if addr == root => {
+                    error!("Failed to fetch graph root {root:?}: {err}");
+                    return Err(err);
+                }
+                Err(err) => {
+                    warn!("Failed to fetch graph entry at {addr:?}: {err}");
+                    vec![]
+                }
+            };
+
+            if entries.is_empty() {
+                if addr == root {
+                    error!("No valid graph entry found at root {root:?}");
+                    return Err(GraphError::FailedVerification);
+                }
+                let _ = walk.missing.insert(addr);
+                continue;
+            }
+            if entries.len() > 1 {
+                warn!(
+                    "Graph entry at {addr:?} is forked into {} entries",
+                    entries.len()
+                );
+            }
+
+            let links: BTreeSet<_> = entries
+                .iter()
+                .flat_map(|entry| direction.links(entry))
+                .collect();
+            next_level.extend(links.iter().copied());
+
+            let _ = walk.depths.insert(addr, depth);
+            let _ = walk.entries.insert(addr, entries);
+            let _ = walk.links.insert(addr, links);
+        }
+
+        next_level.retain(|addr| !walk.depths.contains_key(addr) && !walk.missing.contains(addr));
+        level = next_level;
+        depth += 1;
+    }
+
+    debug!(
+        "Walked {} graph entries from {root:?}, {} missing",
+        walk.entries.len(),
+        walk.missing.len()
+    );
+    Ok(walk)
+}
+
+/// Deduplicate the entries fetched at `addr`, dropping the ones that do not belong there.
+fn valid_entries(addr: GraphEntryAddress, entries: Vec<GraphEntry>) -> Vec<GraphEntry> {
+    let valid: BTreeSet<_> = entries
+        .into_iter()
+        .filter(|entry| {
+            let valid = entry.address() == addr && entry.verify();
+            if !valid {
+                warn!("Ignoring invalid graph entry found at {addr:?}");
+            }
+            valid
+        })
+        .collect();
+    valid.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_networking::GetRecordError;
+    use std::collections::HashMap;
+
+    fn entry(key: &SecretKey, parents: &[&SecretKey], outputs: &[&SecretKey]) -> GraphEntry {
+        let parents = parents.iter().map(|sk| sk.public_key()).collect();
+        let outputs = outputs
+            .iter()
+            .map(|sk| (sk.public_key(), [0; 32]))
+            .collect();
+        GraphEntry::new(key.public_key(), parents, [0; 32], Some(outputs), key)
+    }
+
+    fn addr(key: &SecretKey) -> GraphEntryAddress {
+        GraphEntryAddress::from_owner(key.public_key())
+    }
+
+    #[tokio::test]
+    async fn walks_both_directions_and_reports_forks() -> eyre::Result<()> {
+        // genesis -> a -> b (forked) -> {c, lost}
+        let [genesis, a, b, c, lost] = [(); 5].map(|_| SecretKey::random());
+        let mut network = HashMap::new();
+        let _ = network.insert(addr(&genesis), vec![entry(&genesis, &[], &[&a])]);
+        let _ = network.insert(addr(&a), vec![entry(&a, &[&genesis], &[&b])]);
+        let _ = network.insert(
+            addr(&b),
+            vec![entry(&b, &[&a], &[&c]), entry(&b, &[&a], &[&lost])],
+        );
+        let _ = network.insert(addr(&c), vec![entry(&c, &[&b], &[])]);
+
+        let fetch = |address| {
+            let found = network.get(&address).cloned();
+            async move {
+                found.ok_or(GraphError::Network(NetworkError::GetRecordError(
+                    GetRecordError::RecordNotFound,
+                )))
+            }
+        };
+
+        let descendants = walk_graph(addr(&genesis), WalkDirection::Descendants, 4, fetch).await?;
+        assert_eq!(descendants.entries.len(), 4);
+        assert_eq!(descendants.depths[&addr(&c)], 3);
+        assert_eq!(descendants.missing, BTreeSet::from([addr(&lost)]));
+        let forks: Vec<_> = descendants.forks().map(|(address, _)| *address).collect();
+        assert_eq!(forks, vec![addr(&b)]);
+        assert_eq!(descendants.ends().collect::<Vec<_>>(), vec![&addr(&c)]);
+
+        let ancestors = walk_graph(addr(&c), WalkDirection::Ancestors, 4, fetch).await?;
+        assert_eq!(ancestors.entries.len(), 4);
+        assert!(ancestors.missing.is_empty());
+        assert_eq!(ancestors.ends().collect::<Vec<_>>(), vec![&addr(&genesis)]);
+
+        let res = walk_graph(addr(&lost), WalkDirection::Ancestors, 4, fetch).await;
+        assert!(matches!(res, Err(GraphError::Network(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! On-disk journal of in-progress uploads.
+//!
+//! Each journal is keyed by the hash of the source data and records the payments made, down to
+//! the single quotes of partly paid chunks, and the chunks confirmed as stored, so that a failed
+//! upload can be resumed without paying twice.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use xor_name::XorName;
+
+use crate::client::payment::{PartialReceipt, Receipt};
+use crate::Client;
+
+/// Hash of the source data an [`UploadJournal`] belongs to.
+pub type SourceHash = [u8; 32];
+
+/// Errors that can occur while reading or writing upload journals.
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error("IO failure: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to serialize upload journal: {0}")]
+    Serialization(#[from] rmp_serde::encode::Error),
+    #[error("Failed to deserialize upload journal: {0}")]
+    Deserialization(#[from] rmp_serde::decode::Error),
+}
+
+/// Directory holding the upload journals, one file per source hash.
+#[derive(Debug, Clone)]
+pub struct UploadJournalStore {
+    dir: PathBuf,
+    resume: bool,
+}
+
+impl UploadJournalStore {
+    /// Journals are kept in `dir`. If `resume` is false, existing journals are ignored and
+    /// overwritten, otherwise uploads pick up where a previous attempt left off.
+    pub fn new(dir: PathBuf, resume: bool) -> Self {
+        Self { dir, resume }
+    }
+
+    /// Load the journal for `source_hash`, or a fresh one if there is none (or resuming is disabled).
+    pub fn load(&self, source_hash: SourceHash) -> Result<UploadJournal, JournalError> {
+        let path = self.dir.join(hex::encode(source_hash));
+
+        if !self.resume || !path.exists() {
+            return Ok(UploadJournal {
+                path,
+                ..Default::default()
+            });
+        }
+
+        let bytes = std::fs::read(&path)?;
+        let mut journal: UploadJournal = rmp_serde::from_slice(&bytes)?;
+        journal.path = path;
+        debug!(
+            "Resuming upload journal {:?} with {} payments and {} stored chunks",
+            journal.path,
+            journal.receipt.len(),
+            journal.stored.len()
+        );
+        Ok(journal)
+    }
+
+    fn resume_disabled(mut self) -> Self {
+        self.resume = false;
+        self
+    }
+}
+
+/// Progress of a single upload: the chunks it consists of, the payments made for them
+/// and which of them were confirmed as stored on the network.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadJournal {
+    chunks: HashSet<XorName>,
+    receipt: Receipt,
+    stored: HashSet<XorName>,
+    /// Chunks of which only some quotes were paid.
+    #[serde(default)]
+    partial: PartialReceipt,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl UploadJournal {
+    /// Chunk addresses recorded as part of this upload.
+    pub fn chunks(&self) -> &HashSet<XorName> {
+        &self.chunks
+    }
+
+    /// Payments recorded for this upload.
+    pub fn receipt(&self) -> &Receipt {
+        &self.receipt
+    }
+
+    /// Chunks confirmed as stored on the network.
+    pub fn stored(&self) -> &HashSet<XorName> {
+        &self.stored
+    }
+
+    /// Chunks of which only some quotes were paid.
+    pub fn partial_receipt(&self) -> &PartialReceipt {
+        &self.partial
+    }
+
+    /// Split `content_addrs` into the ones that still need paying for, a receipt of the ones
+    /// with a still valid payment, and the ones of which only some quotes were paid, whose
+    /// remaining quotes are still valid. Chunks already confirmed as stored are left out of all.
+    pub fn pending(
+        &mut self,
+        content_addrs: &[XorName],
+    ) -> (Vec<XorName>, Receipt, PartialReceipt) {
+        let mut to_pay = vec![];
+        let mut receipt = HashMap::new();
+        let mut partly_paid = HashSet::new();
+
+        for addr in content_addrs {
+            let _ = self.chunks.insert(*addr);
+
+            if self.stored.contains(addr) {
+                continue;
+            }
+
+            match self.receipt.get(addr) {
+                Some((proof, price)) if !proof.has_expired() => {
+                    let _ = receipt.insert(*addr, (proof.clone(), *price));
+                }
+                _ if self.partial.contains(addr) && !self.partial.has_expired(addr) => {
+                    let _ = partly_paid.insert(*addr);
+                }
+                _ => to_pay.push(*addr),
+            }
+        }
+
+        let partial = self.partial.filter(|addr| partly_paid.contains(addr));
+        (to_pay, receipt, partial)
+    }
+
+    /// Record payments made for chunks of this upload.
+    pub fn record_payments(&mut self, receipt: &Receipt) {
+        for (addr, payment) in receipt {
+            let _ = self.receipt.insert(*addr, payment.clone());
+            let _ = self.partial.remove(addr);
+        }
+    }
+
+    /// Record chunks of this upload of which only some quotes were paid.
+    pub fn record_partial_payments(&mut self, partial: &PartialReceipt) {
+        self.partial.extend(partial.clone());
+    }
+
+    /// Record chunks confirmed as stored on the network.
+    pub fn record_stored(&mut self, content_addrs: impl IntoIterator<Item = XorName>) {
+        self.stored.extend(content_addrs);
+    }
+
+    /// Persist the journal, replacing the previous version atomically.
+    pub fn save(&self) -> Result<(), JournalError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let bytes = rmp_serde::to_vec(self)?;
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Remove the journal from disk once the upload has completed.
+    pub fn remove(&self) -> Result<(), JournalError> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hash of in-memory source data.
+pub fn source_hash_of_bytes(data: &[u8]) -> SourceHash {
+    Sha256::digest(data).into()
+}
+
+/// Hash of a source file, read in blocks to keep memory usage bounded.
+pub fn source_hash_of_file(path: &Path) -> Result<SourceHash, std::io::Error> {
+    let mut hasher = Sha256::new();
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+impl Client {
+    /// Keep a journal of uploads in `dir`, so that failed uploads can be resumed
+    /// without paying again for chunks that were already paid for or stored.
+    ///
+    /// If `resume` is false, existing journals are ignored and overwritten.
+    pub fn enable_upload_journal(&mut self, dir: PathBuf, resume: bool) {
+        debug!("Upload journal enabled at {dir:?}, resume: {resume}");
+        self.upload_journal = Some(UploadJournalStore::new(dir, resume));
+    }
+
+    /// Load the journal for the given source, if journaling is enabled.
+    /// A journal that cannot be read is replaced by a fresh one.
+    pub(crate) fn load_upload_journal(
+        &self,
+        source_hash: impl FnOnce() -> Result<SourceHash, std::io::Error>,
+    ) -> Option<UploadJournal> {
+        let store = self.upload_journal.as_ref()?;
+        let source_hash = source_hash()
+            .inspect_err(|err| error!("Failed to hash upload source for journal: {err:?}"))
+            .ok()?;
+        match store.load(source_hash) {
+            Ok(journal) => Some(journal),
+            Err(err) => {
+                warn!("Failed to load upload journal, starting a fresh one: {err:?}");
+                store
+                    .clone()
+                    .resume_disabled()
+                    .load(source_hash)
+                    .inspect_err(|err| error!("Failed to create upload journal: {err:?}"))
+                    .ok()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::quote::{QuoteForAddress, StoreQuote};
+    use ant_evm::{Amount, AttoTokens, EncodedPeerId, PaymentQuote, ProofOfPayment, TxHash};
+    use libp2p::PeerId;
+    use std::collections::BTreeMap;
+
+    fn proof_for(addr: XorName) -> ProofOfPayment {
+        ProofOfPayment {
+            peer_quotes: vec![(
+                EncodedPeerId::from(PeerId::random()),
+                PaymentQuote::test_dummy(addr),
+            )],
+        }
+    }
+
+    #[test]
+    fn journal_skips_stored_and_reuses_payments() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = UploadJournalStore::new(dir.path().to_path_buf(), true);
+        let source_hash = source_hash_of_bytes(b"some source data");
+
+        let rng = &mut rand::thread_rng();
+        let (stored, paid, unpaid) = (
+            XorName::random(rng),
+            XorName::random(rng),
+            XorName::random(rng),
+        );
+
+        let mut journal = store.load(source_hash)?;
+        let (to_pay, receipt, _) = journal.pending(&[stored, paid, unpaid]);
+        assert_eq!(to_pay, vec![stored, paid, unpaid]);
+        assert!(receipt.is_empty());
+
+        let mut payments = Receipt::new();
+        let _ = payments.insert(stored, (proof_for(stored), AttoTokens::from_u64(1)));
+        let _ = payments.insert(paid, (proof_for(paid), AttoTokens::from_u64(1)));
+        journal.record_payments(&payments);
+        journal.record_stored([stored]);
+        journal.save()?;
+
+        let mut resumed = store.load(source_hash)?;
+        assert_eq!(resumed.chunks().len(), 3);
+        let (to_pay, receipt, _) = resumed.pending(&[stored, paid, unpaid]);
+        assert_eq!(to_pay, vec![unpaid]);
+        assert_eq!(receipt.keys().collect::<Vec<_>>(), vec![&paid]);
+
+        let fresh = UploadJournalStore::new(dir.path().to_path_buf(), false).load(source_hash)?;
+        assert!(fresh.receipt().is_empty());
+
+        resumed.remove()?;
+        assert!(store.load(source_hash)?.stored().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn journal_keeps_partly_paid_chunks_out_of_new_payments() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = UploadJournalStore::new(dir.path().to_path_buf(), true);
+        let source_hash = source_hash_of_bytes(b"partly paid source data");
+
+        let rng = &mut rand::thread_rng();
+        let (partly_paid, other_batch) = (XorName::random(rng), XorName::random(rng));
+        let quotes = StoreQuote(HashMap::from([partly_paid, other_batch].map(|addr| {
+            let quotes = (0..2u8)
+                .map(|i| {
+                    let quote = PaymentQuote {
+                        pub_key: vec![i],
+                        ..PaymentQuote::test_dummy(addr)
+                    };
+                    (PeerId::random(), quote, Amount::from(1))
+                })
+                .collect();
+            (addr, QuoteForAddress(quotes))
+        })));
+        let first_quotes: BTreeMap<_, _> = quotes
+            .0
+            .values()
+            .map(|quote_for_address| (quote_for_address.0[0].1.hash(), TxHash::default()))
+            .collect();
+        let partial = PartialReceipt::from_paid_quotes(&quotes, &first_quotes);
+        assert_eq!(partial.len(), 2);
+
+        let mut journal = store.load(source_hash)?;
+        let _ = journal.pending(&[partly_paid, other_batch]);
+        journal.record_partial_payments(&partial);
+        journal.save()?;
+
+        let mut resumed = store.load(source_hash)?;
+        let (to_pay, receipt, pending_partial) = resumed.pending(&[partly_paid]);
+        assert!(to_pay.is_empty());
+        assert!(receipt.is_empty());
+        assert_eq!(
+            pending_partial.content_addrs().collect::<Vec<_>>(),
+            vec![&partly_paid]
+        );
+        assert_eq!(pending_partial.paid_amount(), Amount::from(1));
+
+        let mut completed = Receipt::new();
+        let _ = completed.insert(
+            partly_paid,
+            (proof_for(partly_paid), AttoTokens::from_u64(2)),
+        );
+        resumed.record_payments(&completed);
+        assert!(!resumed.partial_receipt().contains(&partly_paid));
+        assert!(resumed.partial_receipt().contains(&other_batch));
+        Ok(())
+    }
+}
@@ -10,12 +10,16 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 pub mod address;
+pub mod budget;
+pub mod chunk_cache;
 pub mod payment;
+pub mod policy;
 pub mod quote;
 
 pub mod data;
 pub mod files;
 pub mod graph;
+pub mod journal;
 pub mod pointer;
 
 #[cfg(feature = "external-signer")]
@@ -34,17 +38,23 @@ mod utils;
 
 use ant_bootstrap::{BootstrapCacheConfig, BootstrapCacheStore, PeersArgs};
 pub use ant_evm::Amount;
-use ant_evm::EvmNetwork;
+pub use ant_evm::AttoTokens;
+use ant_evm::{EvmNetwork, TxHash};
 use ant_networking::{interval, multiaddr_is_global, Network, NetworkBuilder, NetworkEvent};
 use ant_protocol::version::IDENTIFY_PROTOCOL_STR;
 use libp2p::{identity::Keypair, Multiaddr};
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
 use tokio::sync::mpsc;
+use xor_name::XorName;
 
-/// Time before considering the connection timed out.
+/// Default time before considering the connection timed out.
 pub const CONNECT_TIMEOUT_SECS: u64 = 10;
 
-const CLIENT_EVENT_CHANNEL_SIZE: usize = 100;
+// Large enough for the progress events of a batch of chunks.
+const CLIENT_EVENT_CHANNEL_SIZE: usize = 1000;
+
+// Room progress events leave in the channel for upload summaries.
+const UPLOAD_SUMMARY_HEADROOM: usize = 100;
 
 // Amount of peers to confirm into our routing table before we consider the client ready.
 pub use ant_protocol::CLOSE_GROUP_SIZE;
@@ -68,6 +78,10 @@ pub struct Client {
     pub(crate) network: Network,
     pub(crate) client_event_sender: Arc<Option<mpsc::Sender<ClientEvent>>>,
     pub(crate) evm_network: EvmNetwork,
+    pub(crate) upload_journal: Option<journal::UploadJournalStore>,
+    pub(crate) cost_budget: Arc<budget::CostBudget>,
+    pub(crate) chunk_cache: Option<Arc<chunk_cache::ChunkCache>>,
+    pub(crate) policy: Arc<policy::ClientPolicy>,
 }
 
 /// Configuration for [`Client::init_with_config`].
@@ -85,6 +99,20 @@ pub struct ClientConfig {
 
     /// EVM network to use for quotations and payments.
     pub evm_network: EvmNetwork,
+
+    /// Limits on what the client spends on payments made with a wallet.
+    pub cost_limits: budget::CostLimits,
+
+    /// Cache downloaded chunks on disk, so fetching them again does not go to the network.
+    ///
+    /// Disabled by default.
+    pub chunk_cache: Option<chunk_cache::ChunkCacheConfig>,
+
+    /// Concurrency, retries, timeouts and quorums used when talking to the network.
+    ///
+    /// Can be changed later with [`Client::set_policy`], or for a single call with
+    /// [`Client::with_policy`].
+    pub policy: policy::ClientPolicy,
 }
 
 impl Default for ClientConfig {
@@ -96,6 +124,9 @@ impl Default for ClientConfig {
             local: false,
             peers: None,
             evm_network: Default::default(),
+            cost_limits: Default::default(),
+            chunk_cache: None,
+            policy: Default::default(),
         }
     }
 }
@@ -155,7 +186,7 @@ impl Client {
         Self::init_with_config(ClientConfig {
             local,
             peers: Some(peers),
-            evm_network: Default::default(),
+            ..Default::default()
         })
         .await
     }
@@ -175,6 +206,12 @@ impl Client {
     /// # }
     /// ```
     pub async fn init_with_config(config: ClientConfig) -> Result<Self, ConnectError> {
+        let chunk_cache = config.chunk_cache.and_then(|cache_config| {
+            chunk_cache::ChunkCache::open(cache_config)
+                .inspect_err(|err| warn!("Failed to open chunk cache, continuing without: {err:?}"))
+                .ok()
+                .map(Arc::new)
+        });
         let (network, event_receiver) = build_client_and_run_swarm(config.local);
 
         let peers_args = PeersArgs {
@@ -200,7 +237,11 @@ impl Client {
 
         // Wait until we have added a few peers to our routing table.
         let (sender, receiver) = futures::channel::oneshot::channel();
-        ant_networking::time::spawn(handle_event_receiver(event_receiver, sender));
+        ant_networking::time::spawn(handle_event_receiver(
+            event_receiver,
+            sender,
+            config.policy.connect_timeout,
+        ));
         receiver.await.expect("sender should not close")?;
         debug!("Enough peers were added to our routing table, initialization complete");
 
@@ -208,6 +249,10 @@ impl Client {
             network,
             client_event_sender: Arc::new(None),
             evm_network: config.evm_network,
+            upload_journal: None,
+            cost_budget: Arc::new(budget::CostBudget::new(config.cost_limits)),
+            chunk_cache,
+            policy: Arc::new(config.policy),
         })
     }
 
@@ -247,8 +292,13 @@ impl Client {
             }
         });
 
+        let policy = policy::ClientPolicy::default();
         let (sender, receiver) = futures::channel::oneshot::channel();
-        ant_networking::time::spawn(handle_event_receiver(event_receiver, sender));
+        ant_networking::time::spawn(handle_event_receiver(
+            event_receiver,
+            sender,
+            policy.connect_timeout,
+        ));
 
         receiver.await.expect("sender should not close")?;
         debug!("Client is connected to the network");
@@ -263,6 +313,10 @@ impl Client {
             network,
             client_event_sender: Arc::new(None),
             evm_network: Default::default(),
+            upload_journal: None,
+            cost_budget: Default::default(),
+            chunk_cache: None,
+            policy: Arc::new(policy),
         })
     }
 
@@ -276,6 +330,40 @@ impl Client {
         client_event_receiver
     }
 
+    /// Send a progress event, dropping it if the receiver falls behind rather than slowing
+    /// down the upload or download it reports on.
+    pub(crate) fn send_event(&self, event: ClientEvent) {
+        if let Some(sender) = self.client_event_sender.as_ref() {
+            if sender.capacity() <= UPLOAD_SUMMARY_HEADROOM {
+                trace!("Dropped client event, the receiver fell behind: {event:?}");
+                return;
+            }
+            if let Err(err) = sender.try_send(event) {
+                trace!("Dropped client event: {err:?}");
+            }
+        }
+    }
+
+    /// Send the summary of a finished upload. It is never dropped, but never holds up the caller
+    /// either: if the receiver fell that far behind, it is sent once there is room again.
+    pub(crate) fn send_upload_summary(&self, summary: UploadSummary) {
+        let Some(sender) = self.client_event_sender.as_ref() else {
+            return;
+        };
+        match sender.try_send(ClientEvent::UploadComplete(summary)) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                let sender = sender.clone();
+                let _handle = ant_networking::time::spawn(async move {
+                    if let Err(err) = sender.send(event).await {
+                        error!("Failed to send client event: {err:?}");
+                    }
+                });
+            }
+            Err(err) => error!("Failed to send client event: {err:?}"),
+        }
+    }
+
     pub fn set_evm_network(&mut self, evm_network: EvmNetwork) {
         self.evm_network = evm_network;
     }
@@ -307,12 +395,13 @@ fn build_client_and_run_swarm(local: bool) -> (Network, mpsc::Receiver<NetworkEv
 async fn handle_event_receiver(
     mut event_receiver: mpsc::Receiver<NetworkEvent>,
     sender: futures::channel::oneshot::Sender<Result<(), ConnectError>>,
+    connect_timeout: Duration,
 ) {
     // We switch this to `None` when we've sent the oneshot 'connect' result.
     let mut sender = Some(sender);
     let mut unsupported_protocols = vec![];
 
-    let mut timeout_timer = interval(Duration::from_secs(CONNECT_TIMEOUT_SECS));
+    let mut timeout_timer = interval(connect_timeout);
     timeout_timer.tick().await;
 
     loop {
@@ -364,9 +453,34 @@ async fn handle_event_receiver(
 }
 
 /// Events that can be broadcasted by the client.
+///
+/// Apart from [`ClientEvent::UploadComplete`], events only report progress: they are dropped
+/// when the receiver falls behind.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ClientEvent {
     UploadComplete(UploadSummary),
+    /// Quotes were gathered for a content address that needs paying for.
+    ChunkQuoted(XorName),
+    /// A payment transaction was sent, paying for this many quotes.
+    PaymentSent {
+        tx: TxHash,
+        quotes: usize,
+    },
+    /// A chunk was stored on the network.
+    ChunkStored(XorName),
+    /// A chunk was fetched, from the network or the chunk cache.
+    ChunkFetched(XorName),
+    /// A file was uploaded from, or downloaded to, this path.
+    FileCompleted(PathBuf),
+    /// Storing some chunks failed, they are retried.
+    RetryScheduled {
+        chunks: usize,
+        attempt: usize,
+        max_attempts: usize,
+    },
+    /// An operation failed. The error is also returned to the caller.
+    Error(String),
 }
 
 /// Summary of an upload operation.
@@ -1,8 +1,12 @@
 use crate::client::data::PayError;
-use crate::client::quote::StoreQuote;
+use crate::client::quote::{QuoteForAddress, StoreQuote};
 use crate::Client;
-use ant_evm::{AttoTokens, EncodedPeerId, EvmWallet, ProofOfPayment};
-use std::collections::HashMap;
+use ant_evm::{
+    Amount, AttoTokens, EncodedPeerId, EvmWallet, PaymentQuote, ProofOfPayment, QuoteHash, TxHash,
+};
+use ant_protocol::storage::DataTypes;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use xor_name::XorName;
 
 use super::utils::AlreadyPaidAddressesCount;
@@ -37,6 +41,216 @@ pub fn receipt_from_store_quotes(quotes: StoreQuote) -> Receipt {
     receipt
 }
 
+/// Receipt for the content addresses of which all quotes were paid in the `paid` transactions.
+/// Quotes with a zero price never need paying for.
+pub fn receipt_from_paid_quotes(quotes: StoreQuote, paid: &BTreeMap<QuoteHash, TxHash>) -> Receipt {
+    let fully_paid = quotes
+        .0
+        .into_iter()
+        .filter(|(_, quote_for_address)| {
+            quote_for_address
+                .0
+                .iter()
+                .all(|(_, quote, price)| price.is_zero() || paid.contains_key(&quote.hash()))
+        })
+        .collect();
+    receipt_from_store_quotes(StoreQuote(fully_paid))
+}
+
+/// Total price of the quotes paid in the `paid` transactions.
+pub(crate) fn paid_amount(quotes: &StoreQuote, paid: &BTreeMap<QuoteHash, TxHash>) -> Amount {
+    quotes
+        .payments()
+        .iter()
+        .filter(|(hash, _, _)| paid.contains_key(hash))
+        .map(|(_, _, price)| *price)
+        .sum()
+}
+
+/// Content addresses of which only some quotes were paid when a payment failed: all of their
+/// quotes, with the transactions paying the ones that went through. Paying the remaining quotes
+/// makes them fully paid for, without paying the others again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartialReceipt {
+    quotes: HashMap<XorName, Vec<(EncodedPeerId, PaymentQuote, Amount)>>,
+    paid: BTreeMap<QuoteHash, TxHash>,
+}
+
+impl PartialReceipt {
+    /// The content addresses of which some but not all quotes were paid in the `paid` transactions.
+    pub fn from_paid_quotes(quotes: &StoreQuote, paid: &BTreeMap<QuoteHash, TxHash>) -> Self {
+        let mut partial = Self::default();
+        for (content_addr, quote_for_address) in &quotes.0 {
+            let (paid_quotes, unpaid_quotes): (Vec<_>, Vec<_>) = quote_for_address
+                .0
+                .iter()
+                .filter(|(_, _, price)| !price.is_zero())
+                .partition(|(_, quote, _)| paid.contains_key(&quote.hash()));
+            if paid_quotes.is_empty() || unpaid_quotes.is_empty() {
+                continue;
+            }
+
+            for (_, quote, _) in &quote_for_address.0 {
+                if let Some(tx) = paid.get(&quote.hash()) {
+                    let _ = partial.paid.insert(quote.hash(), *tx);
+                }
+            }
+            let _ = partial.quotes.insert(
+                *content_addr,
+                quote_for_address
+                    .0
+                    .iter()
+                    .map(|(peer_id, quote, price)| {
+                        (EncodedPeerId::from(*peer_id), quote.clone(), *price)
+                    })
+                    .collect(),
+            );
+        }
+        partial
+    }
+
+    /// Number of partly paid content addresses.
+    pub fn len(&self) -> usize {
+        self.quotes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quotes.is_empty()
+    }
+
+    pub fn contains(&self, content_addr: &XorName) -> bool {
+        self.quotes.contains_key(content_addr)
+    }
+
+    pub fn content_addrs(&self) -> impl Iterator<Item = &XorName> {
+        self.quotes.keys()
+    }
+
+    /// Total price of the quotes paid so far.
+    pub fn paid_amount(&self) -> Amount {
+        self.quotes
+            .values()
+            .flatten()
+            .filter(|(_, quote, _)| self.paid.contains_key(&quote.hash()))
+            .map(|(_, _, price)| *price)
+            .sum()
+    }
+
+    /// Whether any quote of the content address has expired, so that its payment can no longer
+    /// be completed.
+    pub(crate) fn has_expired(&self, content_addr: &XorName) -> bool {
+        self.quotes
+            .get(content_addr)
+            .is_some_and(|quotes| quotes.iter().any(|(_, quote, _)| quote.has_expired()))
+    }
+
+    /// The quotes that still need paying for.
+    pub(crate) fn unpaid_quotes(&self) -> StoreQuote {
+        let unpaid = self
+            .quotes
+            .iter()
+            .map(|(content_addr, quotes)| {
+                let unpaid = quotes
+                    .iter()
+                    .filter(|(_, quote, price)| {
+                        !price.is_zero() && !self.paid.contains_key(&quote.hash())
+                    })
+                    .filter_map(|(peer_id, quote, price)| {
+                        Some((peer_id.to_peer_id().ok()?, quote.clone(), *price))
+                    })
+                    .collect();
+                (*content_addr, QuoteForAddress(unpaid))
+            })
+            .collect();
+        StoreQuote(unpaid)
+    }
+
+    /// Record the transactions paying some of the quotes.
+    pub(crate) fn record_paid(&mut self, paid: &BTreeMap<QuoteHash, TxHash>) {
+        for quote_hash in self
+            .quotes
+            .values()
+            .flatten()
+            .map(|(_, quote, _)| quote.hash())
+        {
+            if let Some(tx) = paid.get(&quote_hash) {
+                let _ = self.paid.insert(quote_hash, *tx);
+            }
+        }
+    }
+
+    /// Remove the content addresses of which all quotes are paid now, and get their receipt.
+    pub(crate) fn take_fully_paid(&mut self) -> Receipt {
+        let fully_paid: Vec<XorName> = self
+            .quotes
+            .iter()
+            .filter(|(_, quotes)| {
+                quotes.iter().all(|(_, quote, price)| {
+                    price.is_zero() || self.paid.contains_key(&quote.hash())
+                })
+            })
+            .map(|(content_addr, _)| *content_addr)
+            .collect();
+
+        let mut receipt = Receipt::new();
+        for content_addr in fully_paid {
+            if let Some((proof, price)) = self.remove(&content_addr) {
+                let _ = receipt.insert(content_addr, (proof, price));
+            }
+        }
+        receipt
+    }
+
+    /// Add the content addresses of `other`, replacing the ones already held.
+    pub(crate) fn extend(&mut self, other: PartialReceipt) {
+        for content_addr in other.quotes.keys() {
+            let _ = self.remove(content_addr);
+        }
+        self.quotes.extend(other.quotes);
+        self.paid.extend(other.paid);
+    }
+
+    /// Only keep the content addresses for which `keep` is true.
+    pub(crate) fn filter(&self, keep: impl Fn(&XorName) -> bool) -> Self {
+        let quotes: HashMap<_, _> = self
+            .quotes
+            .iter()
+            .filter(|(content_addr, _)| keep(content_addr))
+            .map(|(content_addr, quotes)| (*content_addr, quotes.clone()))
+            .collect();
+        let quote_hashes: HashSet<QuoteHash> = quotes
+            .values()
+            .flatten()
+            .map(|(_, quote, _)| quote.hash())
+            .collect();
+        let paid = self
+            .paid
+            .iter()
+            .filter(|(quote_hash, _)| quote_hashes.contains(*quote_hash))
+            .map(|(quote_hash, tx)| (*quote_hash, *tx))
+            .collect();
+        Self { quotes, paid }
+    }
+
+    /// Remove a content address, with the proof of payment holding all of its quotes.
+    pub(crate) fn remove(
+        &mut self,
+        content_addr: &XorName,
+    ) -> Option<(ProofOfPayment, AttoTokens)> {
+        let quotes = self.quotes.remove(content_addr)?;
+        let mut proof_of_payment = ProofOfPayment {
+            peer_quotes: vec![],
+        };
+        let mut price = Amount::ZERO;
+        for (peer_id, quote, quote_price) in quotes {
+            let _ = self.paid.remove(&quote.hash());
+            price += quote_price;
+            proof_of_payment.peer_quotes.push((peer_id, quote));
+        }
+        Some((proof_of_payment, AttoTokens::from_atto(price)))
+    }
+}
+
 /// Payment options for data payments.
 #[derive(Clone)]
 pub enum PaymentOption {
@@ -65,15 +279,125 @@ impl From<Receipt> for PaymentOption {
 impl Client {
     pub(crate) async fn pay_for_content_addrs(
         &self,
-        content_addrs: impl Iterator<Item = XorName> + Clone,
+        data_type: DataTypes,
+        content_addrs: impl Iterator<Item = (XorName, usize)> + Clone,
         payment_option: PaymentOption,
     ) -> Result<(Receipt, AlreadyPaidAddressesCount), PayError> {
         match payment_option {
             PaymentOption::Wallet(wallet) => {
-                let (receipt, skipped) = self.pay(content_addrs, &wallet).await?;
+                let (receipt, skipped) = self.pay(data_type, content_addrs, &wallet).await?;
                 Ok((receipt, skipped))
             }
             PaymentOption::Receipt(receipt) => Ok((receipt, 0)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::quote::QuoteForAddress;
+    use ant_evm::{Amount, PaymentQuote};
+    use libp2p::PeerId;
+
+    #[test]
+    fn partial_receipt_only_holds_fully_paid_addresses() {
+        let rng = &mut rand::thread_rng();
+        let (paid, half_paid, free) = (
+            XorName::random(rng),
+            XorName::random(rng),
+            XorName::random(rng),
+        );
+        let quotes_for = |addr: XorName, price: u64| {
+            QuoteForAddress(
+                (0..2)
+                    .map(|_| {
+                        (
+                            PeerId::random(),
+                            PaymentQuote::test_dummy(addr),
+                            Amount::from(price),
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        let quotes = StoreQuote(HashMap::from([
+            (paid, quotes_for(paid, 1)),
+            (half_paid, quotes_for(half_paid, 1)),
+            (free, quotes_for(free, 0)),
+        ]));
+
+        let mut transactions = BTreeMap::new();
+        for (addr, quote_for_address) in &quotes.0 {
+            for (i, (_, quote, _)) in quote_for_address.0.iter().enumerate() {
+                if *addr == paid || (*addr == half_paid && i == 0) {
+                    let _ = transactions.insert(quote.hash(), TxHash::default());
+                }
+            }
+        }
+
+        let receipt = receipt_from_paid_quotes(quotes, &transactions);
+        let mut addrs: Vec<_> = receipt.keys().copied().collect();
+        addrs.sort();
+        let mut expected = vec![paid, free];
+        expected.sort();
+        assert_eq!(addrs, expected);
+    }
+
+    #[test]
+    fn partial_receipt_only_pays_the_remaining_quotes() {
+        let rng = &mut rand::thread_rng();
+        let (paid, half_paid) = (XorName::random(rng), XorName::random(rng));
+        let quotes_for = |addr: XorName| {
+            QuoteForAddress(
+                (0..3u8)
+                    .map(|i| {
+                        let quote = PaymentQuote {
+                            pub_key: vec![i],
+                            ..PaymentQuote::test_dummy(addr)
+                        };
+                        // the last quote is free
+                        (PeerId::random(), quote, Amount::from(2 - i.min(2)))
+                    })
+                    .collect(),
+            )
+        };
+        let quotes = StoreQuote(HashMap::from([
+            (paid, quotes_for(paid)),
+            (half_paid, quotes_for(half_paid)),
+        ]));
+        let quote_hashes = |addr: &XorName| -> Vec<QuoteHash> {
+            quotes.0[addr]
+                .0
+                .iter()
+                .map(|(_, quote, _)| quote.hash())
+                .collect()
+        };
+
+        let first_tx = TxHash::repeat_byte(1);
+        let mut transactions = BTreeMap::new();
+        for hash in quote_hashes(&paid).into_iter().take(2) {
+            let _ = transactions.insert(hash, first_tx);
+        }
+        let _ = transactions.insert(quote_hashes(&half_paid)[0], first_tx);
+
+        let mut partial = PartialReceipt::from_paid_quotes(&quotes, &transactions);
+        assert_eq!(partial.len(), 1);
+        assert!(partial.contains(&half_paid));
+        assert_eq!(partial.paid_amount(), Amount::from(2));
+
+        // only the unpaid quote with a price is left to pay
+        let unpaid = partial.unpaid_quotes();
+        assert_eq!(unpaid.payments().len(), 1);
+        assert_eq!(unpaid.payments()[0].0, quote_hashes(&half_paid)[1]);
+        assert!(partial.take_fully_paid().is_empty());
+
+        let second_tx = TxHash::repeat_byte(2);
+        partial.record_paid(&BTreeMap::from([(quote_hashes(&half_paid)[1], second_tx)]));
+        let receipt = partial.take_fully_paid();
+        assert!(partial.is_empty());
+        let (proof, price) = &receipt[&half_paid];
+        assert_eq!(proof.peer_quotes.len(), 3);
+        assert_eq!(*price, AttoTokens::from_u64(3));
+    }
+}
@@ -1,17 +1,19 @@
 use crate::client::data::PayError;
 use crate::client::Client;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 
 use ant_evm::{Amount, AttoTokens, EvmWallet, EvmWalletError};
-use ant_networking::{GetRecordCfg, NetworkError, PutRecordCfg, VerificationKind};
+use ant_networking::{GetRecordError, NetworkError, PutRecordCfg, VerificationKind};
 use ant_protocol::{
     storage::{
-        try_serialize_record, DataTypes, Pointer, PointerAddress, RecordKind, RetryStrategy,
+        try_deserialize_record, try_serialize_record, ChunkAddress, DataTypes, Pointer,
+        PointerAddress, PointerTarget, RecordKind,
     },
     NetworkAddress,
 };
 use bls::SecretKey;
-use libp2p::kad::{Quorum, Record};
+use libp2p::kad::Record;
+use std::collections::HashSet;
 
 use super::data::CostError;
 
@@ -33,22 +35,49 @@ pub enum PointerError {
     InvalidQuote,
     #[error("Pointer already exists at this address: {0:?}")]
     PointerAlreadyExists(PointerAddress),
+    #[error("Pointer counter cannot be incremented any further: {0:?}")]
+    CounterOverflow(PointerAddress),
+    #[error("Pointer chain loops back to a pointer it already went through: {0:?}")]
+    Cycle(PointerAddress),
 }
 
 impl Client {
     /// Get a pointer from the network
+    ///
+    /// Queries the close group of the pointer with the quorum of [`ClientPolicy::pointers`], a
+    /// majority by default. If the nodes hold different versions of the pointer, the validly
+    /// signed one with the highest counter is returned.
+    ///
+    /// [`ClientPolicy::pointers`]: crate::client::policy::ClientPolicy::pointers
     pub async fn pointer_get(&self, address: PointerAddress) -> Result<Pointer, PointerError> {
         let key = NetworkAddress::from_pointer_address(address).to_record_key();
-        let record = self.network.get_local_record(&key).await?;
+        debug!("Fetching pointer from network at: {address:?}");
 
-        match record {
-            Some(record) => {
-                let (_, pointer): (Vec<u8>, Pointer) = rmp_serde::from_slice(&record.value)
+        let get_cfg = self.policy.pointers.get.record_cfg();
+
+        let pointers = match self.network.get_record_from_network(key, &get_cfg).await {
+            Ok(record) => {
+                let pointer = try_deserialize_record::<Pointer>(&record)
                     .map_err(|_| PointerError::Serialization)?;
-                Ok(pointer)
+                vec![pointer]
             }
-            None => Err(PointerError::Corrupt),
-        }
+            Err(NetworkError::GetRecordError(GetRecordError::SplitRecord { result_map })) => {
+                debug!("Got multiple pointers for {address:?}");
+                result_map
+                    .values()
+                    .filter_map(|(record, _)| try_deserialize_record::<Pointer>(record).ok())
+                    .collect()
+            }
+            Err(err) => {
+                warn!("Failed to fetch pointer {address:?} from network: {err}");
+                return Err(err)?;
+            }
+        };
+
+        latest_valid_pointer(address, pointers).ok_or_else(|| {
+            error!("No valid pointer found at {address:?}");
+            PointerError::Corrupt
+        })
     }
 
     /// Store a pointer on the network
@@ -63,7 +92,11 @@ impl Client {
         let xor_name = *address.xorname();
         debug!("Paying for pointer at address: {address:?}");
         let (payment_proofs, _skipped_payments) = self
-            .pay(std::iter::once(xor_name), wallet)
+            .pay(
+                DataTypes::Pointer,
+                std::iter::once((xor_name, pointer.size())),
+                wallet,
+            )
             .await
             .inspect_err(|err| {
                 error!("Failed to pay for pointer at address: {address:?} : {err}")
@@ -92,17 +125,12 @@ impl Client {
             expires: None,
         };
 
-        let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::Majority,
-            retry_strategy: Some(RetryStrategy::default()),
-            target_record: None,
-            expected_holders: Default::default(),
-            is_register: false,
-        };
+        let put_policy = self.policy.pointers.put;
+        let get_cfg = put_policy.verification.record_cfg();
 
         let put_cfg = PutRecordCfg {
-            put_quorum: Quorum::All,
-            retry_strategy: None,
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
             verification: Some((VerificationKind::Crdt, get_cfg)),
             use_put_record_to: Some(payees),
         };
@@ -119,14 +147,97 @@ impl Client {
         Ok(())
     }
 
-    /// Calculate the cost of storing a pointer
+    /// Point an existing pointer to a new target
+    ///
+    /// The latest version of the pointer is fetched from the network and re-signed by its owner
+    /// with an incremented counter. Updating a pointer does not require a payment.
+    pub async fn pointer_update(
+        &self,
+        owner: &SecretKey,
+        target: PointerTarget,
+    ) -> Result<(), PointerError> {
+        let owner_pk = owner.public_key();
+        let address = PointerAddress::from_owner(owner_pk);
+        let current = self.pointer_get(address).await?;
+
+        let counter = current
+            .count()
+            .checked_add(1)
+            .ok_or(PointerError::CounterOverflow(address))?;
+        let pointer = Pointer::new(owner_pk, counter, target, owner);
+
+        let record = Record {
+            key: NetworkAddress::from_pointer_address(address).to_record_key(),
+            value: try_serialize_record(&pointer, RecordKind::DataOnly(DataTypes::Pointer))
+                .map_err(|_| PointerError::Serialization)?
+                .to_vec(),
+            publisher: None,
+            expires: None,
+        };
+
+        let put_policy = self.policy.pointer_updates;
+        let get_cfg = put_policy.verification.record_cfg();
+
+        let put_cfg = PutRecordCfg {
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
+            verification: Some((VerificationKind::Crdt, get_cfg)),
+            use_put_record_to: None,
+        };
+
+        debug!("Updating pointer at address {address:?} to counter {counter}");
+        self.network
+            .put_record(record, &put_cfg)
+            .await
+            .inspect_err(|err| {
+                error!("Failed to update pointer {address:?} on the network: {err}")
+            })?;
+
+        Ok(())
+    }
+
+    /// Follow a chain of pointers until it reaches a target that is not a pointer
+    ///
+    /// Returns [`PointerError::Cycle`] if the chain points back to a pointer it already went through.
+    pub async fn pointer_resolve(
+        &self,
+        address: PointerAddress,
+    ) -> Result<PointerTarget, PointerError> {
+        let mut visited = HashSet::new();
+        let mut current = address;
+
+        loop {
+            if !visited.insert(current) {
+                error!("Pointer chain starting at {address:?} loops back to {current:?}");
+                return Err(PointerError::Cycle(current));
+            }
+
+            let pointer = self.pointer_get(current).await?;
+            match pointer.target() {
+                PointerTarget::PointerAddress(next) => {
+                    trace!("Pointer {current:?} points to pointer {next:?}");
+                    current = *next;
+                }
+                target => {
+                    debug!("Resolved pointer {address:?} to {target:?}");
+                    return Ok(target.clone());
+                }
+            }
+        }
+    }
+
+    /// Calculate the cost of storing a pointer, quoted for a pointer to a chunk
     pub async fn pointer_cost(&self, key: SecretKey) -> Result<AttoTokens, PointerError> {
         let pk = key.public_key();
         trace!("Getting cost for pointer of {pk:?}");
 
         let address = PointerAddress::from_owner(pk);
         let xor = *address.xorname();
-        let store_quote = self.get_store_quotes(std::iter::once(xor)).await?;
+        let target = PointerTarget::ChunkAddress(ChunkAddress::new(xor));
+        let pointer = Pointer::new(pk, 0, target, &key);
+        let store_quote = self
+            .get_store_quotes(DataTypes::Pointer, std::iter::once((xor, pointer.size())))
+            .await?;
         let total_cost = AttoTokens::from_atto(
             store_quote
                 .0
@@ -138,3 +249,50 @@ impl Client {
         Ok(total_cost)
     }
 }
+
+/// Pick the validly signed pointer with the highest counter stored at `address`.
+fn latest_valid_pointer(
+    address: PointerAddress,
+    pointers: impl IntoIterator<Item = Pointer>,
+) -> Option<Pointer> {
+    pointers
+        .into_iter()
+        .filter(|pointer| {
+            let valid = pointer.network_address() == address && pointer.verify();
+            if !valid {
+                warn!("Ignoring invalid pointer version found at {address:?}");
+            }
+            valid
+        })
+        .max_by_key(|pointer| pointer.count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ant_protocol::storage::ChunkAddress;
+    use xor_name::XorName;
+
+    #[test]
+    fn latest_valid_pointer_picks_highest_signed_counter() {
+        let owner = SecretKey::random();
+        let address = PointerAddress::from_owner(owner.public_key());
+        let target = || {
+            PointerTarget::ChunkAddress(ChunkAddress::new(XorName::random(&mut rand::thread_rng())))
+        };
+
+        let old = Pointer::new(owner.public_key(), 1, target(), &owner);
+        let latest = Pointer::new(owner.public_key(), 2, target(), &owner);
+        let forged = Pointer::new(owner.public_key(), 3, target(), &SecretKey::random());
+
+        let picked = latest_valid_pointer(address, [old.clone(), forged, latest.clone()]);
+        assert_eq!(picked, Some(latest));
+
+        let other = SecretKey::random();
+        let foreign = Pointer::new(other.public_key(), 4, target(), &other);
+        assert_eq!(
+            latest_valid_pointer(address, [foreign, old.clone()]),
+            Some(old)
+        );
+    }
+}
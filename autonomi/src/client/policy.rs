@@ -0,0 +1,263 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! How a client talks to the network: concurrency, retries, timeouts and quorums.
+//!
+//! The defaults are what the client always used, with the concurrency still read from the
+//! `CHUNK_UPLOAD_BATCH_SIZE`, `CHUNK_DOWNLOAD_BATCH_SIZE`, `FILE_UPLOAD_BATCH_SIZE`,
+//! `MARKET_PRICE_BATCH_SIZE` and `MARKET_PRICE_CONCURRENCY` environment variables when they
+//! are set.
+
+use std::num::NonZero;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ant_networking::GetRecordCfg;
+use ant_protocol::storage::RetryStrategy;
+use libp2p::kad::Quorum;
+
+use crate::client::data::{CHUNK_DOWNLOAD_BATCH_SIZE, CHUNK_UPLOAD_BATCH_SIZE, RETRY_ATTEMPTS};
+use crate::client::quote::{MARKET_PRICE_BATCH_SIZE, MARKET_PRICE_CONCURRENCY};
+use crate::client::CONNECT_TIMEOUT_SECS;
+use crate::Client;
+
+/// How to fetch a record from the network.
+#[derive(Debug, Clone, Copy)]
+pub struct GetPolicy {
+    /// Number of nodes that must return the record.
+    pub quorum: Quorum,
+    /// How often to retry, `None` to try once.
+    pub retry: Option<RetryStrategy>,
+}
+
+impl GetPolicy {
+    pub(crate) fn record_cfg(&self) -> GetRecordCfg {
+        GetRecordCfg {
+            get_quorum: self.quorum,
+            retry_strategy: self.retry,
+            target_record: None,
+            expected_holders: Default::default(),
+            is_register: false,
+        }
+    }
+}
+
+/// How to store a record on the network.
+#[derive(Debug, Clone, Copy)]
+pub struct PutPolicy {
+    /// Number of nodes that must accept the record.
+    pub quorum: Quorum,
+    /// How often to retry, `None` to try once.
+    pub retry: Option<RetryStrategy>,
+    /// How to fetch the record back to verify it was stored.
+    pub verification: GetPolicy,
+}
+
+/// How to fetch and store one type of data.
+#[derive(Debug, Clone, Copy)]
+pub struct DataTypePolicy {
+    pub get: GetPolicy,
+    pub put: PutPolicy,
+}
+
+/// Tunables for how a client talks to the network, see [`ClientConfig`](crate::ClientConfig)
+/// and [`Client::with_policy`].
+#[derive(Debug, Clone)]
+pub struct ClientPolicy {
+    /// Number of chunks uploaded in parallel.
+    pub chunk_upload_concurrency: usize,
+    /// Number of chunks downloaded in parallel.
+    pub chunk_download_concurrency: usize,
+    /// Number of files uploaded in parallel.
+    pub file_upload_concurrency: usize,
+    /// Number of times chunks that failed to upload are retried.
+    pub chunk_upload_retries: usize,
+    /// How long [`Client::init_with_config`] waits for enough peers before timing out.
+    pub connect_timeout: Duration,
+    /// Number of quoting metrics to look up the market price of in a single smart contract call.
+    pub market_price_batch_size: usize,
+    /// Number of market price lookups run in parallel.
+    pub market_price_concurrency: usize,
+    pub chunks: DataTypePolicy,
+    /// Storing a new pointer, which is paid for.
+    pub pointers: DataTypePolicy,
+    /// Storing a new version of an existing pointer.
+    pub pointer_updates: PutPolicy,
+    pub graph_entries: DataTypePolicy,
+    pub registers: DataTypePolicy,
+    pub scratchpads: DataTypePolicy,
+}
+
+impl Default for ClientPolicy {
+    fn default() -> Self {
+        Self {
+            chunk_upload_concurrency: *CHUNK_UPLOAD_BATCH_SIZE,
+            chunk_download_concurrency: *CHUNK_DOWNLOAD_BATCH_SIZE,
+            file_upload_concurrency: default_file_upload_concurrency(),
+            chunk_upload_retries: RETRY_ATTEMPTS,
+            connect_timeout: Duration::from_secs(CONNECT_TIMEOUT_SECS),
+            market_price_batch_size: *MARKET_PRICE_BATCH_SIZE,
+            market_price_concurrency: *MARKET_PRICE_CONCURRENCY,
+            chunks: DataTypePolicy {
+                get: GetPolicy {
+                    quorum: Quorum::One,
+                    retry: None,
+                },
+                put: PutPolicy {
+                    quorum: Quorum::One,
+                    retry: Some(RetryStrategy::Balanced),
+                    verification: GetPolicy {
+                        quorum: Quorum::N(NonZero::new(2).expect("2 is non-zero")),
+                        retry: Some(RetryStrategy::Balanced),
+                    },
+                },
+            },
+            pointers: DataTypePolicy {
+                get: GetPolicy {
+                    quorum: Quorum::Majority,
+                    retry: Some(RetryStrategy::Quick),
+                },
+                put: paid_crdt_put(),
+            },
+            pointer_updates: PutPolicy {
+                quorum: Quorum::Majority,
+                retry: Some(RetryStrategy::Balanced),
+                verification: GetPolicy {
+                    quorum: Quorum::Majority,
+                    retry: None,
+                },
+            },
+            graph_entries: DataTypePolicy {
+                get: GetPolicy {
+                    quorum: Quorum::All,
+                    retry: Some(RetryStrategy::Quick),
+                },
+                put: paid_crdt_put(),
+            },
+            registers: DataTypePolicy {
+                get: GetPolicy {
+                    quorum: Quorum::Majority,
+                    retry: None,
+                },
+                put: paid_crdt_put(),
+            },
+            scratchpads: DataTypePolicy {
+                get: GetPolicy {
+                    quorum: Quorum::Majority,
+                    retry: None,
+                },
+                put: PutPolicy {
+                    quorum: Quorum::Majority,
+                    retry: Some(RetryStrategy::Balanced),
+                    verification: GetPolicy {
+                        quorum: Quorum::Majority,
+                        retry: None,
+                    },
+                },
+            },
+        }
+    }
+}
+
+/// Paid CRDT records are sent to all payees, then fetched back from a majority.
+fn paid_crdt_put() -> PutPolicy {
+    PutPolicy {
+        quorum: Quorum::All,
+        retry: None,
+        verification: GetPolicy {
+            quorum: Quorum::Majority,
+            retry: Some(RetryStrategy::default()),
+        },
+    }
+}
+
+#[cfg(feature = "fs")]
+fn default_file_upload_concurrency() -> usize {
+    *crate::client::files::fs::FILE_UPLOAD_BATCH_SIZE
+}
+
+#[cfg(not(feature = "fs"))]
+fn default_file_upload_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        * 8
+}
+
+impl Client {
+    /// The policy this client talks to the network with.
+    pub fn policy(&self) -> &ClientPolicy {
+        &self.policy
+    }
+
+    /// Replace the policy from [`ClientConfig`](crate::ClientConfig) for this client.
+    pub fn set_policy(&mut self, policy: ClientPolicy) {
+        debug!("Client policy set to {policy:?}");
+        self.policy = Arc::new(policy);
+    }
+
+    /// A clone of this client using `policy`, to tune a single call.
+    ///
+    /// The clone shares the connection, cost limits, caches and events of this client.
+    ///
+    /// ```no_run
+    /// # use autonomi::client::{policy::ClientPolicy, Client};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = Client::init().await?;
+    /// # let addr = todo!();
+    /// let policy = ClientPolicy {
+    ///     chunk_download_concurrency: 64,
+    ///     ..client.policy().clone()
+    /// };
+    /// let data = client.with_policy(policy).data_get_public(addr).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_policy(&self, policy: ClientPolicy) -> Client {
+        let mut client = self.clone();
+        client.set_policy(policy);
+        client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_matches_env_tunables() {
+        let policy = ClientPolicy::default();
+        assert_eq!(policy.chunk_upload_concurrency, *CHUNK_UPLOAD_BATCH_SIZE);
+        assert_eq!(
+            policy.chunk_download_concurrency,
+            *CHUNK_DOWNLOAD_BATCH_SIZE
+        );
+        assert_eq!(policy.chunk_upload_retries, RETRY_ATTEMPTS);
+        assert_eq!(
+            policy.connect_timeout,
+            Duration::from_secs(CONNECT_TIMEOUT_SECS)
+        );
+    }
+
+    #[test]
+    fn get_policy_builds_record_cfg() {
+        let policy = ClientPolicy::default();
+        let cfg = policy.chunks.put.verification.record_cfg();
+        assert_eq!(
+            cfg.get_quorum,
+            Quorum::N(NonZero::new(2).expect("2 is non-zero"))
+        );
+        assert!(matches!(cfg.retry_strategy, Some(RetryStrategy::Balanced)));
+        assert!(!cfg.is_register);
+
+        let cfg = policy.graph_entries.get.record_cfg();
+        assert_eq!(cfg.get_quorum, Quorum::All);
+        assert!(matches!(cfg.retry_strategy, Some(RetryStrategy::Quick)));
+    }
+}
@@ -6,16 +6,47 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{data::CostError, Client};
+use super::{data::CostError, utils::process_tasks_with_max_concurrency, Client};
 use crate::client::rate_limiter::RateLimiter;
 use ant_evm::payment_vault::get_market_price;
 use ant_evm::{Amount, EvmNetwork, PaymentQuote, QuotePayment, QuotingMetrics};
 use ant_networking::{Network, NetworkError};
-use ant_protocol::{storage::ChunkAddress, NetworkAddress, CLOSE_GROUP_SIZE};
+use ant_protocol::{
+    storage::{ChunkAddress, DataTypes},
+    NetworkAddress, CLOSE_GROUP_SIZE,
+};
 use libp2p::PeerId;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::LazyLock;
+use tokio::sync::Mutex;
 use xor_name::XorName;
 
+/// Default number of quoting metrics to look up the market price of in a single smart contract
+/// call, see [`ClientPolicy::market_price_batch_size`](crate::client::policy::ClientPolicy::market_price_batch_size).
+///
+/// Can be overridden by the `MARKET_PRICE_BATCH_SIZE` environment variable.
+pub(crate) static MARKET_PRICE_BATCH_SIZE: LazyLock<usize> = LazyLock::new(|| {
+    let batch_size = std::env::var("MARKET_PRICE_BATCH_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(256);
+    info!("Market price batch size: {}", batch_size);
+    batch_size
+});
+
+/// Default number of market price lookups to run in parallel, see
+/// [`ClientPolicy::market_price_concurrency`](crate::client::policy::ClientPolicy::market_price_concurrency).
+///
+/// Can be overridden by the `MARKET_PRICE_CONCURRENCY` environment variable.
+pub(crate) static MARKET_PRICE_CONCURRENCY: LazyLock<usize> = LazyLock::new(|| {
+    let concurrency = std::env::var("MARKET_PRICE_CONCURRENCY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4);
+    info!("Market price concurrency: {}", concurrency);
+    concurrency
+});
+
 /// A quote for a single address
 pub struct QuoteForAddress(pub(crate) Vec<(PeerId, PaymentQuote, Amount)>);
 
@@ -53,29 +84,45 @@ impl StoreQuote {
 }
 
 impl Client {
+    /// Get quotes to store data of `data_type` at each content address, with the size in bytes
+    /// of the data stored there.
     pub async fn get_store_quotes(
         &self,
-        content_addrs: impl Iterator<Item = XorName>,
+        data_type: DataTypes,
+        content_addrs: impl Iterator<Item = (XorName, usize)>,
     ) -> Result<StoreQuote, CostError> {
         // get all quotes from nodes
         let futures: Vec<_> = content_addrs
             .into_iter()
-            .map(|content_addr| fetch_store_quote_with_retries(&self.network, content_addr))
+            .map(|(content_addr, data_size)| {
+                fetch_store_quote_with_retries(&self.network, content_addr, data_type, data_size)
+            })
             .collect();
 
         let raw_quotes_per_addr = futures::future::try_join_all(futures).await?;
 
+        // ask smart contract for the market price of all distinct quoting metrics at once
+        let quoting_metrics: BTreeSet<QuotingMetrics> = raw_quotes_per_addr
+            .iter()
+            .flat_map(|(_, raw_quotes)| raw_quotes.iter())
+            .map(|(_, quote)| quote.quoting_metrics.clone())
+            .collect();
+        debug!(
+            "fetching market prices for {} distinct quoting metrics of {} content addrs",
+            quoting_metrics.len(),
+            raw_quotes_per_addr.len()
+        );
+        let market_prices = get_market_prices(
+            &self.evm_network,
+            quoting_metrics,
+            self.policy.market_price_batch_size,
+            self.policy.market_price_concurrency,
+        )
+        .await?;
+
         // choose the quotes to pay for each address
         let mut quotes_to_pay_per_addr = HashMap::new();
-
-        let mut rate_limiter = RateLimiter::new();
-
         for (content_addr, raw_quotes) in raw_quotes_per_addr {
-            debug!(
-                "fetching market price for content_addr: {content_addr}, with {} quotes.",
-                raw_quotes.len()
-            );
-
             // FIXME: find better way to deal with paid content addrs and feedback to the user
             // assume that content addr is already paid for and uploaded
             if raw_quotes.is_empty() {
@@ -83,76 +130,129 @@ impl Client {
                 continue;
             }
 
-            // ask smart contract for the market price
-            let quoting_metrics: Vec<QuotingMetrics> = raw_quotes
-                .clone()
-                .iter()
-                .map(|(_, q)| q.quoting_metrics.clone())
-                .collect();
-
-            let all_prices = get_market_price_with_rate_limiter_and_retries(
-                &self.evm_network,
-                &mut rate_limiter,
-                quoting_metrics.clone(),
-            )
-            .await?;
+            let prices =
+                price_quotes(raw_quotes, &market_prices).ok_or(CostError::MissingMarketPrice)?;
+            debug!("market prices for content_addr {content_addr}: {prices:?}");
 
-            debug!("market prices: {all_prices:?}");
-
-            let mut prices: Vec<(PeerId, PaymentQuote, Amount)> = all_prices
-                .into_iter()
-                .zip(raw_quotes.into_iter())
-                .map(|(price, (peer, quote))| (peer, quote, price))
-                .collect();
-
-            // sort by price
-            prices.sort_by(|(_, _, price_a), (_, _, price_b)| price_a.cmp(price_b));
-
-            // we need at least 5 valid quotes to pay for the data
-            const MINIMUM_QUOTES_TO_PAY: usize = 5;
-            match &prices[..] {
-                [first, second, third, fourth, fifth, ..] => {
-                    let (p1, q1, _) = first;
-                    let (p2, q2, _) = second;
-
-                    // don't pay for the cheapest 2 quotes but include them
-                    let first = (*p1, q1.clone(), Amount::ZERO);
-                    let second = (*p2, q2.clone(), Amount::ZERO);
-
-                    // pay for the rest
-                    quotes_to_pay_per_addr.insert(
-                        content_addr,
-                        QuoteForAddress(vec![
-                            first,
-                            second,
-                            third.clone(),
-                            fourth.clone(),
-                            fifth.clone(),
-                        ]),
-                    );
-                }
-                _ => {
-                    return Err(CostError::NotEnoughNodeQuotes(
-                        content_addr,
-                        prices.len(),
-                        MINIMUM_QUOTES_TO_PAY,
-                    ));
-                }
-            }
+            let quote_count = prices.len();
+            let quotes_to_pay = select_quotes_to_pay(prices).ok_or(
+                CostError::NotEnoughNodeQuotes(content_addr, quote_count, MINIMUM_QUOTES_TO_PAY),
+            )?;
+            quotes_to_pay_per_addr.insert(content_addr, quotes_to_pay);
         }
 
         Ok(StoreQuote(quotes_to_pay_per_addr))
     }
 }
 
+/// We need at least 5 valid quotes to pay for the data.
+const MINIMUM_QUOTES_TO_PAY: usize = 5;
+
+/// Attach the market price to each quote, or `None` if the price of any quote is unknown.
+fn price_quotes(
+    raw_quotes: Vec<(PeerId, PaymentQuote)>,
+    market_prices: &HashMap<QuotingMetrics, Amount>,
+) -> Option<Vec<(PeerId, PaymentQuote, Amount)>> {
+    raw_quotes
+        .into_iter()
+        .map(|(peer, quote)| {
+            let price = *market_prices.get(&quote.quoting_metrics)?;
+            Some((peer, quote, price))
+        })
+        .collect()
+}
+
+/// Choose the quotes to pay for, or `None` if there are fewer than [`MINIMUM_QUOTES_TO_PAY`].
+fn select_quotes_to_pay(
+    mut prices: Vec<(PeerId, PaymentQuote, Amount)>,
+) -> Option<QuoteForAddress> {
+    // sort by price
+    prices.sort_by(|(_, _, price_a), (_, _, price_b)| price_a.cmp(price_b));
+
+    match &prices[..] {
+        [first, second, third, fourth, fifth, ..] => {
+            let (p1, q1, _) = first;
+            let (p2, q2, _) = second;
+
+            // don't pay for the cheapest 2 quotes but include them
+            let first = (*p1, q1.clone(), Amount::ZERO);
+            let second = (*p2, q2.clone(), Amount::ZERO);
+
+            // pay for the rest
+            Some(QuoteForAddress(vec![
+                first,
+                second,
+                third.clone(),
+                fourth.clone(),
+                fifth.clone(),
+            ]))
+        }
+        _ => None,
+    }
+}
+
+/// Look up the market price of each of the quoting metrics, `batch_size` metrics per smart
+/// contract call and at most `concurrency` calls at a time. The calls share a rate limiter, so
+/// that a call backing off after being rate limited holds back the others too.
+async fn get_market_prices(
+    evm_network: &EvmNetwork,
+    quoting_metrics: BTreeSet<QuotingMetrics>,
+    batch_size: usize,
+    concurrency: usize,
+) -> Result<HashMap<QuotingMetrics, Amount>, CostError> {
+    let rate_limiter = Mutex::new(RateLimiter::new());
+    let rate_limiter = &rate_limiter;
+    let lookups = market_price_batches(quoting_metrics, batch_size)
+        .into_iter()
+        .map(|batch| async move {
+            let prices = get_market_price_with_rate_limiter_and_retries(
+                evm_network,
+                rate_limiter,
+                batch.clone(),
+            )
+            .await?;
+            if prices.len() != batch.len() {
+                error!(
+                    "Market price lookup returned {} prices for {} quoting metrics",
+                    prices.len(),
+                    batch.len()
+                );
+                return Err(CostError::MissingMarketPrice);
+            }
+            Ok(batch.into_iter().zip(prices).collect::<Vec<_>>())
+        });
+
+    let mut market_prices = HashMap::new();
+    for prices in process_tasks_with_max_concurrency(lookups, concurrency.max(1)).await {
+        market_prices.extend(prices?);
+    }
+    Ok(market_prices)
+}
+
+/// Split the quoting metrics into batches of at most `batch_size` metrics.
+fn market_price_batches(
+    quoting_metrics: BTreeSet<QuotingMetrics>,
+    batch_size: usize,
+) -> Vec<Vec<QuotingMetrics>> {
+    let quoting_metrics: Vec<_> = quoting_metrics.into_iter().collect();
+    quoting_metrics
+        .chunks(batch_size.max(1))
+        .map(|batch| batch.to_vec())
+        .collect()
+}
+
 /// Fetch a store quote for a content address.
 async fn fetch_store_quote(
     network: &Network,
     content_addr: XorName,
+    data_type: DataTypes,
+    data_size: usize,
 ) -> Result<Vec<(PeerId, PaymentQuote)>, NetworkError> {
     network
         .get_store_quote_from_network(
             NetworkAddress::from_chunk_address(ChunkAddress::new(content_addr)),
+            data_type.get_index(),
+            data_size,
             vec![],
         )
         .await
@@ -162,11 +262,13 @@ async fn fetch_store_quote(
 async fn fetch_store_quote_with_retries(
     network: &Network,
     content_addr: XorName,
+    data_type: DataTypes,
+    data_size: usize,
 ) -> Result<(XorName, Vec<(PeerId, PaymentQuote)>), CostError> {
     let mut retries = 0;
 
     loop {
-        match fetch_store_quote(network, content_addr).await {
+        match fetch_store_quote(network, content_addr, data_type, data_size).await {
             Ok(quote) => {
                 if quote.len() < CLOSE_GROUP_SIZE {
                     retries += 1;
@@ -195,9 +297,11 @@ async fn fetch_store_quote_with_retries(
     }
 }
 
+/// Look up market prices, backing off when rate limited. Only retries wait for the rate limiter,
+/// to space them from the last lookup made with it.
 async fn get_market_price_with_rate_limiter_and_retries(
     evm_network: &EvmNetwork,
-    rate_limiter: &mut RateLimiter,
+    rate_limiter: &Mutex<RateLimiter>,
     quoting_metrics: Vec<QuotingMetrics>,
 ) -> Result<Vec<Amount>, ant_evm::payment_vault::error::Error> {
     const MAX_RETRIES: u64 = 2;
@@ -206,7 +310,9 @@ async fn get_market_price_with_rate_limiter_and_retries(
 
     loop {
         rate_limiter
-            .wait_interval_since_last_request(interval_in_ms)
+            .lock()
+            .await
+            .wait_interval_since_last_request(if retries == 0 { 0 } else { interval_in_ms })
             .await;
 
         match get_market_price(evm_network, quoting_metrics.clone()).await {
@@ -227,3 +333,66 @@ async fn get_market_price_with_rate_limiter_and_retries(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote_with_records_stored(content_addr: XorName, records: usize) -> PaymentQuote {
+        let mut quote = PaymentQuote::test_dummy(content_addr);
+        quote.quoting_metrics.close_records_stored = records;
+        quote
+    }
+
+    #[test]
+    fn market_prices_are_looked_up_once_per_distinct_metrics() {
+        let rng = &mut rand::thread_rng();
+        let addrs: Vec<XorName> = (0..10).map(|_| XorName::random(rng)).collect();
+        let raw_quotes: Vec<Vec<(PeerId, PaymentQuote)>> = addrs
+            .iter()
+            .map(|addr| {
+                (0..5)
+                    .map(|i| (PeerId::random(), quote_with_records_stored(*addr, i % 3)))
+                    .collect()
+            })
+            .collect();
+
+        let distinct: BTreeSet<QuotingMetrics> = raw_quotes
+            .iter()
+            .flatten()
+            .map(|(_, quote)| quote.quoting_metrics.clone())
+            .collect();
+        assert_eq!(distinct.len(), 3);
+
+        let batches = market_price_batches(distinct.clone(), 2);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(market_price_batches(distinct, 0).len(), 3);
+
+        // price is 10 times the records stored, so the cheapest quotes are the least loaded nodes
+        let market_prices: HashMap<QuotingMetrics, Amount> = batches
+            .into_iter()
+            .flatten()
+            .map(|metrics| {
+                let price = Amount::from(metrics.close_records_stored * 10);
+                (metrics, price)
+            })
+            .collect();
+
+        for (addr, quotes) in addrs.into_iter().zip(raw_quotes) {
+            let prices = price_quotes(quotes, &market_prices).expect("all metrics are priced");
+            let Some(QuoteForAddress(to_pay)) = select_quotes_to_pay(prices) else {
+                panic!("not enough quotes for {addr}");
+            };
+            let prices: Vec<Amount> = to_pay.iter().map(|(_, _, price)| *price).collect();
+            assert_eq!(
+                prices,
+                [0, 0, 10, 10, 20].map(Amount::from).to_vec(),
+                "cheapest two are free, the rest are paid at market price"
+            );
+        }
+
+        let mut unpriced = quote_with_records_stored(XorName::random(rng), 7);
+        unpriced.quoting_metrics.max_records = 1;
+        assert!(price_quotes(vec![(PeerId::random(), unpriced)], &market_prices).is_none());
+    }
+}
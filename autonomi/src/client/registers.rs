@@ -10,7 +10,6 @@
 
 use crate::client::data::PayError;
 use crate::client::Client;
-use crate::client::ClientEvent;
 use crate::client::UploadSummary;
 
 pub use ant_registers::{Permissions as RegisterPermissions, RegisterAddress};
@@ -19,13 +18,13 @@ pub use bls::SecretKey as RegisterSecretKey;
 use ant_evm::{Amount, AttoTokens, EvmWallet, EvmWalletError};
 use ant_networking::{GetRecordCfg, GetRecordError, NetworkError, PutRecordCfg, VerificationKind};
 use ant_protocol::{
-    storage::{try_deserialize_record, try_serialize_record, DataTypes, RecordKind, RetryStrategy},
+    storage::{try_deserialize_record, try_serialize_record, DataTypes, RecordKind},
     NetworkAddress,
 };
 use ant_registers::Register as BaseRegister;
 use ant_registers::{Permissions, RegisterCrdt, RegisterOp, SignedRegister};
 use bytes::Bytes;
-use libp2p::kad::{Quorum, Record};
+use libp2p::kad::Record;
 use std::collections::BTreeSet;
 use xor_name::XorName;
 
@@ -145,11 +144,8 @@ impl Client {
         let key = network_address.to_record_key();
 
         let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::Majority,
-            retry_strategy: None,
-            target_record: None,
-            expected_holders: Default::default(),
             is_register: true,
+            ..self.policy.registers.get.record_cfg()
         };
 
         let signed_reg = match self.network.get_record_from_network(key, &get_cfg).await {
@@ -214,16 +210,14 @@ impl Client {
             expires: None,
         };
 
+        let put_policy = self.policy.registers.put;
         let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::Majority,
-            retry_strategy: Some(RetryStrategy::default()),
-            target_record: None,
-            expected_holders: Default::default(),
             is_register: true,
+            ..put_policy.verification.record_cfg()
         };
         let put_cfg = PutRecordCfg {
-            put_quorum: Quorum::All,
-            retry_strategy: None,
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
             use_put_record_to: None,
             verification: Some((VerificationKind::Crdt, get_cfg)),
         };
@@ -261,8 +255,12 @@ impl Client {
         let reg_xor = register.address().xorname();
 
         // get cost to store register
-        // NB TODO: register should be priced differently from other data
-        let store_quote = self.get_store_quotes(std::iter::once(reg_xor)).await?;
+        let store_quote = self
+            .get_store_quotes(
+                DataTypes::Register,
+                std::iter::once((reg_xor, register.signed_reg.size())),
+            )
+            .await?;
 
         let total_cost = AttoTokens::from_atto(
             store_quote
@@ -320,11 +318,15 @@ impl Client {
         let reg_xor = address.xorname();
         debug!("Paying for register at address: {address}");
         let (payment_proofs, skipped_payments) = self
-            .pay(std::iter::once(reg_xor), wallet)
+            .pay(
+                DataTypes::Register,
+                std::iter::once((reg_xor, register.signed_reg.size())),
+                wallet,
+            )
             .await
             .inspect_err(|err| {
-            error!("Failed to pay for register at address: {address} : {err}")
-        })?;
+                error!("Failed to pay for register at address: {address} : {err}")
+            })?;
         let (proof, price) = if let Some((proof, price)) = payment_proofs.get(&reg_xor) {
             (proof, price)
         } else {
@@ -348,17 +350,15 @@ impl Client {
             expires: None,
         };
 
+        let put_policy = self.policy.registers.put;
         let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::Majority,
-            retry_strategy: Some(RetryStrategy::default()),
-            target_record: None,
-            expected_holders: Default::default(),
             is_register: true,
+            ..put_policy.verification.record_cfg()
         };
 
         let put_cfg = PutRecordCfg {
-            put_quorum: Quorum::All,
-            retry_strategy: None,
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
             use_put_record_to: Some(payees),
             verification: Some((VerificationKind::Crdt, get_cfg)),
         };
@@ -371,16 +371,11 @@ impl Client {
                 error!("Failed to put record - register {address} to the network: {err}")
             })?;
 
-        if let Some(channel) = self.client_event_sender.as_ref() {
-            let summary = UploadSummary {
-                records_paid: 1usize.saturating_sub(skipped_payments),
-                records_already_paid: skipped_payments,
-                tokens_spent: price.as_atto(),
-            };
-            if let Err(err) = channel.send(ClientEvent::UploadComplete(summary)).await {
-                error!("Failed to send client event: {err}");
-            }
-        }
+        self.send_upload_summary(UploadSummary {
+            records_paid: 1usize.saturating_sub(skipped_payments),
+            records_already_paid: skipped_payments,
+            tokens_spent: price.as_atto(),
+        });
 
         Ok(register)
     }
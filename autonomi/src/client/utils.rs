@@ -6,24 +6,26 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::client::payment::{receipt_from_store_quotes, Receipt};
-use ant_evm::{EvmWallet, ProofOfPayment};
-use ant_networking::{GetRecordCfg, PutRecordCfg, VerificationKind};
+use crate::client::payment::{
+    paid_amount, receipt_from_paid_quotes, receipt_from_store_quotes, PartialReceipt, Receipt,
+};
+use ant_evm::{AttoTokens, EvmWallet, PayForQuotesError, ProofOfPayment, QuoteHash, TxHash};
+use ant_networking::{PutRecordCfg, VerificationKind};
 use ant_protocol::{
     messages::ChunkProof,
-    storage::{try_serialize_record, Chunk, DataTypes, RecordKind, RetryStrategy},
+    storage::{try_serialize_record, Chunk, DataTypes, RecordKind},
 };
 use bytes::Bytes;
 use futures::stream::{FuturesUnordered, StreamExt};
-use libp2p::kad::{Quorum, Record};
+use libp2p::kad::Record;
 use rand::{thread_rng, Rng};
 use self_encryption::{decrypt_full_set, DataMap, EncryptedChunk};
-use std::{future::Future, num::NonZero};
+use std::{collections::BTreeMap, future::Future};
 use xor_name::XorName;
 
 use super::{
-    data::{GetError, PayError, PutError, CHUNK_DOWNLOAD_BATCH_SIZE},
-    Client,
+    data::{GetError, PayError, PutError},
+    Client, ClientEvent,
 };
 use crate::self_encryption::DataMapLevel;
 
@@ -53,11 +55,13 @@ impl Client {
             });
         }
         debug!("Successfully fetched all the encrypted chunks");
-        let encrypted_chunks =
-            process_tasks_with_max_concurrency(download_tasks, *CHUNK_DOWNLOAD_BATCH_SIZE)
-                .await
-                .into_iter()
-                .collect::<Result<Vec<EncryptedChunk>, GetError>>()?;
+        let encrypted_chunks = process_tasks_with_max_concurrency(
+            download_tasks,
+            self.policy.chunk_download_concurrency,
+        )
+        .await
+        .into_iter()
+        .collect::<Result<Vec<EncryptedChunk>, GetError>>()?;
 
         let data = decrypt_full_set(data_map, &encrypted_chunks).map_err(|e| {
             error!("Error decrypting encrypted_chunks: {e:?}");
@@ -72,28 +76,30 @@ impl Client {
         &self,
         data_map_bytes: &Bytes,
     ) -> Result<Bytes, GetError> {
+        let data_map = self.resolve_data_map(data_map_bytes).await?;
+        self.fetch_from_data_map(&data_map).await
+    }
+
+    /// Unpack a wrapped data map, fetching any additional levels, down to the data map of the source data.
+    pub(crate) async fn resolve_data_map(
+        &self,
+        data_map_bytes: &Bytes,
+    ) -> Result<DataMap, GetError> {
         let mut data_map_level: DataMapLevel = rmp_serde::from_slice(data_map_bytes)
             .map_err(GetError::InvalidDataMap)
             .inspect_err(|err| error!("Error deserializing data map: {err:?}"))?;
 
         loop {
-            let data_map = match &data_map_level {
-                DataMapLevel::First(map) => map,
-                DataMapLevel::Additional(map) => map,
-            };
-
-            let data = self.fetch_from_data_map(data_map).await?;
-
-            match &data_map_level {
-                DataMapLevel::First(_) => break Ok(data),
-                DataMapLevel::Additional(_) => {
+            match data_map_level {
+                DataMapLevel::First(map) => break Ok(map),
+                DataMapLevel::Additional(map) => {
+                    let data = self.fetch_from_data_map(&map).await?;
                     data_map_level = rmp_serde::from_slice(&data).map_err(|err| {
                         error!("Error deserializing data map: {err:?}");
                         GetError::InvalidDataMap(err)
                     })?;
-                    continue;
                 }
-            };
+            }
         }
     }
 
@@ -126,14 +132,9 @@ impl Client {
             expires: None,
         };
 
+        let put_policy = self.policy.chunks.put;
         let verification = {
-            let verification_cfg = GetRecordCfg {
-                get_quorum: Quorum::N(NonZero::new(2).expect("2 is non-zero")),
-                retry_strategy: Some(RetryStrategy::Balanced),
-                target_record: None,
-                expected_holders: Default::default(),
-                is_register: false,
-            };
+            let verification_cfg = put_policy.verification.record_cfg();
 
             let stored_on_node =
                 try_serialize_record(&chunk, RecordKind::DataOnly(DataTypes::Chunk))
@@ -154,42 +155,80 @@ impl Client {
         };
 
         let put_cfg = PutRecordCfg {
-            put_quorum: Quorum::One,
-            retry_strategy: Some(RetryStrategy::Balanced),
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
             use_put_record_to: Some(storing_nodes.clone()),
             verification,
         };
         let payment_upload = Ok(self.network.put_record(record, &put_cfg).await?);
         debug!("Successfully stored chunk: {chunk:?} to {storing_nodes:?}");
+        self.send_event(ClientEvent::ChunkStored(*chunk.name()));
         payment_upload
     }
 
-    /// Pay for the chunks and get the proof of payment.
+    /// Pay for storing data of `data_type` at the content addresses, with the size of the data
+    /// stored at each, and get the proof of payment.
     pub(crate) async fn pay(
         &self,
-        content_addrs: impl Iterator<Item = XorName> + Clone,
+        data_type: DataTypes,
+        content_addrs: impl Iterator<Item = (XorName, usize)> + Clone,
         wallet: &EvmWallet,
     ) -> Result<(Receipt, AlreadyPaidAddressesCount), PayError> {
         let number_of_content_addrs = content_addrs.clone().count();
-        let quotes = self.get_store_quotes(content_addrs).await?;
+        let quotes = self.get_store_quotes(data_type, content_addrs).await?;
+        for addr in quotes.0.keys() {
+            self.send_event(ClientEvent::ChunkQuoted(*addr));
+        }
+
+        // Check the quotes against the cost limits before sending any transaction
+        let reserved = self
+            .cost_budget
+            .reserve(&quotes)
+            .map_err(PayError::CostLimitExceeded)?;
 
         // Make sure nobody else can use the wallet while we are paying
         debug!("Waiting for wallet lock");
         let lock_guard = wallet.lock().await;
         debug!("Locked wallet");
 
-        // TODO: the error might contain some succeeded quote payments as well. These should be returned on err, so that they can be skipped when retrying.
         // TODO: retry when it fails?
         // Execute chunk payments
-        let _payments = wallet
-            .pay_for_quotes(quotes.payments())
-            .await
-            .map_err(|err| PayError::from(err.0))?;
+        let payments = wallet.pay_for_quotes(quotes.payments()).await;
 
         // payment is done, unlock the wallet for other threads
         drop(lock_guard);
         debug!("Unlocked wallet");
 
+        let sent = match &payments {
+            Ok(sent) | Err(PayForQuotesError(_, sent)) => sent,
+        };
+        self.send_payment_events(sent);
+
+        if let Err(PayForQuotesError(error, succeeded)) = payments {
+            // Earlier batches may have gone through, keep what was paid for
+            self.cost_budget.release(
+                reserved,
+                AttoTokens::from_atto(paid_amount(&quotes, &succeeded)),
+            );
+            let partial = PartialReceipt::from_paid_quotes(&quotes, &succeeded);
+            let receipt = receipt_from_paid_quotes(quotes, &succeeded);
+            self.send_event(ClientEvent::Error(format!("Payment failed: {error}")));
+            if receipt.is_empty() && partial.is_empty() {
+                return Err(PayError::from(error));
+            }
+            warn!(
+                "Payment failed after {} transactions paid for {} addresses and part of {} others",
+                succeeded.len(),
+                receipt.len(),
+                partial.len()
+            );
+            return Err(PayError::PartialPayment {
+                error,
+                receipt,
+                partial,
+            });
+        }
+
         let skipped_chunks = number_of_content_addrs - quotes.len();
         trace!(
             "Chunk payments of {} chunks completed. {} chunks were free / already paid for",
@@ -201,6 +240,58 @@ impl Client {
 
         Ok((receipt, skipped_chunks))
     }
+
+    /// Pay the quotes of partly paid content addresses that were not paid yet, and get the proof
+    /// of payment of the ones that are fully paid for now.
+    pub(crate) async fn pay_remaining_quotes(
+        &self,
+        mut partial: PartialReceipt,
+        wallet: &EvmWallet,
+    ) -> Result<Receipt, PayError> {
+        let quotes = partial.unpaid_quotes();
+        let reserved = self
+            .cost_budget
+            .reserve(&quotes)
+            .map_err(PayError::CostLimitExceeded)?;
+
+        debug!("Waiting for wallet lock");
+        let lock_guard = wallet.lock().await;
+        let payments = wallet.pay_for_quotes(quotes.payments()).await;
+        drop(lock_guard);
+        debug!("Unlocked wallet");
+
+        let (sent, error) = match payments {
+            Ok(sent) => (sent, None),
+            Err(PayForQuotesError(error, sent)) => (sent, Some(error)),
+        };
+        self.send_payment_events(&sent);
+        partial.record_paid(&sent);
+        let receipt = partial.take_fully_paid();
+
+        match error {
+            None => Ok(receipt),
+            Some(error) => {
+                self.cost_budget
+                    .release(reserved, AttoTokens::from_atto(paid_amount(&quotes, &sent)));
+                self.send_event(ClientEvent::Error(format!("Payment failed: {error}")));
+                Err(PayError::PartialPayment {
+                    error,
+                    receipt,
+                    partial,
+                })
+            }
+        }
+    }
+
+    fn send_payment_events(&self, sent: &BTreeMap<QuoteHash, TxHash>) {
+        let mut quotes_per_tx = BTreeMap::<TxHash, usize>::new();
+        for tx in sent.values() {
+            *quotes_per_tx.entry(*tx).or_default() += 1;
+        }
+        for (tx, quotes) in quotes_per_tx {
+            self.send_event(ClientEvent::PaymentSent { tx, quotes });
+        }
+    }
 }
 
 pub(crate) async fn process_tasks_with_max_concurrency<I, R>(tasks: I, batch_size: usize) -> Vec<R>
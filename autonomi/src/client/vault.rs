@@ -17,14 +17,13 @@ use crate::client::data::PutError;
 use crate::client::payment::PaymentOption;
 use crate::client::Client;
 use ant_evm::{Amount, AttoTokens};
-use ant_networking::{GetRecordCfg, GetRecordError, NetworkError, PutRecordCfg, VerificationKind};
+use ant_networking::{GetRecordError, NetworkError, PutRecordCfg, VerificationKind};
 use ant_protocol::storage::{
-    try_serialize_record, DataTypes, RecordKind, RetryStrategy, Scratchpad, ScratchpadAddress,
+    try_serialize_record, DataTypes, RecordKind, Scratchpad, ScratchpadAddress,
 };
 use ant_protocol::Bytes;
 use ant_protocol::{storage::try_deserialize_record, NetworkAddress};
-use libp2p::kad::{Quorum, Record};
-use std::collections::HashSet;
+use libp2p::kad::Record;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use tracing::info;
 
@@ -83,13 +82,7 @@ impl Client {
         info!("Fetching vault from network at {network_address:?}",);
         let scratch_key = network_address.to_record_key();
 
-        let get_cfg = GetRecordCfg {
-            get_quorum: Quorum::Majority,
-            retry_strategy: None,
-            target_record: None,
-            expected_holders: HashSet::new(),
-            is_register: false,
-        };
+        let get_cfg = self.policy.scratchpads.get.record_cfg();
 
         let pad = match self
             .network
@@ -143,7 +136,7 @@ impl Client {
         Ok(pad)
     }
 
-    /// Get the cost of creating a new vault
+    /// Get the cost of creating a new vault, quoted for an empty vault
     pub async fn vault_cost(&self, owner: &VaultSecretKey) -> Result<AttoTokens, CostError> {
         info!("Getting cost for vault");
         let client_pk = owner.public_key();
@@ -152,7 +145,12 @@ impl Client {
         let vault_xor = scratch.address().xorname();
 
         // NB TODO: vault should be priced differently from other data
-        let store_quote = self.get_store_quotes(std::iter::once(vault_xor)).await?;
+        let store_quote = self
+            .get_store_quotes(
+                DataTypes::Scratchpad,
+                std::iter::once((vault_xor, scratch.payload_size())),
+            )
+            .await?;
 
         let total_cost = AttoTokens::from_atto(
             store_quote
@@ -193,7 +191,11 @@ impl Client {
 
         let record = if is_new {
             let (receipt, _skipped_payments) = self
-                .pay_for_content_addrs(std::iter::once(scratch.xorname()), payment_option)
+                .pay_for_content_addrs(
+                    DataTypes::Scratchpad,
+                    std::iter::once((scratch.xorname(), scratch.payload_size())),
+                    payment_option,
+                )
                 .await
                 .inspect_err(|err| {
                     error!("Failed to pay for new vault at addr: {scratch_address:?} : {err}");
@@ -234,20 +236,12 @@ impl Client {
             }
         };
 
+        let put_policy = self.policy.scratchpads.put;
         let put_cfg = PutRecordCfg {
-            put_quorum: Quorum::Majority,
-            retry_strategy: Some(RetryStrategy::Balanced),
+            put_quorum: put_policy.quorum,
+            retry_strategy: put_policy.retry,
             use_put_record_to: None,
-            verification: Some((
-                VerificationKind::Crdt,
-                GetRecordCfg {
-                    get_quorum: Quorum::Majority,
-                    retry_strategy: None,
-                    target_record: None,
-                    expected_holders: HashSet::new(),
-                    is_register: false,
-                },
-            )),
+            verification: Some((VerificationKind::Crdt, put_policy.verification.record_cfg())),
         };
 
         debug!("Put record - scratchpad at {scratch_address:?} to the network");
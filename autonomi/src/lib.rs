@@ -71,6 +71,7 @@ pub use ant_evm::EvmNetwork as Network;
 pub use ant_evm::EvmWallet as Wallet;
 pub use ant_evm::QuoteHash;
 pub use ant_evm::RewardsAddress;
+pub use ant_evm::{PaymentSigner, RemoteSigner};
 pub use ant_protocol::storage::{Chunk, ChunkAddress};
 
 #[doc(no_inline)] // Place this under 'Re-exports' in the docs.
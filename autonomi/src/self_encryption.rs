@@ -11,6 +11,8 @@ use bytes::{BufMut, Bytes, BytesMut};
 use rayon::prelude::*;
 use self_encryption::{DataMap, MAX_CHUNK_SIZE};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
 use tracing::debug;
 
 #[derive(Debug, thiserror::Error)]
@@ -45,6 +47,127 @@ pub fn encrypt(data: Bytes) -> Result<(Chunk, Vec<Chunk>), Error> {
     Ok((data_map_chunk, chunks))
 }
 
+/// Self-encrypts a file on disk chunk by chunk, so that only a bounded number of
+/// encrypted chunks are held in memory at any time.
+#[cfg(feature = "fs")]
+pub(crate) struct FileEncryptor {
+    encryptor: self_encryption::StreamSelfEncryptor,
+}
+
+/// Result of a single [`FileEncryptor::next_batch`] call.
+#[cfg(feature = "fs")]
+pub(crate) struct EncryptedBatch {
+    /// Encrypted content chunks of this batch.
+    pub chunks: Vec<Chunk>,
+    /// Once the whole file has been encrypted: the data map chunk and any additional
+    /// chunks produced while packing the data map.
+    pub data_map: Option<(Chunk, Vec<Chunk>)>,
+}
+
+#[cfg(feature = "fs")]
+impl FileEncryptor {
+    pub(crate) fn new(path: PathBuf) -> Result<Self, Error> {
+        // `StreamSelfEncryptor` silently produces an empty data map for tiny files,
+        // make sure we fail the same way as the in-memory `encrypt` does.
+        let size = std::fs::metadata(&path)
+            .map_err(self_encryption::Error::Io)?
+            .len();
+        if size < self_encryption::MIN_ENCRYPTABLE_BYTES as u64 {
+            return Err(Error::SelfEncryption(self_encryption::Error::Generic(
+                format!(
+                    "Too small for self-encryption! Required size at least {}",
+                    self_encryption::MIN_ENCRYPTABLE_BYTES
+                ),
+            )));
+        }
+
+        let encryptor = self_encryption::StreamSelfEncryptor::encrypt_from_file(path, None)?;
+        Ok(Self { encryptor })
+    }
+
+    /// Encrypt up to `batch_size` chunks. The last batch also carries the packed data map.
+    pub(crate) fn next_batch(&mut self, batch_size: usize) -> Result<EncryptedBatch, Error> {
+        let mut chunks = Vec::with_capacity(batch_size);
+
+        while chunks.len() < batch_size.max(1) {
+            match self.encryptor.next_encryption()? {
+                (Some(encrypted_chunk), _) => chunks.push(Chunk::new(encrypted_chunk.content)),
+                (None, Some(data_map)) => {
+                    let data_map = pack_data_map(data_map)?;
+                    return Ok(EncryptedBatch {
+                        chunks,
+                        data_map: Some(data_map),
+                    });
+                }
+                (None, None) => {
+                    return Err(Error::SelfEncryption(self_encryption::Error::Encryption));
+                }
+            }
+        }
+
+        Ok(EncryptedBatch {
+            chunks,
+            data_map: None,
+        })
+    }
+}
+
+/// Self-encrypt the file at `path` without keeping any of the chunks, returning only its data map chunk.
+///
+/// As self-encryption is deterministic, this identifies the file content as stored on the network.
+#[cfg(feature = "fs")]
+pub(crate) fn file_data_map_chunk(path: PathBuf) -> Result<Chunk, Error> {
+    let mut encryptor = FileEncryptor::new(path)?;
+    loop {
+        if let Some((data_map_chunk, _)) = encryptor.next_batch(1)?.data_map {
+            return Ok(data_map_chunk);
+        }
+    }
+}
+
+/// Self-encrypt the file at `path` without keeping any of the chunks.
+///
+/// Returns the data map chunk and the addresses and sizes of all other chunks uploading the file
+/// stores.
+#[cfg(feature = "fs")]
+pub(crate) fn file_chunk_addrs(
+    path: PathBuf,
+) -> Result<(Chunk, Vec<(xor_name::XorName, usize)>), Error> {
+    let addr_and_size = |chunk: &Chunk| (*chunk.name(), chunk.payload_size());
+    let mut encryptor = FileEncryptor::new(path)?;
+    let mut addrs = vec![];
+    loop {
+        let batch = encryptor.next_batch(64)?;
+        addrs.extend(batch.chunks.iter().map(addr_and_size));
+        if let Some((data_map_chunk, additional_chunks)) = batch.data_map {
+            addrs.extend(additional_chunks.iter().map(addr_and_size));
+            return Ok((data_map_chunk, addrs));
+        }
+    }
+}
+
+/// Self-encrypt the file at `path`, keeping only the chunks named in `wanted`, or all of them if
+/// it is `None`.
+///
+/// Returns the data map chunk and the kept chunks.
+#[cfg(feature = "fs")]
+pub(crate) fn file_chunks(
+    path: PathBuf,
+    wanted: Option<&std::collections::HashSet<xor_name::XorName>>,
+) -> Result<(Chunk, Vec<Chunk>), Error> {
+    let is_wanted = |chunk: &Chunk| wanted.is_none_or(|wanted| wanted.contains(chunk.name()));
+    let mut encryptor = FileEncryptor::new(path)?;
+    let mut chunks = vec![];
+    loop {
+        let batch = encryptor.next_batch(64)?;
+        chunks.extend(batch.chunks.into_iter().filter(is_wanted));
+        if let Some((data_map_chunk, additional_chunks)) = batch.data_map {
+            chunks.extend(additional_chunks.into_iter().filter(is_wanted));
+            return Ok((data_map_chunk, chunks));
+        }
+    }
+}
+
 // Produces a chunk out of the first `DataMap`, which is validated for its size.
 // If the chunk is too big, it is self-encrypted and the resulting (additional level) `DataMap` is put into a chunk.
 // The above step is repeated as many times as required until the chunk size is valid.
@@ -92,3 +215,71 @@ fn wrap_data_map(data_map: &DataMapLevel) -> Result<Bytes, rmp_serde::encode::Er
         .inspect_err(|err| error!("Failed to serialize data map: {err:?}"))?;
     Ok(bytes.into_inner().freeze())
 }
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use std::io::Write;
+
+    #[test]
+    fn file_encryptor_matches_in_memory_encryption() -> eyre::Result<()> {
+        let mut data = vec![0u8; 5 * *MAX_CHUNK_SIZE + 123];
+        rand::thread_rng().fill_bytes(&mut data);
+        let data = Bytes::from(data);
+
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(&data)?;
+        file.flush()?;
+
+        let (expected_data_map_chunk, mut expected_chunks) = encrypt(data)?;
+
+        let mut encryptor = FileEncryptor::new(file.path().to_path_buf())?;
+        let mut chunks = vec![];
+        let data_map_chunk = loop {
+            let batch = encryptor.next_batch(2)?;
+            assert!(batch.chunks.len() <= 2);
+            chunks.extend(batch.chunks);
+            if let Some((data_map_chunk, additional_chunks)) = batch.data_map {
+                chunks.extend(additional_chunks);
+                break data_map_chunk;
+            }
+        };
+
+        chunks.sort_by_key(|chunk| *chunk.name());
+        expected_chunks.sort_by_key(|chunk| *chunk.name());
+        assert_eq!(data_map_chunk, expected_data_map_chunk);
+        assert_eq!(chunks, expected_chunks);
+        Ok(())
+    }
+
+    #[test]
+    fn file_chunks_keeps_only_wanted_chunks() -> eyre::Result<()> {
+        let mut data = vec![0u8; 3 * *MAX_CHUNK_SIZE];
+        rand::thread_rng().fill_bytes(&mut data);
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(&data)?;
+        file.flush()?;
+
+        let (expected_data_map_chunk, expected_chunks) = encrypt(Bytes::from(data))?;
+        let wanted = [*expected_chunks[1].name()].into_iter().collect();
+
+        let (data_map_chunk, chunks) = file_chunks(file.path().to_path_buf(), Some(&wanted))?;
+        assert_eq!(data_map_chunk, expected_data_map_chunk);
+        assert_eq!(chunks, vec![expected_chunks[1].clone()]);
+
+        let (_, chunks) = file_chunks(file.path().to_path_buf(), None)?;
+        assert_eq!(chunks.len(), expected_chunks.len());
+        Ok(())
+    }
+
+    #[test]
+    fn file_encryptor_rejects_tiny_files() -> eyre::Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(b"ab")?;
+        file.flush()?;
+
+        assert!(FileEncryptor::new(file.path().to_path_buf()).is_err());
+        Ok(())
+    }
+}
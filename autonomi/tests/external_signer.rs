@@ -4,6 +4,7 @@ use alloy::network::TransactionBuilder;
 use alloy::providers::Provider;
 use ant_evm::{QuoteHash, TxHash};
 use ant_logging::LogBuilder;
+use ant_protocol::storage::DataTypes;
 use autonomi::client::external_signer::encrypt_data;
 use autonomi::client::files::archive::{Metadata, PrivateArchive};
 use autonomi::client::payment::{receipt_from_store_quotes, Receipt};
@@ -23,28 +24,29 @@ async fn pay_for_data(client: &Client, wallet: &Wallet, data: Bytes) -> eyre::Re
     let (data_map_chunk, chunks) = encrypt_data(data)?;
 
     let map_xor_name = *data_map_chunk.address().xorname();
-    let mut xor_names = vec![map_xor_name];
+    let mut xor_names = vec![(map_xor_name, data_map_chunk.payload_size())];
 
     for chunk in chunks {
-        xor_names.push(*chunk.name());
+        xor_names.push((*chunk.name(), chunk.payload_size()));
     }
 
-    pay_for_content_addresses(client, wallet, xor_names.into_iter()).await
+    pay_for_content_addresses(client, wallet, DataTypes::Chunk, xor_names.into_iter()).await
 }
 
 async fn pay_for_content_addresses(
     client: &Client,
     wallet: &Wallet,
-    content_addrs: impl Iterator<Item = XorName> + Clone,
+    data_type: DataTypes,
+    content_addrs: impl Iterator<Item = (XorName, usize)> + Clone,
 ) -> eyre::Result<Receipt> {
     let (quotes, quote_payments, _free_chunks) = client
-        .get_quotes_for_content_addresses(content_addrs)
+        .get_quotes_for_content_addresses(data_type, content_addrs)
         .await?;
 
     // Form quotes payment transaction data
     let pay_for_quotes_calldata = autonomi::client::external_signer::pay_for_quotes_calldata(
         wallet.network(),
-        quote_payments.into_iter(),
+        quote_payments,
     )?;
 
     // Init an external wallet provider. In the webapp, this would be MetaMask for example
@@ -146,14 +148,22 @@ async fn external_signer_put() -> eyre::Result<()> {
 
     assert!(is_new, "Scratchpad is not new");
 
+    // the vault is quoted for the size of the user data written to it
+    let mut written = scratch.clone();
+    let _ = written.update_and_sign(user_data.to_bytes()?, &vault_key);
     let scratch_addresses = if is_new {
-        vec![scratch.xorname()]
+        vec![(scratch.xorname(), written.payload_size())]
     } else {
         vec![]
     };
 
-    let receipt =
-        pay_for_content_addresses(&client, &wallet, scratch_addresses.into_iter()).await?;
+    let receipt = pay_for_content_addresses(
+        &client,
+        &wallet,
+        DataTypes::Scratchpad,
+        scratch_addresses.into_iter(),
+    )
+    .await?;
 
     sleep(Duration::from_secs(5)).await;
 
@@ -187,3 +197,63 @@ async fn external_signer_put() -> eyre::Result<()> {
 
     Ok(())
 }
+
+// Example of the offline workflow: the payment request is written to a file, paid by an external
+// signer, and the recorded transactions are turned back into a receipt for the upload.
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn external_signer_payment_files() -> eyre::Result<()> {
+    use autonomi::client::external_signer::{PaymentRequest, PaymentResult};
+    use std::path::PathBuf;
+
+    let _log_appender_guard =
+        LogBuilder::init_single_threaded_tokio_test("external_signer_payment_files", false);
+
+    let client = Client::init_local().await?;
+    let wallet = get_funded_wallet();
+    let dir = PathBuf::from("tests/file/test_dir");
+
+    let content_addrs = client
+        .dir_and_archive_content_addrs_public(dir.clone())
+        .await?;
+    let request = client
+        .prepare_payment(DataTypes::Chunk, content_addrs.into_iter())
+        .await?;
+    let request = PaymentRequest::from_json(&request.to_json()?)?;
+
+    // Sign and send every transaction of the request, as the external signer would
+    let provider = wallet.to_provider();
+    let mut result = PaymentResult::default();
+    for transaction in std::iter::once(&request.approve).chain(&request.payments) {
+        let transaction_request = provider
+            .transaction_request()
+            .with_to(transaction.to)
+            .with_input(transaction.calldata.clone());
+
+        let tx_hash = provider
+            .send_transaction(transaction_request)
+            .await?
+            .watch()
+            .await?;
+        result.record(transaction, tx_hash);
+    }
+
+    let receipt = request.receipt(&PaymentResult::from_json(&result.to_json()?)?)?;
+
+    sleep(Duration::from_secs(5)).await;
+
+    let archive = client
+        .dir_upload_public_with_payment(dir, receipt.clone().into())
+        .await?;
+    let archive_addr = client
+        .data_put_public(archive.to_bytes()?, receipt.into())
+        .await?;
+
+    let fetched_archive = client.archive_get_public(archive_addr).await?;
+    assert_eq!(
+        fetched_archive, archive,
+        "Fetched archive is not identical to the uploaded archive"
+    );
+
+    Ok(())
+}
@@ -9,6 +9,7 @@
 #![cfg(feature = "fs")]
 
 use ant_logging::LogBuilder;
+use autonomi::client::data::verify::VerifyMode;
 use autonomi::Client;
 use eyre::Result;
 use sha2::{Digest, Sha256};
@@ -47,6 +48,51 @@ async fn dir_upload_download() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn dir_verify_and_repair() -> Result<()> {
+    let _log_appender_guard =
+        LogBuilder::init_single_threaded_tokio_test("dir_verify_and_repair", false);
+
+    let client = Client::init_local().await?;
+    let wallet = get_funded_wallet();
+
+    let addr = client
+        .dir_and_archive_upload_public("tests/file/test_dir".into(), &wallet)
+        .await?;
+
+    sleep(Duration::from_secs(10)).await;
+
+    let mut health = client.dir_verify_public(addr, VerifyMode::Prove).await?;
+    assert!(health.is_healthy(), "{health:?}");
+    assert!(!health.files.is_empty());
+
+    // report the data map chunk of a file as missing
+    let archive = client.archive_get_public(addr).await?;
+    let (path, file_addr, _) = archive.iter().next().expect("archive has files");
+    let file_health = health.files.get_mut(path).expect("file was verified");
+    file_health.missing.push(*file_addr);
+
+    // a local copy is needed to repair the file
+    let summary = client
+        .dir_repair_public(
+            addr,
+            &health,
+            "tests/no_such_dir".into(),
+            wallet.clone().into(),
+        )
+        .await?;
+    assert_eq!(summary.unrepairable, vec![path.clone()]);
+
+    // the chunk is still stored, so it is not paid for again
+    let summary = client
+        .dir_repair_public(addr, &health, "tests/file".into(), wallet.into())
+        .await?;
+    assert!(summary.unrepairable.is_empty());
+    assert_eq!(summary.files_repaired, 1);
+    assert_eq!(summary.chunks_uploaded, 0);
+    Ok(())
+}
+
 fn compute_sha256(path: &str) -> Result<String> {
     let mut hasher = Sha256::new();
     let mut file = BufReader::new(File::open(path)?);
@@ -0,0 +1,124 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use ant_logging::LogBuilder;
+use ant_protocol::storage::{ChunkAddress, Pointer, PointerAddress, PointerTarget};
+use autonomi::{client::pointer::PointerError, Client};
+use eyre::Result;
+use test_utils::evm::get_funded_wallet;
+use xor_name::XorName;
+
+fn random_chunk_target() -> PointerTarget {
+    PointerTarget::ChunkAddress(ChunkAddress::new(XorName::random(&mut rand::thread_rng())))
+}
+
+#[tokio::test]
+async fn pointer_put_get_and_resolve() -> Result<()> {
+    let _log_appender_guard = LogBuilder::init_single_threaded_tokio_test("pointer", false);
+
+    let client = Client::init_local().await?;
+    let wallet = get_funded_wallet();
+
+    // a pointer to a chunk
+    let key = bls::SecretKey::random();
+    let target = random_chunk_target();
+    let pointer = Pointer::new(key.public_key(), 0, target.clone(), &key);
+    client.pointer_put(pointer.clone(), &wallet).await?;
+    println!("pointer put 1");
+
+    // wait for the pointer to be replicated
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let got = client.pointer_get(pointer.network_address()).await?;
+    assert_eq!(got, pointer);
+    println!("pointer got 1");
+
+    // a pointer to the first pointer resolves to the chunk
+    let outer_key = bls::SecretKey::random();
+    let outer = Pointer::new(
+        outer_key.public_key(),
+        0,
+        PointerTarget::PointerAddress(pointer.network_address()),
+        &outer_key,
+    );
+    client.pointer_put(outer.clone(), &wallet).await?;
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let resolved = client.pointer_resolve(outer.network_address()).await?;
+    assert_eq!(resolved, target);
+    println!("pointer resolved");
+
+    // two pointers targeting each other are detected as a cycle
+    let (key_a, key_b) = (bls::SecretKey::random(), bls::SecretKey::random());
+    let address_a = PointerAddress::from_owner(key_a.public_key());
+    let address_b = PointerAddress::from_owner(key_b.public_key());
+    let pointer_a = Pointer::new(
+        key_a.public_key(),
+        0,
+        PointerTarget::PointerAddress(address_b),
+        &key_a,
+    );
+    let pointer_b = Pointer::new(
+        key_b.public_key(),
+        0,
+        PointerTarget::PointerAddress(address_a),
+        &key_b,
+    );
+    client.pointer_put(pointer_a, &wallet).await?;
+    client.pointer_put(pointer_b, &wallet).await?;
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let res = client.pointer_resolve(address_a).await;
+    assert!(matches!(
+        res,
+        Err(PointerError::Cycle(address)) if address == address_a
+    ));
+    Ok(())
+}
+
+/// Relies on nodes accepting free updates from the owner of an already paid pointer.
+#[tokio::test]
+async fn pointer_update_without_payment() -> Result<()> {
+    let _log_appender_guard = LogBuilder::init_single_threaded_tokio_test("pointer_update", false);
+
+    let client = Client::init_local().await?;
+    let wallet = get_funded_wallet();
+
+    let key = bls::SecretKey::random();
+    let pointer = Pointer::new(key.public_key(), 0, random_chunk_target(), &key);
+    client.pointer_put(pointer.clone(), &wallet).await?;
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    // retarget the pointer, without paying again
+    let new_target = random_chunk_target();
+    client.pointer_update(&key, new_target.clone()).await?;
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let got = client.pointer_get(pointer.network_address()).await?;
+    assert_eq!(got.count(), 1);
+    assert_eq!(got.target(), &new_target);
+    println!("pointer updated");
+
+    // the stale version is refused by the client as the address is already paid for, nodes
+    // rejecting lower counters is covered by the unit tests of the node
+    assert!(matches!(
+        client.pointer_put(pointer.clone(), &wallet).await,
+        Err(PointerError::PointerAlreadyExists(_))
+    ));
+    let got = client.pointer_get(pointer.network_address()).await?;
+    assert_eq!(got.count(), 1);
+
+    // updating again resolves to the latest target
+    let latest_target = random_chunk_target();
+    client.pointer_update(&key, latest_target.clone()).await?;
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let resolved = client.pointer_resolve(pointer.network_address()).await?;
+    assert_eq!(resolved, latest_target);
+    Ok(())
+}
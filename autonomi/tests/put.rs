@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use ant_logging::LogBuilder;
+use autonomi::client::ClientEvent;
 use autonomi::Client;
 use eyre::Result;
 use test_utils::{evm::get_funded_wallet, gen_random_data};
@@ -26,3 +27,39 @@ async fn put() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn put_and_get_report_progress() -> Result<()> {
+    let _log_appender_guard =
+        LogBuilder::init_single_threaded_tokio_test("put_and_get_report_progress", false);
+
+    let mut client = Client::init_local().await?;
+    let mut events = client.enable_client_events();
+    let wallet = get_funded_wallet();
+    let data = gen_random_data(1024 * 1024 * 2);
+
+    let addr = client.data_put_public(data.clone(), wallet.into()).await?;
+    let _ = client.data_get_public(addr).await?;
+
+    let (mut quoted, mut paid, mut stored, mut fetched, mut completed) = (0, 0, 0, 0, false);
+    while let Ok(event) = events.try_recv() {
+        match event {
+            ClientEvent::ChunkQuoted(_) => quoted += 1,
+            ClientEvent::PaymentSent { quotes, .. } => paid += quotes,
+            ClientEvent::ChunkStored(_) => stored += 1,
+            ClientEvent::ChunkFetched(_) => fetched += 1,
+            ClientEvent::UploadComplete(_) => completed = true,
+            _ => {}
+        }
+    }
+    assert!(quoted > 0);
+    assert!(paid >= quoted, "every quoted chunk is paid for");
+    assert_eq!(stored, quoted);
+    assert!(
+        fetched >= quoted - 1,
+        "all chunks but the data map are fetched"
+    );
+    assert!(completed);
+
+    Ok(())
+}
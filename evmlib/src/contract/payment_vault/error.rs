@@ -7,6 +7,8 @@ pub enum Error {
     #[error(transparent)]
     RpcError(#[from] RpcError<TransportErrorKind>),
     #[error(transparent)]
+    DecodeError(#[from] alloy::sol_types::Error),
+    #[error(transparent)]
     PendingTransactionError(#[from] alloy::providers::PendingTransactionError),
     #[error("Payment is invalid.")]
     PaymentInvalid,
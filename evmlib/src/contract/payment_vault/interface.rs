@@ -31,6 +31,7 @@ impl From<(QuoteHash, Address, Amount)> for IPaymentVault::DataPayment {
     }
 }
 
+// The vault does not price the data type and size (yet), they are only signed over in the quote.
 impl From<QuotingMetrics> for IPaymentVault::QuotingMetrics {
     fn from(value: QuotingMetrics) -> Self {
         Self {
@@ -3,6 +3,11 @@ use crate::contract::payment_vault::handler::PaymentVaultHandler;
 use crate::quoting_metrics::QuotingMetrics;
 use crate::utils::http_provider;
 use crate::Network;
+use alloy::primitives::Bytes;
+use alloy::rpc::client::RpcClient;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol_types::SolCall;
+use alloy::transports::http::{Client, Http};
 
 pub mod error;
 pub mod handler;
@@ -31,8 +36,6 @@ pub async fn verify_data_payment(
     let provider = http_provider(network.rpc_url().clone());
     let payment_vault = PaymentVaultHandler::new(*network.data_payments_address(), provider);
 
-    let mut amount = Amount::ZERO;
-
     let payment_verifications: Vec<_> = payment
         .into_iter()
         .map(interface::IPaymentVault::PaymentVerification::from)
@@ -40,16 +43,89 @@ pub async fn verify_data_payment(
 
     let payment_verification_results = payment_vault.verify_payment(payment_verifications).await?;
 
-    for payment_verification_result in payment_verification_results {
-        // TODO we currently fail on a single invalid payment, maybe we should deal with this in a different way
-        if !payment_verification_result.isValid {
-            return Err(error::Error::PaymentInvalid);
+    let amount = paid_quotes(payment_verification_results)?
+        .into_iter()
+        .filter(|(quote_hash, _)| owned_quote_hashes.contains(quote_hash))
+        .map(|(_, amount_paid)| amount_paid)
+        .sum();
+    Ok(amount)
+}
+
+/// Verifies several data payments in a single RPC round trip, over one client to the RPC
+/// endpoint kept for all batches.
+#[derive(Clone, Debug)]
+pub struct PaymentBatchVerifier {
+    client: RpcClient<Http<Client>>,
+    data_payments_address: Address,
+}
+
+impl PaymentBatchVerifier {
+    pub fn new(network: &Network) -> Self {
+        Self {
+            client: RpcClient::new_http(network.rpc_url().clone()),
+            data_payments_address: *network.data_payments_address(),
         }
+    }
+
+    /// The payment vault verifies the quotes of one record per `verifyPayment` call, so one call
+    /// per payment is made, all sent together as a single JSON-RPC batch request.
+    /// Returns, for each payment in order, the amount paid to each quote the vault checked, or an
+    /// error if any of them is invalid.
+    pub async fn verify_data_payments(
+        &self,
+        payments: Vec<Vec<(QuoteHash, QuotingMetrics, Address)>>,
+    ) -> Result<Vec<Result<Vec<(QuoteHash, Amount)>, error::Error>>, error::Error> {
+        let mut batch = self.client.new_batch();
+
+        let mut waiters = Vec::with_capacity(payments.len());
+        for payment in payments {
+            let call = interface::IPaymentVault::verifyPaymentCall {
+                _payments: payment
+                    .into_iter()
+                    .map(interface::IPaymentVault::PaymentVerification::from)
+                    .collect(),
+            };
+            let tx = TransactionRequest::default()
+                .to(self.data_payments_address)
+                .input(call.abi_encode().into());
+            waiters.push(batch.add_call::<_, Bytes>("eth_call", &(tx, "latest"))?);
+        }
+        batch.send().await?;
 
-        if owned_quote_hashes.contains(&payment_verification_result.quoteHash) {
-            amount += payment_verification_result.amountPaid;
+        let mut results = Vec::with_capacity(waiters.len());
+        for waiter in waiters {
+            let result = match waiter.await {
+                Ok(output) => {
+                    interface::IPaymentVault::verifyPaymentCall::abi_decode_returns(&output, true)
+                        .map_err(error::Error::from)
+                        .and_then(|output| paid_quotes(output.verificationResults))
+                }
+                Err(err) => Err(err.into()),
+            };
+            results.push(result);
         }
+        Ok(results)
     }
+}
 
-    Ok(amount)
+/// The amount paid to each verified quote, failing if any of the payments is invalid.
+fn paid_quotes(
+    payment_verification_results: impl IntoIterator<
+        Item = interface::IPaymentVault::PaymentVerificationResult,
+    >,
+) -> Result<Vec<(QuoteHash, Amount)>, error::Error> {
+    payment_verification_results
+        .into_iter()
+        .map(|payment_verification_result| {
+            // TODO we currently fail on a single invalid payment, maybe we should deal with this in a different way
+            if payment_verification_result.isValid {
+                Ok((
+                    payment_verification_result.quoteHash,
+                    payment_verification_result.amountPaid,
+                ))
+            } else {
+                Err(error::Error::PaymentInvalid)
+            }
+        })
+        .collect()
 }
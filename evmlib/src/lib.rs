@@ -23,6 +23,7 @@ pub mod cryptography;
 #[cfg(feature = "external-signer")]
 pub mod external_signer;
 pub mod quoting_metrics;
+pub mod signer;
 pub mod testnet;
 pub mod utils;
 pub mod wallet;
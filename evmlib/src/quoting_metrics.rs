@@ -13,6 +13,10 @@ use std::fmt::{Debug, Formatter, Result as FmtResult};
 /// Quoting metrics used to generate a quote, or to track peer's status.
 #[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct QuotingMetrics {
+    /// the type of the data to be stored, as its index in the `DataTypes` of the protocol
+    pub data_type: u32,
+    /// the size in bytes of the data to be stored
+    pub data_size: usize,
     /// the records stored
     pub close_records_stored: usize,
     /// the max_records configured
@@ -32,6 +36,8 @@ impl QuotingMetrics {
     /// construct an empty QuotingMetrics
     pub fn new() -> Self {
         Self {
+            data_type: 0,
+            data_size: 0,
             close_records_stored: 0,
             max_records: 0,
             received_payment_count: 0,
@@ -52,7 +58,7 @@ impl Debug for QuotingMetrics {
     fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
         let density_u256 = self.network_density.map(U256::from_be_bytes);
 
-        write!(formatter, "QuotingMetrics {{ close_records_stored: {}, max_records: {}, received_payment_count: {}, live_time: {}, network_density: {density_u256:?}, network_size: {:?} }}",
-               self.close_records_stored, self.max_records, self.received_payment_count, self.live_time, self.network_size)
+        write!(formatter, "QuotingMetrics {{ data_type: {}, data_size: {}, close_records_stored: {}, max_records: {}, received_payment_count: {}, live_time: {}, network_density: {density_u256:?}, network_size: {:?} }}",
+               self.data_type, self.data_size, self.close_records_stored, self.max_records, self.received_payment_count, self.live_time, self.network_size)
     }
 }
@@ -0,0 +1,190 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Signers that a [`Wallet`](crate::wallet::Wallet) can pay with, so that the key does not have
+//! to live on the machine doing the uploads.
+
+use crate::common::Address;
+use alloy::consensus::{SignableTransaction, TxEnvelope};
+use alloy::eips::eip2718::Decodable2718;
+use alloy::network::TxSigner;
+use alloy::primitives::{Bytes, PrimitiveSignature};
+use alloy::rpc::client::{ClientBuilder, RpcClient};
+use alloy::rpc::types::TransactionRequest;
+use alloy::signers::local::{LocalSignerError, PrivateKeySigner};
+use alloy::signers::Error;
+use alloy::transports::http::{reqwest, Client, Http};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Signs the transactions of EVM payments on behalf of a single account.
+#[async_trait]
+pub trait PaymentSigner: Send + Sync {
+    /// The address of the account this signer signs for.
+    fn address(&self) -> Address;
+
+    /// Sign a transaction sent from [`PaymentSigner::address`].
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> Result<PrimitiveSignature, Error>;
+}
+
+/// A private key held in memory.
+#[async_trait]
+impl PaymentSigner for PrivateKeySigner {
+    fn address(&self) -> Address {
+        PrivateKeySigner::address(self)
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> Result<PrimitiveSignature, Error> {
+        alloy::network::TxSignerSync::sign_transaction_sync(self, tx)
+    }
+}
+
+/// Load the private key of a keystore v3 JSON file, as written by geth, clef and most wallets.
+pub fn keystore_signer(
+    path: &Path,
+    password: impl AsRef<[u8]>,
+) -> Result<PrivateKeySigner, LocalSignerError> {
+    debug!("Decrypting keystore at {path:?}");
+    PrivateKeySigner::decrypt_keystore(path, password)
+}
+
+/// A signer behind a JSON-RPC endpoint that implements `eth_signTransaction`, such as clef or a
+/// node with an unlocked account. The key never leaves the remote signer.
+#[derive(Clone, Debug)]
+pub struct RemoteSigner {
+    address: Address,
+    client: RpcClient<Http<Client>>,
+}
+
+/// `eth_signTransaction` returns the raw signed transaction, some signers wrap it in an object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SignTransactionResponse {
+    Raw(Bytes),
+    WithTx { raw: Bytes },
+}
+
+impl RemoteSigner {
+    /// Sign for `address` using the signer at `url`.
+    pub fn new(url: reqwest::Url, address: Address) -> Self {
+        Self {
+            address,
+            client: ClientBuilder::default().http(url),
+        }
+    }
+}
+
+#[async_trait]
+impl PaymentSigner for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> Result<PrimitiveSignature, Error> {
+        let request = transaction_request(self.address, tx);
+        debug!(
+            "Requesting remote signature for transaction from {}",
+            self.address
+        );
+
+        let response: SignTransactionResponse = self
+            .client
+            .request("eth_signTransaction", (request,))
+            .await
+            .map_err(Error::other)?;
+        let raw = match response {
+            SignTransactionResponse::Raw(raw) | SignTransactionResponse::WithTx { raw } => raw,
+        };
+
+        let signed = TxEnvelope::decode_2718(&mut raw.as_ref()).map_err(Error::other)?;
+        // Only hand out the signature if it is for the transaction we asked to sign.
+        if signed.signature_hash() != tx.signature_hash() {
+            error!("Remote signer returned a signature for a different transaction");
+            return Err(Error::other(
+                "remote signer signed a different transaction than requested",
+            ));
+        }
+
+        Ok(*signed.signature())
+    }
+}
+
+/// The JSON-RPC form of a transaction that is ready to be signed.
+fn transaction_request(
+    from: Address,
+    tx: &dyn SignableTransaction<PrimitiveSignature>,
+) -> TransactionRequest {
+    TransactionRequest {
+        from: Some(from),
+        to: Some(tx.kind()),
+        gas_price: tx.gas_price(),
+        max_fee_per_gas: tx.gas_price().is_none().then(|| tx.max_fee_per_gas()),
+        max_priority_fee_per_gas: tx.max_priority_fee_per_gas(),
+        gas: Some(tx.gas_limit()),
+        value: Some(tx.value()),
+        input: tx.input().clone().into(),
+        nonce: Some(tx.nonce()),
+        chain_id: tx.chain_id(),
+        access_list: tx.access_list().cloned(),
+        transaction_type: Some(tx.ty()),
+        ..Default::default()
+    }
+}
+
+/// Lets alloy providers sign with a [`PaymentSigner`].
+pub(crate) struct ProviderSigner(pub(crate) Arc<dyn PaymentSigner>);
+
+#[async_trait]
+impl TxSigner<PrimitiveSignature> for ProviderSigner {
+    fn address(&self) -> Address {
+        self.0.address()
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> Result<PrimitiveSignature, Error> {
+        self.0.sign_transaction(tx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystore_signer() {
+        let dir = temp_keystore_dir();
+        let (signer, uuid) =
+            PrivateKeySigner::new_keystore(&dir, &mut rand::thread_rng(), "password", None)
+                .unwrap();
+
+        let loaded = keystore_signer(&dir.join(&uuid), "password").unwrap();
+        assert_eq!(PaymentSigner::address(&loaded), signer.address());
+        assert!(keystore_signer(&dir.join(&uuid), "wrong password").is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn temp_keystore_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("evmlib-keystore-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
@@ -11,6 +11,7 @@ use crate::contract::network_token::NetworkToken;
 use crate::contract::payment_vault::handler::PaymentVaultHandler;
 use crate::contract::payment_vault::MAX_TRANSFERS_PER_TRANSACTION;
 use crate::contract::{network_token, payment_vault};
+use crate::signer::{keystore_signer, PaymentSigner, ProviderSigner};
 use crate::utils::http_provider;
 use crate::{Network, TX_TIMEOUT};
 use alloy::hex::ToHexExt;
@@ -20,10 +21,11 @@ use alloy::providers::fillers::{
 };
 use alloy::providers::{Identity, Provider, ProviderBuilder, ReqwestProvider};
 use alloy::rpc::types::TransactionRequest;
-use alloy::signers::local::{LocalSigner, PrivateKeySigner};
+use alloy::signers::local::{LocalSigner, LocalSignerError, PrivateKeySigner};
 use alloy::transports::http::{reqwest, Client, Http};
 use alloy::transports::{RpcError, TransportErrorKind};
 use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::Arc;
 
 #[derive(thiserror::Error, Debug)]
@@ -32,6 +34,8 @@ pub enum Error {
     InsufficientTokensForQuotes(Amount, Amount),
     #[error("Private key is invalid")]
     PrivateKeyInvalid,
+    #[error("Failed to decrypt keystore: {0}")]
+    Keystore(#[from] LocalSignerError),
     #[error(transparent)]
     RpcError(#[from] RpcError<TransportErrorKind>),
     #[error("Network token contract error: {0}")]
@@ -40,10 +44,22 @@ pub enum Error {
     ChunkPaymentsContract(#[from] payment_vault::error::Error),
 }
 
+/// How the payment vault gets allowed to spend the wallet's tokens when paying for quotes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ApprovalMode {
+    /// Approve the payment vault once to spend all of the wallet's tokens.
+    #[default]
+    Unlimited,
+    /// Approve the payment vault to spend the exact total of each payment, in a transaction of
+    /// its own sent right before paying.
+    Exact,
+}
+
 #[derive(Clone)]
 pub struct Wallet {
     wallet: EthereumWallet,
     network: Network,
+    approval_mode: ApprovalMode,
     lock: Arc<tokio::sync::Mutex<()>>,
 }
 
@@ -53,19 +69,51 @@ impl Wallet {
         Self {
             wallet,
             network,
+            approval_mode: ApprovalMode::default(),
             lock: Arc::new(tokio::sync::Mutex::new(())),
         }
     }
 
     /// Convenience function that creates a new Wallet with a random EthereumWallet.
     pub fn new_with_random_wallet(network: Network) -> Self {
-        Self::new(network, random())
+        Self::new_with_signer(network, LocalSigner::random())
     }
 
     /// Creates a new Wallet based on the given Ethereum private key. It will fail with Error::PrivateKeyInvalid if private_key is invalid.
     pub fn new_from_private_key(network: Network, private_key: &str) -> Result<Self, Error> {
-        let wallet = from_private_key(private_key)?;
-        Ok(Self::new(network, wallet))
+        let signer = signer_from_private_key(private_key)?;
+        Ok(Self::new_with_signer(network, signer))
+    }
+
+    /// Creates a new Wallet based on the private key in a keystore v3 JSON file.
+    pub fn new_from_keystore(
+        network: Network,
+        path: &Path,
+        password: impl AsRef<[u8]>,
+    ) -> Result<Self, Error> {
+        let signer = keystore_signer(path, password)?;
+        Ok(Self::new_with_signer(network, signer))
+    }
+
+    /// Creates a new Wallet that signs its payments with the given signer, for example a
+    /// [`RemoteSigner`](crate::signer::RemoteSigner) keeping the key on another machine.
+    pub fn new_with_payment_signer(network: Network, signer: Arc<dyn PaymentSigner>) -> Self {
+        Self::new(network, EthereumWallet::new(ProviderSigner(signer)))
+    }
+
+    fn new_with_signer(network: Network, signer: PrivateKeySigner) -> Self {
+        Self::new_with_payment_signer(network, Arc::new(signer))
+    }
+
+    /// Use the given approval mode when paying for quotes.
+    pub fn with_approval_mode(mut self, approval_mode: ApprovalMode) -> Self {
+        self.approval_mode = approval_mode;
+        self
+    }
+
+    /// Returns the approval mode used when paying for quotes.
+    pub fn approval_mode(&self) -> ApprovalMode {
+        self.approval_mode
     }
 
     /// Returns the address of this wallet.
@@ -126,7 +174,17 @@ impl Wallet {
         &self,
         quote_payments: I,
     ) -> Result<BTreeMap<QuoteHash, TxHash>, PayForQuotesError> {
-        pay_for_quotes(self.wallet.clone(), &self.network, quote_payments).await
+        let allowance = match self.approval_mode {
+            ApprovalMode::Unlimited => Allowance::Unlimited,
+            ApprovalMode::Exact => Allowance::Exact,
+        };
+        pay_for_quotes_with_allowance(
+            self.wallet.clone(),
+            &self.network,
+            quote_payments,
+            allowance,
+        )
+        .await
     }
 
     /// Build a provider using this wallet.
@@ -147,19 +205,18 @@ impl Wallet {
     }
 }
 
-/// Generate an EthereumWallet with a random private key.
-fn random() -> EthereumWallet {
-    let signer: PrivateKeySigner = LocalSigner::random();
-    EthereumWallet::from(signer)
-}
-
 /// Creates a wallet from a private key in HEX format.
+#[cfg(test)]
 fn from_private_key(private_key: &str) -> Result<EthereumWallet, Error> {
-    let signer: PrivateKeySigner = private_key.parse().map_err(|err| {
+    signer_from_private_key(private_key).map(EthereumWallet::from)
+}
+
+/// Creates a signer from a private key in HEX format.
+fn signer_from_private_key(private_key: &str) -> Result<PrivateKeySigner, Error> {
+    private_key.parse().map_err(|err| {
         error!("Error parsing private key: {err}");
         Error::PrivateKeyInvalid
-    })?;
-    Ok(EthereumWallet::from(signer))
+    })
 }
 
 // TODO(optimization): Find a way to reuse/persist contracts and/or a provider without the wallet nonce going out of sync
@@ -286,10 +343,27 @@ pub struct PayForQuotesError(pub Error, pub BTreeMap<QuoteHash, TxHash>);
 
 /// Use this wallet to pay for chunks in batched transfer transactions.
 /// If the amount of transfers is more than one transaction can contain, the transfers will be split up over multiple transactions.
+///
+/// If the payment vault is not allowed to spend enough tokens, it is approved to spend all of them.
 pub async fn pay_for_quotes<T: IntoIterator<Item = QuotePayment>>(
     wallet: EthereumWallet,
     network: &Network,
     payments: T,
+) -> Result<BTreeMap<QuoteHash, TxHash>, PayForQuotesError> {
+    pay_for_quotes_with_allowance(wallet, network, payments, Allowance::Unlimited).await
+}
+
+/// How to raise the allowance of the payment vault when it is short, see [`ApprovalMode`].
+enum Allowance {
+    Unlimited,
+    Exact,
+}
+
+async fn pay_for_quotes_with_allowance<T: IntoIterator<Item = QuotePayment>>(
+    wallet: EthereumWallet,
+    network: &Network,
+    payments: T,
+    allowance_mode: Allowance,
 ) -> Result<BTreeMap<QuoteHash, TxHash>, PayForQuotesError> {
     let payments: Vec<_> = payments.into_iter().collect();
     info!("Paying for quotes of len: {}", payments.len());
@@ -318,17 +392,22 @@ pub async fn pay_for_quotes<T: IntoIterator<Item = QuotePayment>>(
     .await
     .map_err(|err| PayForQuotesError(Error::from(err), Default::default()))?;
 
-    // TODO: Get rid of approvals altogether, by using permits or whatever..
     if allowance < total_amount_to_be_paid {
-        // Approve the contract to spend all the client's tokens.
-        approve_to_spend_tokens(
-            wallet.clone(),
-            network,
-            *network.data_payments_address(),
-            U256::MAX,
-        )
-        .await
-        .map_err(|err| PayForQuotesError(Error::from(err), Default::default()))?;
+        let spender = *network.data_payments_address();
+        let result = match allowance_mode {
+            // Approve the contract to spend all the client's tokens.
+            Allowance::Unlimited => {
+                approve_to_spend_tokens(wallet.clone(), network, spender, U256::MAX)
+                    .await
+                    .map_err(Error::from)
+            }
+            Allowance::Exact => {
+                approve_to_spend_tokens(wallet.clone(), network, spender, total_amount_to_be_paid)
+                    .await
+                    .map_err(Error::from)
+            }
+        };
+        result.map_err(|err| PayForQuotesError(err, Default::default()))?;
     }
 
     let provider = http_provider_with_wallet(network.rpc_url().clone(), wallet);
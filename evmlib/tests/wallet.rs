@@ -7,13 +7,15 @@ use alloy::primitives::utils::parse_ether;
 use alloy::providers::ext::AnvilApi;
 use alloy::providers::{ProviderBuilder, WalletProvider};
 use alloy::signers::local::{LocalSigner, PrivateKeySigner};
-use evmlib::common::{Amount, TxHash};
+use evmlib::common::{Address, Amount, TxHash};
 use evmlib::contract::payment_vault::{verify_data_payment, MAX_TRANSFERS_PER_TRANSACTION};
 use evmlib::quoting_metrics::QuotingMetrics;
+use evmlib::signer::RemoteSigner;
 use evmlib::testnet::{deploy_data_payments_contract, deploy_network_token_contract, start_node};
-use evmlib::wallet::{transfer_tokens, wallet_address, Wallet};
+use evmlib::wallet::{transfer_tokens, wallet_address, ApprovalMode, Wallet};
 use evmlib::{CustomNetwork, Network};
 use std::collections::HashSet;
+use std::sync::Arc;
 
 #[allow(clippy::unwrap_used)]
 async fn local_testnet() -> (AnvilInstance, Network, EthereumWallet) {
@@ -38,8 +40,12 @@ async fn local_testnet() -> (AnvilInstance, Network, EthereumWallet) {
 async fn funded_wallet(network: &Network, genesis_wallet: EthereumWallet) -> Wallet {
     let signer: PrivateKeySigner = LocalSigner::random();
     let wallet = EthereumWallet::from(signer);
-    let account = wallet_address(&wallet);
+    fund(network, genesis_wallet, wallet_address(&wallet)).await;
+    Wallet::new(network.clone(), wallet)
+}
 
+#[allow(clippy::unwrap_used)]
+async fn fund(network: &Network, genesis_wallet: EthereumWallet, account: Address) {
     let provider = ProviderBuilder::new()
         .with_recommended_fillers()
         .wallet(genesis_wallet.clone())
@@ -60,8 +66,6 @@ async fn funded_wallet(network: &Network, genesis_wallet: EthereumWallet) -> Wal
     )
     .await
     .unwrap();
-
-    Wallet::new(network.clone(), wallet)
 }
 
 #[tokio::test]
@@ -101,3 +105,33 @@ async fn test_pay_for_quotes_and_data_payment_verification() {
         );
     }
 }
+
+#[tokio::test]
+async fn test_pay_for_quotes_with_exact_approval() {
+    let (_anvil, network, genesis_wallet) = local_testnet().await;
+    let wallet = funded_wallet(&network, genesis_wallet)
+        .await
+        .with_approval_mode(ApprovalMode::Exact);
+    let spender = *network.data_payments_address();
+
+    let quote_payments: Vec<_> = (0..3).map(|_| random_quote_payment()).collect();
+    let _ = wallet.pay_for_quotes(quote_payments).await.unwrap();
+
+    // exactly the paid amount was approved, so nothing is left to spend
+    assert_eq!(wallet.token_allowance(spender).await.unwrap(), Amount::ZERO);
+}
+
+#[tokio::test]
+async fn test_pay_for_quotes_with_remote_signer() {
+    let (anvil, network, genesis_wallet) = local_testnet().await;
+    // anvil's accounts are unlocked, so the node stands in for the remote signer
+    let account = anvil.addresses()[1];
+    fund(&network, genesis_wallet, account).await;
+    let signer = RemoteSigner::new(network.rpc_url().clone(), account);
+    let wallet = Wallet::new_with_payment_signer(network.clone(), Arc::new(signer));
+    assert_eq!(wallet.address(), account);
+
+    let quote_payments: Vec<_> = (0..3).map(|_| random_quote_payment()).collect();
+    let tx_hashes = wallet.pay_for_quotes(quote_payments.clone()).await.unwrap();
+    assert_eq!(tx_hashes.len(), quote_payments.len());
+}